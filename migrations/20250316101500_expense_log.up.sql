@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE
+    expense_log (
+        id SERIAL PRIMARY KEY,
+        -- No foreign key, the log should outlive deleted expenses
+        expense_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        action TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE INDEX expense_log_expense_id_idx ON expense_log (expense_id);
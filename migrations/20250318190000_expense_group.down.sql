@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE expense
+DROP COLUMN group_id;
+
+DROP TABLE group_member;
+DROP TABLE expense_group;
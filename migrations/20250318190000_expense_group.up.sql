@@ -0,0 +1,31 @@
+-- Add up migration script here
+CREATE TABLE
+    expense_group (
+        id SERIAL PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE TABLE
+    group_member (
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        PRIMARY KEY (group_id, user_id)
+    );
+
+-- Everything up until now has been shared within one household
+INSERT INTO
+    expense_group (name)
+VALUES
+    ('Household');
+
+INSERT INTO
+    group_member (group_id, user_id)
+SELECT
+    1,
+    id
+FROM
+    users;
+
+ALTER TABLE expense
+ADD COLUMN group_id INTEGER NOT NULL DEFAULT 1 REFERENCES expense_group (id);
@@ -0,0 +1,21 @@
+-- Add down migration script here
+UPDATE expense
+SET
+    category_id = 4
+WHERE
+    category_id > 43;
+
+UPDATE expense_category
+SET
+    parent_id = NULL;
+
+DELETE FROM expense_category
+WHERE
+    id > 43;
+
+ALTER TABLE expense_category
+DROP COLUMN parent_id,
+DROP COLUMN group_id,
+DROP COLUMN icon,
+DROP COLUMN color,
+DROP COLUMN archived_at;
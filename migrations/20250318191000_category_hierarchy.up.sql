@@ -0,0 +1,155 @@
+-- Add up migration script here
+-- The seeded categories were inserted with explicit ids
+SELECT
+    setval(
+        'expense_category_id_seq',
+        (
+            SELECT
+                MAX(id)
+            FROM
+                expense_category
+        )
+    );
+
+ALTER TABLE expense_category
+ADD COLUMN parent_id INTEGER REFERENCES expense_category (id),
+ADD COLUMN group_id INTEGER REFERENCES expense_group (id) ON DELETE CASCADE,
+ADD COLUMN icon TEXT,
+ADD COLUMN color TEXT,
+ADD COLUMN archived_at TIMESTAMPTZ;
+
+INSERT INTO
+    expense_category (name, icon, color)
+VALUES
+    ('Utilities', 'bolt', '#f59e0b'),
+    ('Entertainment', 'movie', '#8b5cf6'),
+    ('Food and drink', 'tools-kitchen', '#ef4444'),
+    ('Home', 'home', '#10b981'),
+    ('Transportation', 'car', '#3b82f6'),
+    ('Life', 'heart', '#ec4899');
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Utilities'
+    )
+WHERE
+    id IN (1, 2, 3, 5, 6, 7);
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Entertainment'
+    )
+WHERE
+    id IN (9, 10, 11, 13);
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Food and drink'
+    )
+WHERE
+    id IN (14, 15, 16);
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Home'
+    )
+WHERE
+    id IN (18, 19, 20, 21, 22, 24, 25, 26);
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Transportation'
+    )
+WHERE
+    id IN (27, 28, 29, 30, 31, 33, 34, 35);
+
+UPDATE expense_category
+SET
+    parent_id = (
+        SELECT
+            id
+        FROM
+            expense_category
+        WHERE
+            name = 'Life'
+    )
+WHERE
+    id IN (36, 37, 38, 39, 40, 41, 43);
+
+UPDATE expense_category AS ec
+SET
+    icon = icons.icon
+FROM
+    (
+        VALUES
+            (1, 'wash-machine'),
+            (2, 'bolt'),
+            (3, 'flame'),
+            (4, 'notes'),
+            (5, 'trash'),
+            (6, 'wifi'),
+            (7, 'droplet'),
+            (8, 'notes'),
+            (9, 'device-gamepad'),
+            (10, 'movie'),
+            (11, 'music'),
+            (13, 'ball-football'),
+            (14, 'tools-kitchen'),
+            (15, 'shopping-cart'),
+            (16, 'bottle'),
+            (18, 'device-mobile'),
+            (19, 'armchair'),
+            (20, 'spray'),
+            (21, 'hammer'),
+            (22, 'building-bank'),
+            (24, 'cat'),
+            (25, 'building-bank'),
+            (26, 'notes'),
+            (27, 'bike'),
+            (28, 'bus'),
+            (29, 'car'),
+            (30, 'gas-station'),
+            (31, 'home'),
+            (33, 'parking'),
+            (34, 'plane'),
+            (35, 'car'),
+            (36, 'baby-carriage'),
+            (37, 'shirt'),
+            (38, 'school'),
+            (39, 'gift'),
+            (40, 'note'),
+            (41, 'hospital'),
+            (43, 'receipt-tax')
+    ) AS icons (id, icon)
+WHERE
+    ec.id = icons.id;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE
+    category_rule (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        -- Stored in lowercase, matched against the lowercased expense name
+        keyword TEXT NOT NULL,
+        category_id INTEGER NOT NULL REFERENCES expense_category (id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        UNIQUE (group_id, keyword)
+    );
@@ -0,0 +1,27 @@
+-- Add up migration script here
+CREATE TABLE
+    budget (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        -- Spending in sub-categories counts towards the budget of their parent
+        category_id INTEGER NOT NULL REFERENCES expense_category (id) ON DELETE CASCADE,
+        period TEXT NOT NULL CHECK (period IN ('week', 'month', 'year')),
+        amount INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        -- Only count what this user consumed
+        user_id INTEGER REFERENCES users (id),
+        -- Percentages of the amount at which to alert
+        alert_thresholds INTEGER[] NOT NULL DEFAULT '{80,100}',
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE TABLE
+    budget_alert (
+        budget_id INTEGER NOT NULL REFERENCES budget (id) ON DELETE CASCADE,
+        period_start TIMESTAMPTZ NOT NULL,
+        threshold INTEGER NOT NULL,
+        expense_id INTEGER NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        PRIMARY KEY (budget_id, period_start, threshold)
+    );
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE balance_reminder;
+DROP TABLE notification_preference;
@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE
+    notification_preference (
+        user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+        expense_created BOOLEAN NOT NULL DEFAULT TRUE,
+        settlement_received BOOLEAN NOT NULL DEFAULT TRUE,
+        negative_balance BOOLEAN NOT NULL DEFAULT TRUE,
+        -- In minor units, how far below zero the balance has to be
+        negative_balance_threshold INTEGER NOT NULL DEFAULT 50000,
+        negative_balance_days INTEGER NOT NULL DEFAULT 7
+    );
+
+CREATE TABLE
+    balance_reminder (
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        currency TEXT NOT NULL,
+        sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        PRIMARY KEY (user_id, group_id, currency)
+    );
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE vapid_key;
+DROP TABLE push_subscription;
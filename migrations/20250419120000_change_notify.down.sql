@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TRIGGER expense_category_change ON expense_category;
+DROP FUNCTION notify_category_change;
+DROP TRIGGER expense_change ON expense;
+DROP FUNCTION notify_expense_change;
@@ -0,0 +1,52 @@
+-- Add up migration script here
+-- Changes are published on the ledger_changes channel once committed, so every
+-- server instance can forward them to its clients.
+CREATE OR REPLACE FUNCTION notify_expense_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    PERFORM pg_notify('ledger_changes', json_build_object(
+        'entity', CASE WHEN changed.is_payment THEN 'settlement' ELSE 'expense' END,
+        'action', lower(TG_OP),
+        'id', changed.id,
+        'group_id', changed.group_id
+    )::TEXT);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_change
+AFTER INSERT OR UPDATE OR DELETE ON expense
+FOR EACH ROW EXECUTE FUNCTION notify_expense_change();
+
+CREATE OR REPLACE FUNCTION notify_category_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    PERFORM pg_notify('ledger_changes', json_build_object(
+        'entity', 'category',
+        'action', lower(TG_OP),
+        'id', changed.id,
+        'group_id', changed.group_id
+    )::TEXT);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_category_change
+AFTER INSERT OR UPDATE OR DELETE ON expense_category
+FOR EACH ROW EXECUTE FUNCTION notify_category_change();
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP TABLE sync_mutation;
+DROP TRIGGER expense_category_sync ON expense_category;
+DROP FUNCTION sync_category_change;
+DROP TRIGGER account_share_sync ON account_share;
+DROP FUNCTION sync_account_share_change;
+DROP TRIGGER expense_sync ON expense;
+DROP FUNCTION sync_expense_change;
+DROP FUNCTION record_sync_change;
+DROP TABLE sync_change;
@@ -0,0 +1,97 @@
+-- Add up migration script here
+-- The latest change of every expense and category, stamped with the id of the
+-- transaction that made it. Deleted rows keep their entry as a tombstone so
+-- that offline clients learn about the deletion on their next sync.
+CREATE TABLE sync_change (
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    group_id INTEGER,
+    tx_id XID8 NOT NULL DEFAULT pg_current_xact_id(),
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entity, entity_id)
+);
+
+CREATE INDEX sync_change_tx_id_idx ON sync_change (tx_id);
+
+CREATE OR REPLACE FUNCTION record_sync_change(
+    changed_entity TEXT,
+    changed_id INTEGER,
+    changed_group_id INTEGER
+) RETURNS VOID AS $$
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, group_id)
+    VALUES (changed_entity, changed_id, changed_group_id)
+    ON CONFLICT (entity, entity_id) DO UPDATE
+    SET
+        group_id = COALESCE(EXCLUDED.group_id, sync_change.group_id),
+        tx_id = EXCLUDED.tx_id,
+        changed_at = EXCLUDED.changed_at;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION sync_expense_change() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM record_sync_change('expense', OLD.id, OLD.group_id);
+    ELSE
+        PERFORM record_sync_change('expense', NEW.id, NEW.group_id);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_sync
+AFTER INSERT OR UPDATE OR DELETE ON expense
+FOR EACH ROW EXECUTE FUNCTION sync_expense_change();
+
+-- Shares are synced as part of their expense. When the expense itself is
+-- deleted its own trigger records the change.
+CREATE OR REPLACE FUNCTION sync_account_share_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed_expense_id INTEGER;
+    changed_group_id INTEGER;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed_expense_id := OLD.expense_id;
+    ELSE
+        changed_expense_id := NEW.expense_id;
+    END IF;
+
+    SELECT group_id INTO changed_group_id FROM expense WHERE id = changed_expense_id;
+    IF FOUND THEN
+        PERFORM record_sync_change('expense', changed_expense_id, changed_group_id);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER account_share_sync
+AFTER INSERT OR UPDATE OR DELETE ON account_share
+FOR EACH ROW EXECUTE FUNCTION sync_account_share_change();
+
+CREATE OR REPLACE FUNCTION sync_category_change() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM record_sync_change('category', OLD.id, OLD.group_id);
+    ELSE
+        PERFORM record_sync_change('category', NEW.id, NEW.group_id);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_category_sync
+AFTER INSERT OR UPDATE OR DELETE ON expense_category
+FOR EACH ROW EXECUTE FUNCTION sync_category_change();
+
+-- Mutations sent by offline clients, keyed by the UUID the client generated,
+-- so that a batch retried after a lost response is not applied twice.
+CREATE TABLE sync_mutation (
+    client_id UUID PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expense_id INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
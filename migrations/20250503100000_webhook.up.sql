@@ -0,0 +1,36 @@
+-- Add up migration script here
+CREATE TABLE
+    webhook (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        url TEXT NOT NULL,
+        -- Key for the HMAC signature sent with every delivery
+        secret TEXT NOT NULL,
+        -- Types of ledger events to deliver, e.g. expense_created
+        events TEXT[] NOT NULL,
+        description TEXT,
+        active BOOLEAN NOT NULL DEFAULT TRUE,
+        created_by INTEGER NOT NULL REFERENCES users (id),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE TABLE
+    webhook_delivery (
+        id SERIAL PRIMARY KEY,
+        webhook_id INTEGER NOT NULL REFERENCES webhook (id) ON DELETE CASCADE,
+        event TEXT NOT NULL,
+        payload TEXT NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+        attempts INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        last_attempt_at TIMESTAMPTZ,
+        response_status INTEGER,
+        last_error TEXT,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE INDEX webhook_delivery_webhook_id_idx ON webhook_delivery (webhook_id, created_at DESC);
+
+CREATE INDEX webhook_delivery_pending_idx ON webhook_delivery (next_attempt_at)
+WHERE
+    status = 'pending';
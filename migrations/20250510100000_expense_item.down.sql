@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE expense_extra;
+DROP TABLE expense_item_share;
+DROP TABLE expense_item;
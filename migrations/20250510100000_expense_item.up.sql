@@ -0,0 +1,35 @@
+-- Add up migration script here
+CREATE TABLE
+    expense_item (
+        id SERIAL PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expense (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        amount INTEGER NOT NULL CHECK (amount > 0),
+        -- Falls back to the category of the expense when not set
+        category_id INTEGER REFERENCES expense_category (id),
+        UNIQUE (expense_id, position)
+    );
+
+-- What each participant of an item consumed, including their part of the
+-- receipt's extras. Summed per user these make up the consumed side of the
+-- expense's account_share rows.
+CREATE TABLE
+    expense_item_share (
+        item_id INTEGER NOT NULL REFERENCES expense_item (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id),
+        weight INTEGER NOT NULL CHECK (weight >= 0),
+        consumed INTEGER NOT NULL,
+        PRIMARY KEY (item_id, user_id)
+    );
+
+-- Tax, tip and discounts that apply to the receipt as a whole
+CREATE TABLE
+    expense_extra (
+        id SERIAL PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expense (id) ON DELETE CASCADE,
+        kind TEXT NOT NULL CHECK (kind IN ('tax', 'tip', 'discount')),
+        amount INTEGER NOT NULL CHECK (amount > 0)
+    );
+
+CREATE INDEX expense_extra_expense_id_idx ON expense_extra (expense_id);
@@ -0,0 +1,12 @@
+-- Add down migration script here
+DROP TABLE invitation;
+DROP TABLE guest;
+
+DELETE FROM users
+WHERE is_guest;
+
+ALTER TABLE users
+DROP COLUMN is_guest;
+
+ALTER TABLE users
+ALTER COLUMN email SET NOT NULL;
@@ -0,0 +1,36 @@
+-- Add up migration script here
+-- Guests are users without an account, so that they can take part in
+-- expenses and balances like everyone else
+ALTER TABLE users
+ALTER COLUMN email DROP NOT NULL;
+
+ALTER TABLE users
+ADD COLUMN is_guest BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE
+    guest (
+        user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        -- Only for getting in touch, users.email stays free for the account
+        -- the guest is merged into
+        email TEXT,
+        created_by INTEGER NOT NULL REFERENCES users (id),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+    );
+
+CREATE TABLE
+    invitation (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        -- The guest that is merged into whoever accepts the invitation
+        guest_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+        token TEXT NOT NULL UNIQUE,
+        email TEXT,
+        created_by INTEGER NOT NULL REFERENCES users (id),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        expires_at TIMESTAMPTZ NOT NULL,
+        accepted_by INTEGER REFERENCES users (id),
+        accepted_at TIMESTAMPTZ
+    );
+
+CREATE INDEX invitation_group_id_idx ON invitation (group_id);
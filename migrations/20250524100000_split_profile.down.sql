@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE split_profile_weight;
+DROP TABLE split_profile;
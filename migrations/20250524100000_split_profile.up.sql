@@ -0,0 +1,31 @@
+-- Add up migration script here
+-- Named presets for dividing expenses between the members of a group. Used
+-- for new expenses without explicit shares: the profile of the expense's
+-- category first, then the profile of its parent and finally the group's
+-- default profile.
+CREATE TABLE
+    split_profile (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        category_id INTEGER REFERENCES expense_category (id) ON DELETE CASCADE,
+        is_default BOOLEAN NOT NULL DEFAULT FALSE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        CHECK (NOT (is_default AND category_id IS NOT NULL))
+    );
+
+CREATE UNIQUE INDEX split_profile_default_idx ON split_profile (group_id)
+WHERE
+    is_default;
+
+CREATE UNIQUE INDEX split_profile_category_idx ON split_profile (group_id, category_id)
+WHERE
+    category_id IS NOT NULL;
+
+CREATE TABLE
+    split_profile_weight (
+        profile_id INTEGER NOT NULL REFERENCES split_profile (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        weight INTEGER NOT NULL CHECK (weight >= 0),
+        PRIMARY KEY (profile_id, user_id)
+    );
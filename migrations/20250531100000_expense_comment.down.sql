@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TRIGGER expense_comment_sync ON expense_comment;
+DROP FUNCTION sync_comment_change;
+DROP TRIGGER expense_comment_change ON expense_comment;
+DROP FUNCTION notify_comment_change;
+ALTER TABLE notification_preference
+DROP COLUMN expense_commented;
+DROP TABLE expense_comment;
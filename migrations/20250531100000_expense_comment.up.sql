@@ -0,0 +1,72 @@
+-- Add up migration script here
+CREATE TABLE
+    expense_comment (
+        id SERIAL PRIMARY KEY,
+        expense_id INTEGER NOT NULL REFERENCES expense (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        body TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        -- Set when the author edits the comment
+        updated_at TIMESTAMPTZ
+    );
+
+CREATE INDEX expense_comment_expense_id_idx ON expense_comment (expense_id);
+
+ALTER TABLE notification_preference
+ADD COLUMN expense_commented BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Comments are published on the same channel as the expenses they belong to.
+CREATE OR REPLACE FUNCTION notify_comment_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed RECORD;
+    changed_group_id INTEGER;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    SELECT group_id INTO changed_group_id FROM expense WHERE id = changed.expense_id;
+    IF FOUND THEN
+        PERFORM pg_notify('ledger_changes', json_build_object(
+            'entity', 'comment',
+            'action', lower(TG_OP),
+            'id', changed.id,
+            'group_id', changed_group_id,
+            'expense_id', changed.expense_id
+        )::TEXT);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_comment_change
+AFTER INSERT OR UPDATE OR DELETE ON expense_comment
+FOR EACH ROW EXECUTE FUNCTION notify_comment_change();
+
+-- The comment count is synced as part of the expense.
+CREATE OR REPLACE FUNCTION sync_comment_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed_expense_id INTEGER;
+    changed_group_id INTEGER;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed_expense_id := OLD.expense_id;
+    ELSE
+        changed_expense_id := NEW.expense_id;
+    END IF;
+
+    SELECT group_id INTO changed_group_id FROM expense WHERE id = changed_expense_id;
+    IF FOUND THEN
+        PERFORM record_sync_change('expense', changed_expense_id, changed_group_id);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER expense_comment_sync
+AFTER INSERT OR DELETE ON expense_comment
+FOR EACH ROW EXECUTE FUNCTION sync_comment_change();
@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX expense_tags_idx;
+ALTER TABLE expense
+DROP COLUMN longitude,
+DROP COLUMN latitude,
+DROP COLUMN place,
+DROP COLUMN merchant,
+DROP COLUMN tags,
+DROP COLUMN note;
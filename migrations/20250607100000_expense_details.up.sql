@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE expense
+ADD COLUMN note TEXT,
+ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+ADD COLUMN merchant TEXT,
+-- A name for where the expense was made, with optional coordinates
+ADD COLUMN place TEXT,
+ADD COLUMN latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90),
+ADD COLUMN longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180),
+ADD CHECK ((latitude IS NULL) = (longitude IS NULL));
+
+CREATE INDEX expense_tags_idx ON expense USING GIN (tags);
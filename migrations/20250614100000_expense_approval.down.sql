@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE expense_approval;
+ALTER TABLE expense
+DROP COLUMN status;
+ALTER TABLE expense_group
+DROP COLUMN approval_threshold;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+-- In minor units. Expenses of the group with a larger total have to be
+-- approved by their participants before they count towards balances.
+ALTER TABLE expense_group
+ADD COLUMN approval_threshold INTEGER CHECK (approval_threshold > 0);
+
+ALTER TABLE expense
+ADD COLUMN status TEXT NOT NULL DEFAULT 'confirmed' CHECK (status IN ('confirmed', 'pending', 'disputed'));
+
+-- One row per participant whose approval is needed. The expense is pending
+-- until all of them have approved and disputed once any of them disputes.
+CREATE TABLE
+    expense_approval (
+        expense_id INTEGER NOT NULL REFERENCES expense (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'disputed')),
+        reason TEXT,
+        decided_at TIMESTAMPTZ,
+        PRIMARY KEY (expense_id, user_id),
+        CHECK ((status = 'disputed') = (reason IS NOT NULL))
+    );
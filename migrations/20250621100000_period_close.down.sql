@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE period_close_balance;
+DROP TABLE period_close;
@@ -0,0 +1,22 @@
+-- Add up migration script here
+-- Expenses of a group created before `closed_until` can no longer be changed
+-- once the period is closed. Every close snapshots the balances at that point
+-- so statements don't have to be recomputed from the whole history.
+CREATE TABLE
+    period_close (
+        id SERIAL PRIMARY KEY,
+        group_id INTEGER NOT NULL REFERENCES expense_group (id) ON DELETE CASCADE,
+        closed_until TIMESTAMPTZ NOT NULL,
+        closed_by INTEGER NOT NULL REFERENCES users (id),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        UNIQUE (group_id, closed_until)
+    );
+
+CREATE TABLE
+    period_close_balance (
+        close_id INTEGER NOT NULL REFERENCES period_close (id) ON DELETE CASCADE,
+        user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+        currency TEXT NOT NULL,
+        balance BIGINT NOT NULL,
+        PRIMARY KEY (close_id, user_id, currency)
+    );
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX expense_refund_of_idx;
+ALTER TABLE expense
+DROP COLUMN refund_of;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- A refund is an expense with a negative total linked to the expense it
+-- refunds, so spending reports can net it against the original.
+ALTER TABLE expense
+ADD COLUMN refund_of INTEGER REFERENCES expense (id) ON DELETE CASCADE,
+ADD CONSTRAINT expense_refund_total_check CHECK (refund_of IS NULL OR total < 0);
+
+CREATE INDEX expense_refund_of_idx ON expense (refund_of)
+WHERE
+    refund_of IS NOT NULL;
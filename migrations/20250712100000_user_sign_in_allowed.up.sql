@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE users
+ADD COLUMN sign_in_allowed BOOLEAN NOT NULL DEFAULT FALSE;
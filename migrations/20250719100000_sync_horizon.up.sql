@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- The latest change whose tombstone has been purged. Clients that synced
+-- before it may have missed deletions and have to sync from scratch.
+CREATE TABLE sync_horizon (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    tx_id XID8 NOT NULL,
+    purged_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
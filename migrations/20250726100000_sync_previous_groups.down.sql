@@ -0,0 +1,19 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION record_sync_change(
+    changed_entity TEXT,
+    changed_id INTEGER,
+    changed_group_id INTEGER
+) RETURNS VOID AS $$
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, group_id)
+    VALUES (changed_entity, changed_id, changed_group_id)
+    ON CONFLICT (entity, entity_id) DO UPDATE
+    SET
+        group_id = COALESCE(EXCLUDED.group_id, sync_change.group_id),
+        tx_id = EXCLUDED.tx_id,
+        changed_at = EXCLUDED.changed_at;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE sync_change
+DROP COLUMN previous_group_ids;
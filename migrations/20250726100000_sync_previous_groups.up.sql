@@ -0,0 +1,34 @@
+-- Add up migration script here
+-- Groups an entity has been moved out of, so that their members learn that it
+-- is gone from their group on their next sync.
+ALTER TABLE sync_change
+ADD COLUMN previous_group_ids INTEGER[] NOT NULL DEFAULT '{}';
+
+CREATE OR REPLACE FUNCTION record_sync_change(
+    changed_entity TEXT,
+    changed_id INTEGER,
+    changed_group_id INTEGER
+) RETURNS VOID AS $$
+BEGIN
+    INSERT INTO sync_change (entity, entity_id, group_id)
+    VALUES (changed_entity, changed_id, changed_group_id)
+    ON CONFLICT (entity, entity_id) DO UPDATE
+    SET
+        group_id = COALESCE(EXCLUDED.group_id, sync_change.group_id),
+        previous_group_ids = CASE
+            WHEN EXCLUDED.group_id IS NULL
+                OR sync_change.group_id IS NULL
+                OR EXCLUDED.group_id = sync_change.group_id
+                THEN sync_change.previous_group_ids
+            ELSE array_append(
+                array_remove(
+                    array_remove(sync_change.previous_group_ids, sync_change.group_id),
+                    EXCLUDED.group_id
+                ),
+                sync_change.group_id
+            )
+        END,
+        tx_id = EXCLUDED.tx_id,
+        changed_at = EXCLUDED.changed_at;
+END;
+$$ LANGUAGE plpgsql;
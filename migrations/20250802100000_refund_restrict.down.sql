@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE expense
+DROP CONSTRAINT expense_refund_of_fkey,
+ADD CONSTRAINT expense_refund_of_fkey FOREIGN KEY (refund_of) REFERENCES expense (id) ON DELETE CASCADE;
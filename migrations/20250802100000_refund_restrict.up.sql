@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- An expense can't be deleted while it has refunds, so that every refund is
+-- deleted on its own, logged and published.
+ALTER TABLE expense
+DROP CONSTRAINT expense_refund_of_fkey,
+ADD CONSTRAINT expense_refund_of_fkey FOREIGN KEY (refund_of) REFERENCES expense (id);
@@ -0,0 +1,21 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION notify_expense_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    PERFORM pg_notify('ledger_changes', json_build_object(
+        'entity', CASE WHEN changed.is_payment THEN 'settlement' ELSE 'expense' END,
+        'action', lower(TG_OP),
+        'id', changed.id,
+        'group_id', changed.group_id
+    )::TEXT);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,32 @@
+-- Add up migration script here
+-- An expense moved to another group is gone from the old one, whose members
+-- are told that it was deleted.
+CREATE OR REPLACE FUNCTION notify_expense_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed RECORD;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+
+    IF TG_OP = 'UPDATE' AND OLD.group_id <> NEW.group_id THEN
+        PERFORM pg_notify('ledger_changes', json_build_object(
+            'entity', CASE WHEN OLD.is_payment THEN 'settlement' ELSE 'expense' END,
+            'action', 'delete',
+            'id', OLD.id,
+            'group_id', OLD.group_id
+        )::TEXT);
+    END IF;
+
+    PERFORM pg_notify('ledger_changes', json_build_object(
+        'entity', CASE WHEN changed.is_payment THEN 'settlement' ELSE 'expense' END,
+        'action', lower(TG_OP),
+        'id', changed.id,
+        'group_id', changed.group_id
+    )::TEXT);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -9,12 +9,14 @@ use log::warn;
 use oauth2::{CsrfToken, RefreshToken};
 use serde::{Deserialize, Serialize};
 use tower_sessions::Session;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use axum::extract::State;
 use oauth2::{PkceCodeChallenge, TokenResponse};
 use tracing::{event, Level};
 
 use crate::{
+    api::user::UserDto,
     db::user::User,
     service::auth_service::{AuthService, JostridTokenResponse},
 };
@@ -24,24 +26,44 @@ use crate::{server::application::App, service::auth_service::Credentials};
 pub const CSRF_STATE_KEY: &str = "oauth.csrf-state";
 pub const PKCE_CODE_VERIFIER: &str = "pkce.code-verifier";
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AuthzResp {
     code: String,
+    #[param(value_type = String)]
     state: CsrfToken,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 struct LoginResponseDto {
+    #[schema(value_type = UserDto)]
     user: User,
     #[serde(flatten)]
+    #[schema(value_type = TokenResponseSchema)]
     token: JostridTokenResponse,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// The OAuth 2 token response as flattened into [`LoginResponseDto`], only
+/// used to document it.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+struct TokenResponseSchema {
+    access_token: String,
+    token_type: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 struct RefreshRequestDto {
     refresh_token: String,
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(callback, redirect, refresh))]
+pub struct AuthApi;
+
 pub fn router() -> Router<App> {
     Router::new()
         .route("/callback", get(callback))
@@ -49,6 +71,14 @@ pub fn router() -> Router<App> {
         .route("/refresh", post(refresh))
 }
 
+/// Starts signing in, returning the Microsoft URL to send the browser to.
+#[utoipa::path(
+    get,
+    path = "/api/oauth/redirect",
+    tag = "auth",
+    security(()),
+    responses((status = 200, description = "Authorization URL", body = String))
+)]
 async fn redirect(State(app_state): State<App>, session: Session) -> String {
     let auth_service = AuthService::new(app_state.db, app_state.oauth_client);
 
@@ -67,6 +97,17 @@ async fn redirect(State(app_state): State<App>, session: Session) -> String {
     auth_url.to_string()
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/oauth/refresh",
+    tag = "auth",
+    security(()),
+    request_body = RefreshRequestDto,
+    responses(
+        (status = 200, body = LoginResponseDto),
+        (status = 403, description = "The token doesn't belong to a known user", body = String)
+    )
+)]
 async fn refresh(
     State(app_state): State<App>,
     Json(refresh_request): Json<RefreshRequestDto>,
@@ -113,6 +154,19 @@ async fn refresh(
     .into_response()
 }
 
+/// Where Microsoft redirects back to after signing in.
+#[utoipa::path(
+    get,
+    path = "/api/oauth/callback",
+    tag = "auth",
+    security(()),
+    params(AuthzResp),
+    responses(
+        (status = 200, body = LoginResponseDto),
+        (status = 400, description = "The session doesn't match the redirect"),
+        (status = 403, description = "The token doesn't belong to a known user", body = String)
+    )
+)]
 async fn callback(
     State(app_state): State<App>,
     session: Session,
@@ -1,17 +1,33 @@
-use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use jwt_authorizer::JwtClaims;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use crate::{
-    db::{self, balance::Balance},
+    db::{
+        self,
+        balance::{Balance, BalanceHistoryFilter, BalancePoint},
+        group::DEFAULT_GROUP_ID,
+    },
     server::application::App,
+    service::auth_service::MicrosoftClaims,
 };
 
-use super::util::internal_error;
+use super::util::{current_user, internal_error};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct BalanceDto {
     user_id: i32,
+    /// Of confirmed expenses only.
     balance: i64,
+    /// Of expenses awaiting approval or disputed, not part of `balance`.
+    pending: i64,
     currency: String,
 }
 
@@ -20,22 +36,171 @@ impl From<&Balance> for BalanceDto {
         BalanceDto {
             user_id: value.user_id,
             balance: value.balance,
+            pending: value.pending,
             currency: value.currency.clone(),
         }
     }
 }
 
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Granularity {
+    Daily,
+    #[default]
+    Expense,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BalanceQuery {
+    group_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BalanceHistoryQuery {
+    group_id: Option<i32>,
+    currency: Option<String>,
+    user_id: Option<i32>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    granularity: Granularity,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BalancePointDto {
+    at: DateTime<Utc>,
+    expense_id: Option<i32>,
+    change: i64,
+    balance: i64,
+}
+
+impl From<&BalancePoint> for BalancePointDto {
+    fn from(value: &BalancePoint) -> Self {
+        BalancePointDto {
+            at: value.at,
+            expense_id: value.expense_id,
+            change: value.change,
+            balance: value.balance,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct BalanceSeriesDto {
+    user_id: i32,
+    currency: String,
+    points: Vec<BalancePointDto>,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_balance, get_balance_history),
+    components(schemas(Granularity))
+)]
+pub struct BalanceApi;
+
 pub fn get_balance_api() -> Router<App> {
-    Router::new().route("/", get(get_balance))
+    Router::new()
+        .route("/", get(get_balance))
+        .route("/history", get(get_balance_history))
 }
 
+/// Every user's balance per currency in the group, with expenses awaiting
+/// approval kept apart.
+#[utoipa::path(
+    get,
+    path = "/api/balance",
+    tag = "balance",
+    params(BalanceQuery),
+    responses(
+        (status = 200, body = [BalanceDto]),
+        (status = 403, body = String)
+    )
+)]
 async fn get_balance(
     State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<BalanceQuery>,
 ) -> Result<Json<Vec<BalanceDto>>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+    let group_id = query.group_id.unwrap_or(DEFAULT_GROUP_ID);
+    let is_member = db::group::is_member(&app.db, group_id, me.id)
+        .await
+        .map_err(internal_error)?;
+    if !is_member {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("User {} is not a member of group {}", me.id, group_id),
+        ));
+    }
+
     Ok(Json(
-        db::balance::get_balance(&app.db)
+        db::balance::get_balance(&app.db, group_id)
             .await
             .map(|balance| balance.iter().map(|b| b.into()).collect())
             .map_err(internal_error)?,
     ))
 }
+
+/// Every user's running balance over time, one series per user and currency.
+/// Only confirmed expenses of the current user's groups are included.
+#[utoipa::path(
+    get,
+    path = "/api/balance/history",
+    tag = "balance",
+    params(BalanceHistoryQuery),
+    responses(
+        (status = 200, body = [BalanceSeriesDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_balance_history(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<BalanceHistoryQuery>,
+) -> Result<Json<Vec<BalanceSeriesDto>>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+    if let Some(group_id) = query.group_id {
+        let is_member = db::group::is_member(&app.db, group_id, me.id)
+            .await
+            .map_err(internal_error)?;
+        if !is_member {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("User {} is not a member of group {}", me.id, group_id),
+            ));
+        }
+    }
+
+    let filter = BalanceHistoryFilter {
+        member_id: me.id,
+        group_id: query.group_id,
+        currency: query.currency,
+        user_id: query.user_id,
+        from: query.from.unwrap_or(DateTime::UNIX_EPOCH),
+        to: query.to.unwrap_or(Utc::now()),
+    };
+    let points =
+        db::balance::get_balance_history(&app.db, &filter, query.granularity == Granularity::Daily)
+            .await
+            .map_err(internal_error)?;
+
+    // Points are ordered by user and currency, so each series is contiguous
+    let mut series: Vec<BalanceSeriesDto> = Vec::new();
+    for point in &points {
+        match series.last_mut() {
+            Some(last) if last.user_id == point.user_id && last.currency == point.currency => {
+                last.points.push(point.into());
+            }
+            _ => series.push(BalanceSeriesDto {
+                user_id: point.user_id,
+                currency: point.currency.clone(),
+                points: vec![point.into()],
+            }),
+        }
+    }
+
+    Ok(Json(series))
+}
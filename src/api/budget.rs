@@ -0,0 +1,300 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{budget::Budget, group::DEFAULT_GROUP_ID},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        budget_service::{BudgetError, BudgetPeriod, BudgetService, BudgetStatus, NewBudget},
+    },
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum BudgetPeriodDto {
+    Week,
+    Month,
+    Year,
+}
+
+impl From<BudgetPeriodDto> for BudgetPeriod {
+    fn from(value: BudgetPeriodDto) -> Self {
+        match value {
+            BudgetPeriodDto::Week => BudgetPeriod::Week,
+            BudgetPeriodDto::Month => BudgetPeriod::Month,
+            BudgetPeriodDto::Year => BudgetPeriod::Year,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct BudgetDto {
+    id: i32,
+    group_id: i32,
+    name: String,
+    category_id: i32,
+    period: String,
+    amount: i32,
+    currency: String,
+    user_id: Option<i32>,
+    alert_thresholds: Vec<i32>,
+}
+
+impl From<&Budget> for BudgetDto {
+    fn from(value: &Budget) -> Self {
+        BudgetDto {
+            id: value.id,
+            group_id: value.group_id,
+            name: value.name.clone(),
+            category_id: value.category_id,
+            period: value.period.clone(),
+            amount: value.amount,
+            currency: value.currency.clone(),
+            user_id: value.user_id,
+            alert_thresholds: value.alert_thresholds.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpsertBudgetDto {
+    group_id: Option<i32>,
+    name: String,
+    category_id: i32,
+    period: BudgetPeriodDto,
+    amount: i32,
+    currency: String,
+    user_id: Option<i32>,
+    alert_thresholds: Option<Vec<i32>>,
+}
+
+impl From<UpsertBudgetDto> for NewBudget {
+    fn from(value: UpsertBudgetDto) -> Self {
+        NewBudget {
+            group_id: value.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            name: value.name,
+            category_id: value.category_id,
+            period: value.period.into(),
+            amount: value.amount,
+            currency: value.currency,
+            user_id: value.user_id,
+            alert_thresholds: value.alert_thresholds.unwrap_or(vec![80, 100]),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetBudgetsQuery {
+    group_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetBudgetStatusQuery {
+    group_id: Option<i32>,
+    /// How many periods before the current one to include.
+    past_periods: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BudgetPeriodStatusDto {
+    period_start: chrono::DateTime<Utc>,
+    period_end: chrono::DateTime<Utc>,
+    spent: i64,
+    amount: i32,
+    /// `spent` as a percentage of `amount`.
+    percentage: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BudgetStatusDto {
+    budget: BudgetDto,
+    periods: Vec<BudgetPeriodStatusDto>,
+}
+
+impl From<&BudgetStatus> for BudgetStatusDto {
+    fn from(value: &BudgetStatus) -> Self {
+        let amount = value.budget.amount;
+        BudgetStatusDto {
+            budget: (&value.budget).into(),
+            periods: value
+                .periods
+                .iter()
+                .map(|period| BudgetPeriodStatusDto {
+                    period_start: period.period_start,
+                    period_end: period.period_end,
+                    spent: period.spent,
+                    amount,
+                    percentage: period.spent as f64 * 100.0 / amount as f64,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn budget_error(err: BudgetError) -> (StatusCode, String) {
+    match err {
+        BudgetError::Sqlx(err) => internal_error(err),
+        BudgetError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        BudgetError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        BudgetError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_budgets,
+    get_budget_status,
+    create_budget,
+    update_budget,
+    delete_budget
+))]
+pub struct BudgetApi;
+
+pub fn get_budget_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_budgets).post(create_budget))
+        .route("/status", get(get_budget_status))
+        .route("/:id", put(update_budget).delete(delete_budget))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/budget",
+    tag = "budget",
+    params(GetBudgetsQuery),
+    responses(
+        (status = 200, body = [BudgetDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_budgets(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetBudgetsQuery>,
+) -> Result<Json<Vec<BudgetDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let budgets = BudgetService::new(app.db, app.events)
+        .get_budgets(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(budget_error)?;
+
+    Ok(Json(budgets.iter().map(|budget| budget.into()).collect()))
+}
+
+/// Spending against every budget of the group, current period first.
+#[utoipa::path(
+    get,
+    path = "/api/budget/status",
+    tag = "budget",
+    params(GetBudgetStatusQuery),
+    responses(
+        (status = 200, body = [BudgetStatusDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_budget_status(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetBudgetStatusQuery>,
+) -> Result<Json<Vec<BudgetStatusDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let statuses = BudgetService::new(app.db, app.events)
+        .get_budget_status(
+            &actor,
+            query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            query.past_periods.unwrap_or(0).min(24),
+        )
+        .await
+        .map_err(budget_error)?;
+
+    Ok(Json(statuses.iter().map(|status| status.into()).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/budget",
+    tag = "budget",
+    request_body = UpsertBudgetDto,
+    responses(
+        (status = 200, body = BudgetDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_budget(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(budget): Json<UpsertBudgetDto>,
+) -> Result<Json<BudgetDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let budget = BudgetService::new(app.db, app.events)
+        .create_budget(&actor, budget.into())
+        .await
+        .map_err(budget_error)?;
+
+    Ok(Json((&budget).into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/budget/{id}",
+    tag = "budget",
+    params(("id" = i32, Path, description = "Budget id")),
+    request_body = UpsertBudgetDto,
+    responses(
+        (status = 200, body = BudgetDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn update_budget(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(budget): Json<UpsertBudgetDto>,
+) -> Result<Json<BudgetDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let budget = BudgetService::new(app.db, app.events)
+        .update_budget(&actor, id, budget.into())
+        .await
+        .map_err(budget_error)?;
+
+    Ok(Json((&budget).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/budget/{id}",
+    tag = "budget",
+    params(("id" = i32, Path, description = "Budget id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_budget(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    BudgetService::new(app.db, app.events)
+        .delete_budget(&actor, id)
+        .await
+        .map_err(budget_error)
+}
@@ -0,0 +1,85 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use futures::{stream, Stream};
+use jwt_authorizer::JwtClaims;
+use tokio::sync::broadcast::error::RecvError;
+use utoipa::OpenApi;
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db,
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        change_service::{Change, RESYNC_ENTITY},
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(paths(stream_changes))]
+pub struct ChangeApi;
+
+pub fn get_change_api() -> Router<App> {
+    Router::new().route("/", get(stream_changes))
+}
+
+fn change_event(change: &Change) -> Event {
+    Event::default()
+        .event(&change.entity)
+        .data(serde_json::to_string(change).expect("Changes serialize"))
+}
+
+/// Streams changes within the user's groups as Server-Sent Events named after
+/// the changed entity. A `resync` event means changes were missed and the
+/// client should reload. Group memberships are read once when connecting.
+///
+/// The browser's `EventSource` can't send the `Authorization` header, so
+/// clients have to use a fetch based implementation.
+#[utoipa::path(
+    get,
+    path = "/api/changes",
+    tag = "change",
+    responses((status = 200, description = "Stream of changes", content_type = "text/event-stream", body = String))
+)]
+async fn stream_changes(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+    let group_ids: Vec<i32> = db::group::get_groups_for_user(&app.db, me.id)
+        .await
+        .map_err(internal_error)?
+        .iter()
+        .map(|group| group.id)
+        .collect();
+
+    let changes = stream::unfold(app.changes.subscribe(), move |mut receiver| {
+        let group_ids = group_ids.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(change) if change.entity == RESYNC_ENTITY => {
+                        return Some((Ok(Event::default().event("resync").data("{}")), receiver));
+                    }
+                    Ok(change) if change.is_visible_in(&group_ids) => {
+                        return Some((Ok(change_event(&change)), receiver));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(_)) => {
+                        return Some((Ok(Event::default().event("resync").data("{}")), receiver));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    Ok(Sse::new(changes).keep_alive(KeepAlive::default()))
+}
@@ -1,25 +1,40 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
-    routing::get,
+    routing::{get, post, put},
     Json, Router,
 };
 use chrono::Utc;
+use jwt_authorizer::JwtClaims;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use crate::{
-    api::util::internal_error,
+    api::util::{current_user, internal_error},
     db::{
-        self,
-        expense::{AccountShare, Expense, InsertAccountShare, InsertExpense},
+        expense::{
+            AccountShare, Expense, ExpenseDetails, ExpenseFilter, ExpenseWithShares,
+            InsertAccountShare, Receipt,
+        },
+        expense_approval::{ExpenseApproval, CONFIRMED, DISPUTED, PENDING},
+        expense_comment::ExpenseComment,
+        expense_log::ExpenseLog,
     },
     server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        comment_service::{CommentError, CommentService},
+        expense_service::{
+            normalize_tag, ExpenseError, ExpenseService, ExtraKind, NewExpense, NewReceipt,
+            NewReceiptExtra, NewReceiptItem, NewRefund, RefundAmount, RefundShare, Split,
+            SplitWeight,
+        },
+    },
 };
 
 use super::expense_category::ExpenseCategoryDto;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ExpenseDto {
     pub id: i32,
     pub name: String,
@@ -27,10 +42,28 @@ pub struct ExpenseDto {
     pub currency: String,
     pub created_at: chrono::DateTime<Utc>,
     pub is_payment: bool,
+    pub group_id: i32,
+    /// `confirmed`, or `pending` or `disputed` while the expense awaits the
+    /// approval of its participants and doesn't count towards balances.
+    pub status: String,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+    pub merchant: Option<String>,
+    pub place: Option<String>,
+    pub location: Option<LocationDto>,
+    /// Set for refunds, which have a negative total, to the expense refunded.
+    pub refund_of: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LocationDto {
+    pub latitude: f64,
+    pub longitude: f64,
 }
 
 impl From<&Expense> for ExpenseDto {
     fn from(value: &Expense) -> Self {
+        let details = &value.details;
         ExpenseDto {
             id: value.id,
             name: value.name.clone(),
@@ -38,11 +71,25 @@ impl From<&Expense> for ExpenseDto {
             currency: value.currency.clone(),
             created_at: value.created_at,
             is_payment: value.is_payment,
+            group_id: value.group_id,
+            status: value.status.clone(),
+            note: details.note.clone(),
+            tags: details.tags.clone(),
+            merchant: details.merchant.clone(),
+            place: details.place.clone(),
+            location: details
+                .latitude
+                .zip(details.longitude)
+                .map(|(latitude, longitude)| LocationDto {
+                    latitude,
+                    longitude,
+                }),
+            refund_of: value.refund_of,
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AccountShareDto {
     pub expense_id: i32,
     pub user_id: i32,
@@ -59,8 +106,8 @@ impl From<&AccountShare> for AccountShareDto {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct UpsertExpenseDto {
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UpsertExpenseDto {
     id: Option<i32>,
     name: String,
     created_at: Option<chrono::DateTime<Utc>>,
@@ -68,114 +115,823 @@ struct UpsertExpenseDto {
     total: i32,
     currency: String,
     category_id: Option<i32>,
+    #[serde(default)]
     shares: Vec<UpsertAccountShareDto>,
+    /// Alternative to `shares`, lets the server split the total by weight.
+    weights: Option<Vec<SplitWeightDto>>,
+    /// Alternative to `shares` and `weights`, splits each line item between
+    /// its own participants.
+    receipt: Option<UpsertReceiptDto>,
+    /// Alternative to `shares`, splits the total by the weights of a split
+    /// profile. Expenses without any split use the group's applicable
+    /// profile.
+    split_profile_id: Option<i32>,
     is_payment: bool,
+    group_id: Option<i32>,
+    note: Option<String>,
+    /// Stored lowercase and without a leading `#`.
+    #[serde(default)]
+    tags: Vec<String>,
+    merchant: Option<String>,
+    /// A name for where the expense was made.
+    place: Option<String>,
+    location: Option<LocationDto>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct UpsertAccountShareDto {
     user_id: i32,
     share: i32,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
+struct SplitWeightDto {
+    user_id: i32,
+    weight: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ExtraKindDto {
+    Tax,
+    Tip,
+    /// Subtracted from the items rather than added.
+    Discount,
+}
+
+impl From<ExtraKindDto> for ExtraKind {
+    fn from(value: ExtraKindDto) -> Self {
+        match value {
+            ExtraKindDto::Tax => ExtraKind::Tax,
+            ExtraKindDto::Tip => ExtraKind::Tip,
+            ExtraKindDto::Discount => ExtraKind::Discount,
+        }
+    }
+}
+
+/// The items and extras must add up to the total of the expense.
+#[derive(Serialize, Deserialize, ToSchema)]
+struct UpsertReceiptDto {
+    items: Vec<UpsertExpenseItemDto>,
+    #[serde(default)]
+    extras: Vec<UpsertExpenseExtraDto>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct UpsertExpenseItemDto {
+    name: String,
+    amount: i32,
+    /// Defaults to the category of the expense.
+    category_id: Option<i32>,
+    weights: Vec<SplitWeightDto>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct UpsertExpenseExtraDto {
+    kind: ExtraKindDto,
+    amount: i32,
+}
+
+impl From<UpsertReceiptDto> for NewReceipt {
+    fn from(value: UpsertReceiptDto) -> Self {
+        NewReceipt {
+            items: value
+                .items
+                .into_iter()
+                .map(|item| NewReceiptItem {
+                    name: item.name,
+                    amount: item.amount,
+                    category_id: item.category_id,
+                    weights: item
+                        .weights
+                        .into_iter()
+                        .map(|weight| SplitWeight {
+                            user_id: weight.user_id,
+                            weight: weight.weight,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            extras: value
+                .extras
+                .into_iter()
+                .map(|extra| NewReceiptExtra {
+                    kind: extra.kind.into(),
+                    amount: extra.amount,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseItemShareDto {
+    user_id: i32,
+    weight: i32,
+    /// Including the user's part of the extras.
+    consumed: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseItemDto {
+    id: i32,
+    name: String,
+    amount: i32,
+    category_id: Option<i32>,
+    shares: Vec<ExpenseItemShareDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseExtraDto {
+    kind: String,
+    amount: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReceiptDto {
+    items: Vec<ExpenseItemDto>,
+    extras: Vec<ExpenseExtraDto>,
+}
+
+impl From<&Receipt> for ReceiptDto {
+    fn from(value: &Receipt) -> Self {
+        ReceiptDto {
+            items: value
+                .items
+                .iter()
+                .map(|(item, shares)| ExpenseItemDto {
+                    id: item.id,
+                    name: item.name.clone(),
+                    amount: item.amount,
+                    category_id: item.category_id,
+                    shares: shares
+                        .iter()
+                        .map(|share| ExpenseItemShareDto {
+                            user_id: share.user_id,
+                            weight: share.weight,
+                            consumed: share.consumed,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            extras: value
+                .extras
+                .iter()
+                .map(|extra| ExpenseExtraDto {
+                    kind: extra.kind.clone(),
+                    amount: extra.amount,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetExpensesQuery {
+    group_id: Option<i32>,
+    /// Comma separated, expenses must have all of the tags.
+    tag: Option<String>,
+    /// Case-insensitive, but otherwise exact.
+    merchant: Option<String>,
+    /// Searches the name, note, merchant, place and tags.
+    q: Option<String>,
+    status: Option<ExpenseStatusDto>,
+}
+
+#[derive(Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ExpenseStatusDto {
+    Confirmed,
+    Pending,
+    Disputed,
+}
+
+impl ExpenseStatusDto {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExpenseStatusDto::Confirmed => CONFIRMED,
+            ExpenseStatusDto::Pending => PENDING,
+            ExpenseStatusDto::Disputed => DISPUTED,
+        }
+    }
+}
+
+impl From<GetExpensesQuery> for ExpenseFilter {
+    fn from(value: GetExpensesQuery) -> Self {
+        ExpenseFilter {
+            group_id: value.group_id,
+            tags: value
+                .tag
+                .iter()
+                .flat_map(|tags| tags.split(','))
+                .map(normalize_tag)
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            merchant: value
+                .merchant
+                .filter(|merchant| !merchant.trim().is_empty()),
+            search: value.q.filter(|q| !q.trim().is_empty()),
+            status: value.status.map(|status| status.as_str().to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseLogDto {
+    id: i32,
+    expense_id: i32,
+    user_id: i32,
+    action: String,
+    created_at: chrono::DateTime<Utc>,
+}
+
+impl From<&ExpenseLog> for ExpenseLogDto {
+    fn from(value: &ExpenseLog) -> Self {
+        ExpenseLogDto {
+            id: value.id,
+            expense_id: value.expense_id,
+            user_id: value.user_id,
+            action: value.action.clone(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct ExpenseWithEverythingDto {
     #[serde(flatten)]
     pub expense: ExpenseDto,
     pub paid_by: i32,
     pub category: Option<ExpenseCategoryDto>,
     shares: Vec<AccountShareDto>,
+    /// Only set for itemised expenses.
+    receipt: Option<ReceiptDto>,
+    comment_count: i64,
+    /// The ids of the expense's refunds, oldest first.
+    refund_ids: Vec<i32>,
+    /// The participants asked to approve the expense, if it needed approval.
+    approvals: Vec<ExpenseApprovalDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseApprovalDto {
+    user_id: i32,
+    /// One of `pending`, `approved` or `disputed`.
+    status: String,
+    /// Only set for disputes.
+    reason: Option<String>,
+    decided_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl From<&ExpenseApproval> for ExpenseApprovalDto {
+    fn from(value: &ExpenseApproval) -> Self {
+        ExpenseApprovalDto {
+            user_id: value.user_id,
+            status: value.status.clone(),
+            reason: value.reason.clone(),
+            decided_at: value.decided_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct DisputeExpenseDto {
+    reason: String,
+}
+
+/// Refunds whatever hasn't been refunded yet unless `shares` or `amount` is
+/// set, `shares` takes precedence.
+#[derive(Deserialize, ToSchema)]
+struct RefundExpenseDto {
+    /// Defaults to the name of the refunded expense.
+    name: Option<String>,
+    created_at: Option<chrono::DateTime<Utc>>,
+    /// Divided between the participants in proportion to what they consumed.
+    amount: Option<i32>,
+    /// What each participant gets back.
+    shares: Option<Vec<RefundShareDto>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct RefundShareDto {
+    user_id: i32,
+    amount: i32,
+}
+
+impl From<RefundExpenseDto> for NewRefund {
+    fn from(value: RefundExpenseDto) -> Self {
+        let amount = match (value.shares, value.amount) {
+            (Some(shares), _) => RefundAmount::PerParticipant(
+                shares
+                    .into_iter()
+                    .map(|share| RefundShare {
+                        user_id: share.user_id,
+                        amount: share.amount,
+                    })
+                    .collect(),
+            ),
+            (None, Some(amount)) => RefundAmount::Partial(amount),
+            (None, None) => RefundAmount::Full,
+        };
+
+        NewRefund {
+            name: value.name,
+            created_at: value.created_at,
+            amount,
+        }
+    }
+}
+
+impl From<&ExpenseWithShares> for ExpenseWithEverythingDto {
+    fn from((expense, shares): &ExpenseWithShares) -> Self {
+        ExpenseWithEverythingDto {
+            expense: (&expense.expense).into(),
+            category: expense.category.as_ref().map(|category| category.into()),
+            paid_by: expense.paid_by,
+            shares: shares.iter().map(|share| share.into()).collect(),
+            receipt: expense.receipt.as_ref().map(|receipt| receipt.into()),
+            comment_count: expense.comment_count,
+            refund_ids: expense.refund_ids.clone(),
+            approvals: expense
+                .approvals
+                .iter()
+                .map(|approval| approval.into())
+                .collect(),
+        }
+    }
 }
 
+#[derive(Serialize, ToSchema)]
+struct ExpenseCommentDto {
+    id: i32,
+    expense_id: i32,
+    user_id: i32,
+    body: String,
+    created_at: chrono::DateTime<Utc>,
+    /// Set once the author has edited the comment.
+    updated_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl From<&ExpenseComment> for ExpenseCommentDto {
+    fn from(value: &ExpenseComment) -> Self {
+        ExpenseCommentDto {
+            id: value.id,
+            expense_id: value.expense_id,
+            user_id: value.user_id,
+            body: value.body.clone(),
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpsertExpenseCommentDto {
+    body: String,
+}
+
+impl From<UpsertExpenseDto> for NewExpense {
+    fn from(value: UpsertExpenseDto) -> Self {
+        let split = match (value.receipt, value.weights, value.split_profile_id) {
+            (Some(receipt), _, _) => Split::Itemised(receipt.into()),
+            (None, Some(weights), _) => Split::Weighted(
+                weights
+                    .into_iter()
+                    .map(|weight| SplitWeight {
+                        user_id: weight.user_id,
+                        weight: weight.weight,
+                    })
+                    .collect(),
+            ),
+            (None, None, Some(profile_id)) => Split::Profile(profile_id),
+            (None, None, None) => Split::Shares(
+                value
+                    .shares
+                    .into_iter()
+                    .map(|share| InsertAccountShare {
+                        share: share.share,
+                        user_id: share.user_id,
+                    })
+                    .collect(),
+            ),
+        };
+
+        NewExpense {
+            name: value.name,
+            created_at: value.created_at,
+            paid_by: value.paid_by,
+            total: value.total,
+            currency: value.currency,
+            category_id: value.category_id,
+            is_payment: value.is_payment,
+            split,
+            group_id: value.group_id,
+            details: ExpenseDetails {
+                note: value.note,
+                tags: value.tags,
+                merchant: value.merchant,
+                place: value.place,
+                latitude: value.location.as_ref().map(|location| location.latitude),
+                longitude: value.location.as_ref().map(|location| location.longitude),
+            },
+        }
+    }
+}
+
+/// Maps errors from the expense service into a response.
+pub fn expense_error(err: ExpenseError) -> (StatusCode, String) {
+    match err {
+        ExpenseError::Sqlx(err) => internal_error(err),
+        ExpenseError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        ExpenseError::Forbidden(_) | ExpenseError::NotMember(_, _) => {
+            (StatusCode::FORBIDDEN, err.to_string())
+        }
+        ExpenseError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+        ExpenseError::Closed(_, _) => (StatusCode::CONFLICT, err.to_string()),
+    }
+}
+
+fn comment_error(err: CommentError) -> (StatusCode, String) {
+    match err {
+        CommentError::Sqlx(err) => internal_error(err),
+        CommentError::ExpenseNotFound(_) | CommentError::NotFound(_) => {
+            (StatusCode::NOT_FOUND, err.to_string())
+        }
+        CommentError::Forbidden(_) | CommentError::NotMember(_, _) => {
+            (StatusCode::FORBIDDEN, err.to_string())
+        }
+        CommentError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_expenses,
+        get_expense,
+        get_expense_log,
+        upsert_expense,
+        delete_expense,
+        approve_expense,
+        dispute_expense,
+        refund_expense,
+        get_comments,
+        create_comment,
+        update_comment,
+        delete_comment
+    ),
+    components(schemas(ExpenseStatusDto))
+)]
+pub struct ExpenseApi;
+
 pub fn get_expense_api() -> Router<App> {
     Router::new()
         .route("/", get(get_expenses).put(upsert_expense))
         .route("/:id", get(get_expense).delete(delete_expense))
+        .route("/:id/log", get(get_expense_log))
+        .route("/:id/approve", post(approve_expense))
+        .route("/:id/dispute", post(dispute_expense))
+        .route("/:id/refund", post(refund_expense))
+        .route("/:id/comment", get(get_comments).post(create_comment))
+        .route(
+            "/:id/comment/:comment_id",
+            put(update_comment).delete(delete_comment),
+        )
 }
 
+/// Expenses of the current user's groups, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/expense",
+    tag = "expense",
+    params(GetExpensesQuery),
+    responses((status = 200, body = [ExpenseWithEverythingDto]))
+)]
 async fn get_expenses(
     State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetExpensesQuery>,
 ) -> Result<Json<Vec<ExpenseWithEverythingDto>>, (StatusCode, String)> {
-    let expenses = db::expense::get_expenses(&app.db)
+    let actor = current_user(&app.db, &claims).await?;
+    let expenses = ExpenseService::new(app.db, app.events)
+        .get_expenses(&actor, query.into())
         .await
-        .map_err(internal_error)?;
+        .map_err(expense_error)?;
 
-    let dtos = expenses
-        .iter()
-        .map(|(expense, shares)| ExpenseWithEverythingDto {
-            expense: (&expense.expense).into(),
-            category: expense.category.as_ref().map(|category| category.into()),
-            paid_by: expense.paid_by,
-            shares: shares.iter().map(|share| share.into()).collect(),
-        })
-        .collect::<Vec<_>>();
-
-    Ok(Json(dtos))
+    Ok(Json(
+        expenses.iter().map(|expense| expense.into()).collect(),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/expense/{id}",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    responses(
+        (status = 200, body = ExpenseWithEverythingDto),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
 async fn get_expense(
     Path(id): Path<i32>,
     State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
 ) -> Result<Json<ExpenseWithEverythingDto>, (StatusCode, String)> {
-    let expense = db::expense::get_expense(id, &app.db)
+    let actor = current_user(&app.db, &claims).await?;
+    let expense = ExpenseService::new(app.db, app.events)
+        .get_expense(&actor, id)
         .await
-        .map_err(internal_error)?
-        .map(|(expense, shares)| ExpenseWithEverythingDto {
-            expense: (&expense.expense).into(),
-            category: expense.category.as_ref().map(|category| category.into()),
-            paid_by: expense.paid_by,
-            shares: shares.iter().map(|share| share.into()).collect(),
-        })
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Expense not found".to_string()))?;
+        .map_err(expense_error)?;
+
+    Ok(Json((&expense).into()))
+}
+
+/// Who created, updated or deleted the expense and when.
+#[utoipa::path(
+    get,
+    path = "/api/expense/{id}/log",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    responses(
+        (status = 200, body = [ExpenseLogDto]),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_expense_log(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<Vec<ExpenseLogDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let log = ExpenseService::new(app.db, app.events)
+        .get_expense_log(&actor, id)
+        .await
+        .map_err(expense_error)?;
 
-    Ok(Json(expense))
+    Ok(Json(log.iter().map(|entry| entry.into()).collect()))
 }
 
+/// Creates the expense, or updates it if it has an `id`.
+#[utoipa::path(
+    put,
+    path = "/api/expense",
+    tag = "expense",
+    request_body = UpsertExpenseDto,
+    responses(
+        (status = 200, body = ExpenseWithEverythingDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
 async fn upsert_expense(
     State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
     Json(expense): Json<UpsertExpenseDto>,
 ) -> Result<Json<ExpenseWithEverythingDto>, (StatusCode, String)> {
-    let to_insert = InsertExpense {
-        category_id: expense.category_id,
-        created_at: expense.created_at,
-        total: expense.total,
-        currency: expense.currency,
-        name: expense.name,
-        paid_by: expense.paid_by,
-        is_payment: expense.is_payment,
-        shares: expense
-            .shares
-            .into_iter()
-            .map(|share| InsertAccountShare {
-                share: share.share,
-                user_id: share.user_id,
-            })
-            .collect(),
-    };
+    let actor = current_user(&app.db, &claims).await?;
+    let expense_service = ExpenseService::new(app.db, app.events);
 
     let new_expense = match expense.id {
-        Some(expense_id) => db::expense::update_expense(expense_id, to_insert, &app.db).await,
-        None => db::expense::insert_expense(to_insert, &app.db).await,
+        Some(expense_id) => {
+            expense_service
+                .update_expense(&actor, expense_id, expense.into())
+                .await
+        }
+        None => expense_service.create_expense(&actor, expense.into()).await,
     }
-    .map_err(internal_error)?;
+    .map_err(expense_error)?;
 
-    Ok(Json(ExpenseWithEverythingDto {
-        expense: (&new_expense.0.expense).into(),
-        category: new_expense
-            .0
-            .category
-            .as_ref()
-            .map(|category| category.into()),
-        paid_by: new_expense.0.paid_by,
-        shares: new_expense.1.iter().map(|share| share.into()).collect(),
-    }))
+    Ok(Json((&new_expense).into()))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/expense/{id}",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
 async fn delete_expense(
     Path(id): Path<i32>,
     State(app): State<App>,
-) -> Result<(), impl IntoResponse> {
-    db::expense::delete_expense(id, &app.db)
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    ExpenseService::new(app.db, app.events)
+        .delete_expense(&actor, id)
+        .await
+        .map_err(expense_error)
+}
+
+/// Approves an expense the current user was asked to approve. The expense
+/// counts towards balances once everyone asked has approved.
+#[utoipa::path(
+    post,
+    path = "/api/expense/{id}/approve",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    responses(
+        (status = 200, body = ExpenseWithEverythingDto),
+        (status = 400, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn approve_expense(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<ExpenseWithEverythingDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let expense = ExpenseService::new(app.db, app.events)
+        .approve_expense(&actor, id)
+        .await
+        .map_err(expense_error)?;
+
+    Ok(Json((&expense).into()))
+}
+
+/// Disputes an expense the current user was asked to approve, keeping it out
+/// of balances until it is approved after all or changed.
+#[utoipa::path(
+    post,
+    path = "/api/expense/{id}/dispute",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    request_body = DisputeExpenseDto,
+    responses(
+        (status = 200, body = ExpenseWithEverythingDto),
+        (status = 400, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn dispute_expense(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(dispute): Json<DisputeExpenseDto>,
+) -> Result<Json<ExpenseWithEverythingDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let expense = ExpenseService::new(app.db, app.events)
+        .dispute_expense(&actor, id, dispute.reason)
+        .await
+        .map_err(expense_error)?;
+
+    Ok(Json((&expense).into()))
+}
+
+/// Refunds the expense in full or in part with a linked expense of negative
+/// total, paid back to whoever paid the expense.
+#[utoipa::path(
+    post,
+    path = "/api/expense/{id}/refund",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    request_body = RefundExpenseDto,
+    responses(
+        (status = 200, body = ExpenseWithEverythingDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String),
+        (status = 409, body = String)
+    )
+)]
+async fn refund_expense(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(refund): Json<RefundExpenseDto>,
+) -> Result<Json<ExpenseWithEverythingDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let refund = ExpenseService::new(app.db, app.events)
+        .refund_expense(&actor, id, refund.into())
+        .await
+        .map_err(expense_error)?;
+
+    Ok(Json((&refund).into()))
+}
+
+/// Comments on the expense, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/expense/{id}/comment",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    responses(
+        (status = 200, body = [ExpenseCommentDto]),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_comments(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<Vec<ExpenseCommentDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let comments = CommentService::new(app.db, app.events)
+        .get_comments(&actor, id)
+        .await
+        .map_err(comment_error)?;
+
+    Ok(Json(
+        comments.iter().map(|comment| comment.into()).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/expense/{id}/comment",
+    tag = "expense",
+    params(("id" = i32, Path, description = "Expense id")),
+    request_body = UpsertExpenseCommentDto,
+    responses(
+        (status = 200, body = ExpenseCommentDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn create_comment(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(comment): Json<UpsertExpenseCommentDto>,
+) -> Result<Json<ExpenseCommentDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let comment = CommentService::new(app.db, app.events)
+        .create_comment(&actor, id, comment.body)
+        .await
+        .map_err(comment_error)?;
+
+    Ok(Json((&comment).into()))
+}
+
+/// Only the author can edit a comment.
+#[utoipa::path(
+    put,
+    path = "/api/expense/{id}/comment/{comment_id}",
+    tag = "expense",
+    params(
+        ("id" = i32, Path, description = "Expense id"),
+        ("comment_id" = i32, Path, description = "Comment id")
+    ),
+    request_body = UpsertExpenseCommentDto,
+    responses(
+        (status = 200, body = ExpenseCommentDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn update_comment(
+    Path((id, comment_id)): Path<(i32, i32)>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(comment): Json<UpsertExpenseCommentDto>,
+) -> Result<Json<ExpenseCommentDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let comment = CommentService::new(app.db, app.events)
+        .update_comment(&actor, id, comment_id, comment.body)
+        .await
+        .map_err(comment_error)?;
+
+    Ok(Json((&comment).into()))
+}
+
+/// Only the author can delete a comment.
+#[utoipa::path(
+    delete,
+    path = "/api/expense/{id}/comment/{comment_id}",
+    tag = "expense",
+    params(
+        ("id" = i32, Path, description = "Expense id"),
+        ("comment_id" = i32, Path, description = "Comment id")
+    ),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_comment(
+    Path((id, comment_id)): Path<(i32, i32)>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    CommentService::new(app.db, app.events)
+        .delete_comment(&actor, id, comment_id)
         .await
-        .map_err(internal_error)
+        .map_err(comment_error)
 }
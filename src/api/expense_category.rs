@@ -1,12 +1,35 @@
-use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
-use serde::Serialize;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, patch, post},
+    Json, Router,
+};
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-use crate::{api::util::internal_error, db, server::application::App};
+use crate::{
+    api::util::{current_user, double_option, internal_error},
+    db::{self, category_rule::CategoryRule, group::DEFAULT_GROUP_ID},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        category_service::{
+            CategoryError, CategoryService, CategorySuggestion, NewCategory, NewCategoryRule,
+            PatchCategory, SuggestionQuery,
+        },
+    },
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ExpenseCategoryDto {
     id: i32,
     name: String,
+    parent_id: Option<i32>,
+    group_id: Option<i32>,
+    icon: Option<String>,
+    color: Option<String>,
+    archived: bool,
 }
 
 impl From<&db::expense_category::ExpenseCategory> for ExpenseCategoryDto {
@@ -14,6 +37,11 @@ impl From<&db::expense_category::ExpenseCategory> for ExpenseCategoryDto {
         ExpenseCategoryDto {
             id: value.id,
             name: value.name.clone(),
+            parent_id: value.parent_id,
+            group_id: value.group_id,
+            icon: value.icon.clone(),
+            color: value.color.clone(),
+            archived: value.archived_at.is_some(),
         }
     }
 }
@@ -23,18 +51,425 @@ impl From<db::expense_category::ExpenseCategory> for ExpenseCategoryDto {
     }
 }
 
+#[derive(Serialize, ToSchema)]
+struct CategorySuggestionDto {
+    category: ExpenseCategoryDto,
+    confidence: f64,
+    rule_keyword: Option<String>,
+}
+
+impl From<CategorySuggestion> for CategorySuggestionDto {
+    fn from(value: CategorySuggestion) -> Self {
+        CategorySuggestionDto {
+            category: value.category.into(),
+            confidence: value.confidence,
+            rule_keyword: value.rule_keyword,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct CategoryRuleDto {
+    id: i32,
+    group_id: i32,
+    keyword: String,
+    category_id: i32,
+}
+
+impl From<&CategoryRule> for CategoryRuleDto {
+    fn from(value: &CategoryRule) -> Self {
+        CategoryRuleDto {
+            id: value.id,
+            group_id: value.group_id,
+            keyword: value.keyword.clone(),
+            category_id: value.category_id,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SuggestQuery {
+    name: String,
+    group_id: Option<i32>,
+    paid_by: Option<i32>,
+    total: Option<i32>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetCategoryRulesQuery {
+    group_id: Option<i32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateCategoryRuleDto {
+    group_id: Option<i32>,
+    keyword: String,
+    category_id: i32,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetExpenseCategoriesQuery {
+    group_id: Option<i32>,
+    #[serde(default)]
+    include_archived: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CreateExpenseCategoryDto {
+    name: String,
+    parent_id: Option<i32>,
+    group_id: i32,
+    icon: Option<String>,
+    color: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PatchExpenseCategoryDto {
+    name: Option<String>,
+    /// `null` moves the category to the top level.
+    #[serde(default, deserialize_with = "double_option")]
+    #[schema(value_type = Option<i32>)]
+    parent_id: Option<Option<i32>>,
+    icon: Option<String>,
+    color: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MergeExpenseCategoryDto {
+    into_id: i32,
+}
+
+fn category_error(err: CategoryError) -> (StatusCode, String) {
+    match err {
+        CategoryError::Sqlx(err) => internal_error(err),
+        CategoryError::NotFound(_) | CategoryError::RuleNotFound(_) => {
+            (StatusCode::NOT_FOUND, err.to_string())
+        }
+        CategoryError::Forbidden(_, _) | CategoryError::ReadOnly(_) => {
+            (StatusCode::FORBIDDEN, err.to_string())
+        }
+        CategoryError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_expense_categories,
+    create_expense_category,
+    patch_expense_category,
+    archive_expense_category,
+    unarchive_expense_category,
+    merge_expense_category,
+    suggest_expense_categories,
+    get_category_rules,
+    create_category_rule,
+    delete_category_rule
+))]
+pub struct ExpenseCategoryApi;
+
 pub fn get_expense_category_api() -> Router<App> {
-    Router::new().route("/", get(get_expense_categories))
+    Router::new()
+        .route(
+            "/",
+            get(get_expense_categories).post(create_expense_category),
+        )
+        .route("/:id", patch(patch_expense_category))
+        .route("/:id/archive", post(archive_expense_category))
+        .route("/:id/unarchive", post(unarchive_expense_category))
+        .route("/:id/merge", post(merge_expense_category))
+        .route("/suggest", get(suggest_expense_categories))
+        .route("/rule", get(get_category_rules).post(create_category_rule))
+        .route("/rule/:id", delete(delete_category_rule))
 }
 
+/// The global categories and those of the group, most used first.
+#[utoipa::path(
+    get,
+    path = "/api/expense_category",
+    tag = "expense_category",
+    params(GetExpenseCategoriesQuery),
+    responses((status = 200, body = [ExpenseCategoryDto]))
+)]
 async fn get_expense_categories(
     State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetExpenseCategoriesQuery>,
 ) -> Result<Json<Vec<ExpenseCategoryDto>>, (StatusCode, String)> {
-    let categories = db::expense_category::get_expense_categories(&app.db)
+    let actor = current_user(&app.db, &claims).await?;
+    let categories = CategoryService::new(app.db)
+        .get_categories(&actor, query.group_id, query.include_archived)
         .await
-        .map_err(internal_error)?;
+        .map_err(category_error)?;
 
     let dto = categories.iter().map(|category| category.into()).collect();
 
     Ok(Json(dto))
 }
+
+#[utoipa::path(
+    post,
+    path = "/api/expense_category",
+    tag = "expense_category",
+    request_body = CreateExpenseCategoryDto,
+    responses(
+        (status = 200, body = ExpenseCategoryDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_expense_category(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(category): Json<CreateExpenseCategoryDto>,
+) -> Result<Json<ExpenseCategoryDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let category = CategoryService::new(app.db)
+        .create_category(
+            &actor,
+            NewCategory {
+                name: category.name,
+                parent_id: category.parent_id,
+                group_id: category.group_id,
+                icon: category.icon,
+                color: category.color,
+            },
+        )
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(category.into()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/expense_category/{id}",
+    tag = "expense_category",
+    params(("id" = i32, Path, description = "Category id")),
+    request_body = PatchExpenseCategoryDto,
+    responses(
+        (status = 200, body = ExpenseCategoryDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn patch_expense_category(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(patch_dto): Json<PatchExpenseCategoryDto>,
+) -> Result<Json<ExpenseCategoryDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let category = CategoryService::new(app.db)
+        .update_category(
+            &actor,
+            id,
+            PatchCategory {
+                name: patch_dto.name,
+                parent_id: patch_dto.parent_id,
+                icon: patch_dto.icon,
+                color: patch_dto.color,
+            },
+        )
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(category.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/expense_category/{id}/archive",
+    tag = "expense_category",
+    params(("id" = i32, Path, description = "Category id")),
+    responses(
+        (status = 200, body = ExpenseCategoryDto),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn archive_expense_category(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<ExpenseCategoryDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let category = CategoryService::new(app.db)
+        .set_archived(&actor, id, true)
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(category.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/expense_category/{id}/unarchive",
+    tag = "expense_category",
+    params(("id" = i32, Path, description = "Category id")),
+    responses(
+        (status = 200, body = ExpenseCategoryDto),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn unarchive_expense_category(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<ExpenseCategoryDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let category = CategoryService::new(app.db)
+        .set_archived(&actor, id, false)
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(category.into()))
+}
+
+/// Merges the custom category into `into_id`, a global default or another
+/// category of the same group.
+#[utoipa::path(
+    post,
+    path = "/api/expense_category/{id}/merge",
+    tag = "expense_category",
+    params(("id" = i32, Path, description = "Category id")),
+    request_body = MergeExpenseCategoryDto,
+    responses(
+        (status = 200, body = ExpenseCategoryDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn merge_expense_category(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(merge_dto): Json<MergeExpenseCategoryDto>,
+) -> Result<Json<ExpenseCategoryDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let category = CategoryService::new(app.db)
+        .merge_category(&actor, id, merge_dto.into_id)
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(category.into()))
+}
+
+/// Suggests categories for a new expense from its name and the group's history.
+#[utoipa::path(
+    get,
+    path = "/api/expense_category/suggest",
+    tag = "expense_category",
+    params(SuggestQuery),
+    responses((status = 200, body = [CategorySuggestionDto]))
+)]
+async fn suggest_expense_categories(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SuggestQuery>,
+) -> Result<Json<Vec<CategorySuggestionDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let suggestions = CategoryService::new(app.db)
+        .suggest_categories(
+            &actor,
+            SuggestionQuery {
+                name: query.name,
+                group_id: query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                paid_by: query.paid_by,
+                total: query.total,
+                limit: query.limit.unwrap_or(3),
+            },
+        )
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(
+        suggestions
+            .into_iter()
+            .map(|suggestion| suggestion.into())
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/expense_category/rule",
+    tag = "expense_category",
+    params(GetCategoryRulesQuery),
+    responses((status = 200, body = [CategoryRuleDto]))
+)]
+async fn get_category_rules(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetCategoryRulesQuery>,
+) -> Result<Json<Vec<CategoryRuleDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let rules = CategoryService::new(app.db)
+        .get_rules(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json(rules.iter().map(|rule| rule.into()).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/expense_category/rule",
+    tag = "expense_category",
+    request_body = CreateCategoryRuleDto,
+    responses(
+        (status = 200, body = CategoryRuleDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_category_rule(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(rule): Json<CreateCategoryRuleDto>,
+) -> Result<Json<CategoryRuleDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let rule = CategoryService::new(app.db)
+        .create_rule(
+            &actor,
+            NewCategoryRule {
+                group_id: rule.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                keyword: rule.keyword,
+                category_id: rule.category_id,
+            },
+        )
+        .await
+        .map_err(category_error)?;
+
+    Ok(Json((&rule).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/expense_category/rule/{id}",
+    tag = "expense_category",
+    params(("id" = i32, Path, description = "Rule id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_category_rule(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    CategoryService::new(app.db)
+        .delete_rule(&actor, id)
+        .await
+        .map_err(category_error)
+}
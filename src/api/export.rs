@@ -0,0 +1,168 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{Duration, Utc};
+use jwt_authorizer::JwtClaims;
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{export::ExportFilter, group::DEFAULT_GROUP_ID},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        export_service::{ExportColumn, ExportError, ExportLocale, ExportOptions, ExportService},
+    },
+};
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum RowsDto {
+    #[default]
+    Expense,
+    Share,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum LocaleDto {
+    #[default]
+    Sv,
+    En,
+}
+
+impl From<LocaleDto> for ExportLocale {
+    fn from(value: LocaleDto) -> Self {
+        match value {
+            LocaleDto::Sv => ExportLocale::Sv,
+            LocaleDto::En => ExportLocale::En,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum FormatDto {
+    #[default]
+    Csv,
+    /// CSV tweaked to open correctly in Excel.
+    Excel,
+}
+
+fn default_include_payments() -> bool {
+    true
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportQuery {
+    group_id: Option<i32>,
+    /// Defaults to a year before `to`.
+    from: Option<chrono::DateTime<Utc>>,
+    /// Defaults to now.
+    to: Option<chrono::DateTime<Utc>>,
+    category_id: Option<i32>,
+    #[serde(default = "default_include_payments")]
+    include_payments: bool,
+    #[serde(default)]
+    rows: RowsDto,
+    /// Comma separated column names, defaults depend on `rows`.
+    columns: Option<String>,
+    #[serde(default)]
+    locale: LocaleDto,
+    #[serde(default)]
+    format: FormatDto,
+}
+
+fn export_error(err: ExportError) -> (StatusCode, String) {
+    match err {
+        ExportError::Sqlx(err) => internal_error(err),
+        ExportError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        ExportError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(export_expenses),
+    components(schemas(RowsDto, LocaleDto, FormatDto))
+)]
+pub struct ExportApi;
+
+pub fn get_export_api() -> Router<App> {
+    Router::new().route("/expenses.csv", get(export_expenses))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/export/expenses.csv",
+    tag = "export",
+    params(ExportQuery),
+    responses(
+        (status = 200, content_type = "text/csv", body = String),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn export_expenses(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let to = query.to.unwrap_or(Utc::now());
+    let from = query.from.unwrap_or(to - Duration::days(365));
+    let per_share = query.rows == RowsDto::Share;
+
+    let columns = match &query.columns {
+        Some(columns) => columns
+            .split(',')
+            .map(|column| {
+                ExportColumn::parse(column.trim()).ok_or((
+                    StatusCode::BAD_REQUEST,
+                    format!("'{}' is not an export column", column),
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None if per_share => ExportColumn::SHARE_DEFAULTS.to_vec(),
+        None => ExportColumn::EXPENSE_DEFAULTS.to_vec(),
+    };
+
+    let lines = ExportService::new(app.db)
+        .export_csv(
+            &actor,
+            ExportOptions {
+                filter: ExportFilter {
+                    group_id: query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                    from,
+                    to,
+                    category_id: query.category_id,
+                    include_payments: query.include_payments,
+                    per_share,
+                },
+                columns,
+                locale: query.locale.into(),
+                excel: query.format == FormatDto::Excel,
+            },
+        )
+        .await
+        .map_err(export_error)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"expenses.csv\"",
+            ),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db,
+    server::application::App,
+    service::auth_service::MicrosoftClaims,
+};
+
+#[derive(Serialize, ToSchema)]
+struct GroupDto {
+    id: i32,
+    name: String,
+    created_at: chrono::DateTime<Utc>,
+    member_ids: Vec<i32>,
+    /// In minor units, expenses with a larger total need to be approved by
+    /// their participants before they count towards balances.
+    approval_threshold: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct GroupSettingsDto {
+    /// `null` turns approvals off.
+    approval_threshold: Option<i32>,
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_groups, update_group_settings))]
+pub struct GroupApi;
+
+pub fn get_group_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_groups))
+        .route("/:id/settings", put(update_group_settings))
+}
+
+/// The groups the current user is a member of.
+#[utoipa::path(
+    get,
+    path = "/api/group",
+    tag = "group",
+    responses((status = 200, body = [GroupDto]))
+)]
+async fn get_groups(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<Vec<GroupDto>>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+    let groups = db::group::get_groups_for_user(&app.db, me.id)
+        .await
+        .map_err(internal_error)?;
+
+    let mut dtos = Vec::new();
+    for group in groups {
+        let member_ids = db::group::get_member_ids(&app.db, group.id)
+            .await
+            .map_err(internal_error)?;
+        dtos.push(GroupDto {
+            id: group.id,
+            name: group.name,
+            created_at: group.created_at,
+            member_ids,
+            approval_threshold: group.approval_threshold,
+        });
+    }
+
+    Ok(Json(dtos))
+}
+
+/// Changes only apply to expenses created or changed from now on.
+#[utoipa::path(
+    put,
+    path = "/api/group/{id}/settings",
+    tag = "group",
+    params(("id" = i32, Path, description = "Group id")),
+    request_body = GroupSettingsDto,
+    responses(
+        (status = 200, body = GroupSettingsDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn update_group_settings(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(settings): Json<GroupSettingsDto>,
+) -> Result<Json<GroupSettingsDto>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+    let is_member = db::group::is_member(&app.db, id, me.id)
+        .await
+        .map_err(internal_error)?;
+    if !is_member {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("User {} is not a member of group {}", me.id, id),
+        ));
+    }
+    if settings
+        .approval_threshold
+        .is_some_and(|threshold| threshold <= 0)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "approval threshold must be positive".to_string(),
+        ));
+    }
+
+    let group = db::group::set_approval_threshold(&app.db, id, settings.approval_threshold)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(GroupSettingsDto {
+        approval_threshold: group.approval_threshold,
+    }))
+}
@@ -0,0 +1,194 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{group::DEFAULT_GROUP_ID, guest::Guest},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        guest_service::{GuestError, GuestService, NewGuest},
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct GuestDto {
+    /// Used as `user_id` in expenses and balances.
+    id: i32,
+    name: String,
+    email: Option<String>,
+    phone_number: Option<String>,
+    group_id: i32,
+    created_by: i32,
+    created_at: chrono::DateTime<Utc>,
+}
+
+impl From<&Guest> for GuestDto {
+    fn from(value: &Guest) -> Self {
+        GuestDto {
+            id: value.id,
+            name: value.name.clone(),
+            email: value.email.clone(),
+            phone_number: value.phone_number.clone(),
+            group_id: value.group_id,
+            created_by: value.created_by,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpsertGuestDto {
+    group_id: Option<i32>,
+    name: String,
+    email: Option<String>,
+    /// A Swedish mobile number, so that the guest can be paid with Swish.
+    phone_number: Option<String>,
+}
+
+impl From<UpsertGuestDto> for NewGuest {
+    fn from(value: UpsertGuestDto) -> Self {
+        NewGuest {
+            group_id: value.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            name: value.name,
+            email: value.email,
+            phone_number: value.phone_number,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetGuestsQuery {
+    group_id: Option<i32>,
+}
+
+fn guest_error(err: GuestError) -> (StatusCode, String) {
+    match err {
+        GuestError::Sqlx(err) => internal_error(err),
+        GuestError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        GuestError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        GuestError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_guests, create_guest, update_guest, delete_guest))]
+pub struct GuestApi;
+
+pub fn get_guest_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_guests).post(create_guest))
+        .route("/:id", put(update_guest).delete(delete_guest))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/guest",
+    tag = "guest",
+    params(GetGuestsQuery),
+    responses(
+        (status = 200, body = [GuestDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_guests(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetGuestsQuery>,
+) -> Result<Json<Vec<GuestDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let guests = GuestService::new(app.db)
+        .get_guests(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(guest_error)?;
+
+    Ok(Json(guests.iter().map(|guest| guest.into()).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/guest",
+    tag = "guest",
+    request_body = UpsertGuestDto,
+    responses(
+        (status = 200, body = GuestDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_guest(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(guest): Json<UpsertGuestDto>,
+) -> Result<Json<GuestDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let guest = GuestService::new(app.db)
+        .create_guest(&actor, guest.into())
+        .await
+        .map_err(guest_error)?;
+
+    Ok(Json((&guest).into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/guest/{id}",
+    tag = "guest",
+    params(("id" = i32, Path, description = "Guest id")),
+    request_body = UpsertGuestDto,
+    responses(
+        (status = 200, body = GuestDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn update_guest(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(guest): Json<UpsertGuestDto>,
+) -> Result<Json<GuestDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let guest = GuestService::new(app.db)
+        .update_guest(&actor, id, guest.into())
+        .await
+        .map_err(guest_error)?;
+
+    Ok(Json((&guest).into()))
+}
+
+/// Guests that take part in expenses can't be deleted, invite them instead.
+#[utoipa::path(
+    delete,
+    path = "/api/guest/{id}",
+    tag = "guest",
+    params(("id" = i32, Path, description = "Guest id")),
+    responses(
+        (status = 200),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_guest(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    GuestService::new(app.db)
+        .delete_guest(&actor, id)
+        .await
+        .map_err(guest_error)
+}
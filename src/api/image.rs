@@ -5,6 +5,7 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 use crate::{
     db::{
@@ -16,20 +17,21 @@ use crate::{
 
 use super::util::internal_error;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct ImageDto {
     id: i32,
     url: String,
     tags: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 struct ImportImageDto {
     url: String,
     tags: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct GetImageQuery {
     tag: Option<String>,
     page: Option<usize>,
@@ -55,12 +57,23 @@ impl From<Image> for ImageDto {
     }
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_image, import_image))]
+pub struct ImageApi;
+
 pub fn get_image_api() -> Router<App> {
     Router::new()
         .route("/", get(get_image))
         .route("/import", post(import_image))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/image",
+    tag = "image",
+    params(GetImageQuery),
+    responses((status = 200, body = [ImageDto]))
+)]
 async fn get_image(
     State(app): State<App>,
     Query(query): Query<GetImageQuery>,
@@ -73,6 +86,13 @@ async fn get_image(
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/image/import",
+    tag = "image",
+    request_body = Vec<ImportImageDto>,
+    responses((status = 200, body = [ImageDto]))
+)]
 async fn import_image(
     State(app): State<App>,
     Json(images): Json<Vec<ImportImageDto>>,
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::group::DEFAULT_GROUP_ID,
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        expense_service::ExpenseService,
+        import_service::{
+            Bank, BankCsvMapping, BankImport, BankStatementFormat, BankTransactionProposal,
+            ImportError, ImportReport, ImportRow, ImportRowStatus, ImportService, ProposalStatus,
+            SplitwiseImport,
+        },
+    },
+};
+
+use super::expense::{expense_error, ExpenseDto, ExpenseWithEverythingDto, UpsertExpenseDto};
+
+fn default_dry_run() -> bool {
+    true
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SplitwiseImportDto {
+    group_id: Option<i32>,
+    /// The contents of the exported CSV file.
+    csv: String,
+    #[serde(default)]
+    people: HashMap<String, i32>,
+    /// Only preview the import unless explicitly disabled.
+    #[serde(default = "default_dry_run")]
+    dry_run: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct PersonMappingDto {
+    name: String,
+    user_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ImportRowStatusDto {
+    New,
+    Duplicate,
+    Skipped,
+    Conflict,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ImportShareDto {
+    user_id: i32,
+    share: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ImportRowDto {
+    line: u64,
+    status: ImportRowStatusDto,
+    /// Why the row is skipped or in conflict.
+    reason: Option<String>,
+    /// The existing expense a duplicate matches, or the created expense.
+    expense_id: Option<i32>,
+    name: String,
+    created_at: Option<chrono::DateTime<Utc>>,
+    total: i32,
+    currency: String,
+    category_id: Option<i32>,
+    is_payment: bool,
+    paid_by: Option<i32>,
+    shares: Vec<ImportShareDto>,
+    warnings: Vec<String>,
+}
+
+impl From<&ImportRow> for ImportRowDto {
+    fn from(value: &ImportRow) -> Self {
+        let (status, reason, expense_id) = match &value.status {
+            ImportRowStatus::New => (ImportRowStatusDto::New, None, value.expense_id),
+            ImportRowStatus::Duplicate(id) => (ImportRowStatusDto::Duplicate, None, Some(*id)),
+            ImportRowStatus::Skipped(reason) => {
+                (ImportRowStatusDto::Skipped, Some(reason.clone()), None)
+            }
+            ImportRowStatus::Conflict(reason) => {
+                (ImportRowStatusDto::Conflict, Some(reason.clone()), None)
+            }
+        };
+
+        ImportRowDto {
+            line: value.line,
+            status,
+            reason,
+            expense_id,
+            name: value.name.clone(),
+            created_at: value.created_at,
+            total: value.total,
+            currency: value.currency.clone(),
+            category_id: value.category_id,
+            is_payment: value.is_payment,
+            paid_by: value.paid_by,
+            shares: value
+                .shares
+                .iter()
+                .map(|share| ImportShareDto {
+                    user_id: share.user_id,
+                    share: share.share,
+                })
+                .collect(),
+            warnings: value.warnings.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct ImportReportDto {
+    committed: bool,
+    people: Vec<PersonMappingDto>,
+    rows: Vec<ImportRowDto>,
+}
+
+impl From<&ImportReport> for ImportReportDto {
+    fn from(value: &ImportReport) -> Self {
+        ImportReportDto {
+            committed: value.committed,
+            people: value
+                .people
+                .iter()
+                .map(|person| PersonMappingDto {
+                    name: person.name.clone(),
+                    user_id: person.user_id,
+                })
+                .collect(),
+            rows: value.rows.iter().map(|row| row.into()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct BankCsvMappingDto {
+    /// Defaults to `;`.
+    delimiter: Option<char>,
+    date_column: String,
+    description_column: String,
+    amount_column: String,
+    currency_column: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BankStatementFormatDto {
+    /// A CSV export from one of the supported banks.
+    Bank {
+        bank: String,
+    },
+    /// A CSV export with custom column names.
+    Csv {
+        mapping: BankCsvMappingDto,
+    },
+    Camt053,
+}
+
+fn default_currency() -> String {
+    "SEK".to_string()
+}
+
+#[derive(Deserialize, ToSchema)]
+struct BankImportDto {
+    group_id: Option<i32>,
+    format: BankStatementFormatDto,
+    /// The contents of the exported file.
+    content: String,
+    /// Currency of CSV exports without a currency column.
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default)]
+    include_incoming: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BankTransactionDto {
+    line: u64,
+    date: chrono::NaiveDate,
+    description: String,
+    amount: i32,
+    currency: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ProposalStatusDto {
+    New,
+    Matched,
+    Ignored,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ProposedWeightDto {
+    user_id: i32,
+    weight: u32,
+}
+
+/// Can be sent back as is to confirm the expense.
+#[derive(Serialize, ToSchema)]
+struct ProposedExpenseDto {
+    name: String,
+    created_at: chrono::DateTime<Utc>,
+    paid_by: i32,
+    total: i32,
+    currency: String,
+    category_id: Option<i32>,
+    weights: Vec<ProposedWeightDto>,
+    is_payment: bool,
+    group_id: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExpenseMatchDto {
+    #[serde(flatten)]
+    expense: ExpenseDto,
+    similarity: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct BankTransactionProposalDto {
+    transaction: BankTransactionDto,
+    status: ProposalStatusDto,
+    reason: Option<String>,
+    expense: Option<ProposedExpenseDto>,
+    matches: Vec<ExpenseMatchDto>,
+}
+
+impl From<&BankTransactionProposal> for BankTransactionProposalDto {
+    fn from(value: &BankTransactionProposal) -> Self {
+        let (status, reason) = match &value.status {
+            ProposalStatus::New => (ProposalStatusDto::New, None),
+            ProposalStatus::Matched => (ProposalStatusDto::Matched, None),
+            ProposalStatus::Ignored(reason) => (ProposalStatusDto::Ignored, Some(reason.clone())),
+        };
+
+        BankTransactionProposalDto {
+            transaction: BankTransactionDto {
+                line: value.transaction.line,
+                date: value.transaction.date,
+                description: value.transaction.description.clone(),
+                amount: value.transaction.amount,
+                currency: value.transaction.currency.clone(),
+            },
+            status,
+            reason,
+            expense: value.expense.as_ref().map(|expense| ProposedExpenseDto {
+                name: expense.name.clone(),
+                created_at: expense.created_at,
+                paid_by: expense.paid_by,
+                total: expense.total,
+                currency: expense.currency.clone(),
+                category_id: expense.category_id,
+                weights: expense
+                    .weights
+                    .iter()
+                    .map(|weight| ProposedWeightDto {
+                        user_id: weight.user_id,
+                        weight: weight.weight,
+                    })
+                    .collect(),
+                is_payment: false,
+                group_id: expense.group_id,
+            }),
+            matches: value
+                .matches
+                .iter()
+                .map(|expense_match| ExpenseMatchDto {
+                    expense: (&expense_match.expense).into(),
+                    similarity: expense_match.similarity,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn import_error(err: ImportError) -> (StatusCode, String) {
+    match err {
+        ImportError::Sqlx(err) => internal_error(err),
+        ImportError::Expense(err) => expense_error(err),
+        ImportError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        ImportError::Csv(_) | ImportError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(import_splitwise, preview_bank_statement, confirm_bank_expenses))]
+pub struct ImportApi;
+
+pub fn get_import_api() -> Router<App> {
+    Router::new()
+        .route("/splitwise", post(import_splitwise))
+        .route("/bank", post(preview_bank_statement))
+        .route("/bank/confirm", post(confirm_bank_expenses))
+}
+
+/// Imports a Splitwise CSV export, or previews the import while `dry_run` is set.
+#[utoipa::path(
+    post,
+    path = "/api/import/splitwise",
+    tag = "import",
+    request_body = SplitwiseImportDto,
+    responses(
+        (status = 200, body = ImportReportDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn import_splitwise(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(import): Json<SplitwiseImportDto>,
+) -> Result<Json<ImportReportDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    let report = ImportService::new(app.db, app.events)
+        .import_splitwise(
+            &actor,
+            SplitwiseImport {
+                group_id: import.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                csv: import.csv,
+                people: import.people,
+                dry_run: import.dry_run,
+            },
+        )
+        .await
+        .map_err(import_error)?;
+
+    Ok(Json((&report).into()))
+}
+
+/// Proposes an expense for every transaction of a bank statement.
+#[utoipa::path(
+    post,
+    path = "/api/import/bank",
+    tag = "import",
+    request_body = BankImportDto,
+    responses(
+        (status = 200, body = [BankTransactionProposalDto]),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn preview_bank_statement(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(import): Json<BankImportDto>,
+) -> Result<Json<Vec<BankTransactionProposalDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let format = match import.format {
+        BankStatementFormatDto::Bank { bank } => {
+            let bank = Bank::parse(&bank).ok_or((
+                StatusCode::BAD_REQUEST,
+                format!("'{}' is not a supported bank", bank),
+            ))?;
+            BankStatementFormat::Csv(bank.mapping())
+        }
+        BankStatementFormatDto::Csv { mapping } => {
+            let delimiter = mapping.delimiter.unwrap_or(';');
+            if !delimiter.is_ascii() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "delimiter must be an ASCII character".to_string(),
+                ));
+            }
+            BankStatementFormat::Csv(BankCsvMapping {
+                delimiter: delimiter as u8,
+                date_column: mapping.date_column,
+                description_column: mapping.description_column,
+                amount_column: mapping.amount_column,
+                currency_column: mapping.currency_column,
+            })
+        }
+        BankStatementFormatDto::Camt053 => BankStatementFormat::Camt053,
+    };
+
+    let proposals = ImportService::new(app.db, app.events)
+        .preview_bank_statement(
+            &actor,
+            BankImport {
+                group_id: import.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                format,
+                content: import.content,
+                currency: import.currency.to_uppercase(),
+                include_incoming: import.include_incoming,
+            },
+        )
+        .await
+        .map_err(import_error)?;
+
+    Ok(Json(
+        proposals.iter().map(|proposal| proposal.into()).collect(),
+    ))
+}
+
+/// Creates the confirmed proposals in one go.
+#[utoipa::path(
+    post,
+    path = "/api/import/bank/confirm",
+    tag = "import",
+    request_body = Vec<UpsertExpenseDto>,
+    responses(
+        (status = 200, body = [ExpenseWithEverythingDto]),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn confirm_bank_expenses(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(expenses): Json<Vec<UpsertExpenseDto>>,
+) -> Result<Json<Vec<ExpenseWithEverythingDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    let created = ExpenseService::new(app.db, app.events)
+        .create_expenses(&actor, expenses.into_iter().map(|e| e.into()).collect())
+        .await
+        .map_err(expense_error)?;
+
+    Ok(Json(created.iter().map(|expense| expense.into()).collect()))
+}
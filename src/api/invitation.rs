@@ -0,0 +1,207 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{group::DEFAULT_GROUP_ID, invitation::Invitation},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        invitation_service::{InvitationError, InvitationService, NewInvitation},
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct InvitationDto {
+    id: i32,
+    group_id: i32,
+    guest_id: Option<i32>,
+    /// Hand this to the invited person, who accepts with it.
+    token: String,
+    email: Option<String>,
+    created_by: i32,
+    created_at: chrono::DateTime<Utc>,
+    expires_at: chrono::DateTime<Utc>,
+    accepted_by: Option<i32>,
+    accepted_at: Option<chrono::DateTime<Utc>>,
+}
+
+impl From<&Invitation> for InvitationDto {
+    fn from(value: &Invitation) -> Self {
+        InvitationDto {
+            id: value.id,
+            group_id: value.group_id,
+            guest_id: value.guest_id,
+            token: value.token.clone(),
+            email: value.email.clone(),
+            created_by: value.created_by,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            accepted_by: value.accepted_by,
+            accepted_at: value.accepted_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct NewInvitationDto {
+    group_id: Option<i32>,
+    /// The guest to merge into whoever accepts the invitation.
+    guest_id: Option<i32>,
+    /// Defaults to the email of the guest.
+    email: Option<String>,
+    /// Defaults to 14.
+    valid_days: Option<i64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetInvitationsQuery {
+    group_id: Option<i32>,
+}
+
+fn invitation_error(err: InvitationError) -> (StatusCode, String) {
+    match err {
+        InvitationError::Sqlx(err) => internal_error(err),
+        InvitationError::NotFound => (StatusCode::NOT_FOUND, err.to_string()),
+        InvitationError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        InvitationError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_invitations,
+    create_invitation,
+    revoke_invitation,
+    accept_invitation
+))]
+pub struct InvitationApi;
+
+pub fn get_invitation_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_invitations).post(create_invitation))
+        .route("/:id", delete(revoke_invitation))
+        .route("/accept/:token", post(accept_invitation))
+}
+
+/// Invitations of the group that haven't been accepted or expired yet.
+#[utoipa::path(
+    get,
+    path = "/api/invitation",
+    tag = "invitation",
+    params(GetInvitationsQuery),
+    responses(
+        (status = 200, body = [InvitationDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_invitations(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetInvitationsQuery>,
+) -> Result<Json<Vec<InvitationDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let invitations = InvitationService::new(app.db)
+        .get_invitations(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(invitation_error)?;
+
+    Ok(Json(
+        invitations
+            .iter()
+            .map(|invitation| invitation.into())
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invitation",
+    tag = "invitation",
+    request_body = NewInvitationDto,
+    responses(
+        (status = 200, body = InvitationDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_invitation(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(invitation): Json<NewInvitationDto>,
+) -> Result<Json<InvitationDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let invitation = InvitationService::new(app.db)
+        .create_invitation(
+            &actor,
+            NewInvitation {
+                group_id: invitation.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                guest_id: invitation.guest_id,
+                email: invitation.email,
+                valid_days: invitation.valid_days,
+            },
+        )
+        .await
+        .map_err(invitation_error)?;
+
+    Ok(Json((&invitation).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/invitation/{id}",
+    tag = "invitation",
+    params(("id" = i32, Path, description = "Invitation id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn revoke_invitation(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    InvitationService::new(app.db)
+        .revoke_invitation(&actor, id)
+        .await
+        .map_err(invitation_error)
+}
+
+/// Joins the group of the invitation, taking over its guest if it has one.
+#[utoipa::path(
+    post,
+    path = "/api/invitation/accept/{token}",
+    tag = "invitation",
+    params(("token" = String, Path, description = "Invitation token")),
+    responses(
+        (status = 200, body = InvitationDto),
+        (status = 400, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn accept_invitation(
+    Path(token): Path<String>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<InvitationDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let invitation = InvitationService::new(app.db)
+        .accept_invitation(&actor, &token)
+        .await
+        .map_err(invitation_error)?;
+
+    Ok(Json((&invitation).into()))
+}
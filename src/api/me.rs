@@ -2,16 +2,24 @@ use axum::{extract::State, routing::get, Json, Router};
 use hyper::StatusCode;
 use jwt_authorizer::JwtClaims;
 use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
 
 use crate::{
+    db::notification::NotificationPreference,
     db::{self, user::PatchUser},
     server::application::App,
-    service::auth_service::MicrosoftClaims,
+    service::{
+        auth_service::MicrosoftClaims,
+        notification_service::{
+            NotificationError, NotificationService, PatchNotificationPreference,
+        },
+        swish,
+    },
 };
 
-use super::util::internal_error;
+use super::util::{current_user, internal_error};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 struct MeDto {
     id: i32,
     name: String,
@@ -19,15 +27,76 @@ struct MeDto {
     phone_number: Option<String>,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct PatchMeDto {
+    /// A Swedish mobile number, stored in the national format used by Swish.
+    /// An empty number removes it.
     phone_number: Option<String>,
 }
 
+#[derive(Serialize, ToSchema)]
+struct NotificationPreferenceDto {
+    expense_created: bool,
+    settlement_received: bool,
+    expense_commented: bool,
+    negative_balance: bool,
+    negative_balance_threshold: i32,
+    negative_balance_days: i32,
+}
+
+impl From<&NotificationPreference> for NotificationPreferenceDto {
+    fn from(value: &NotificationPreference) -> Self {
+        NotificationPreferenceDto {
+            expense_created: value.expense_created,
+            settlement_received: value.settlement_received,
+            expense_commented: value.expense_commented,
+            negative_balance: value.negative_balance,
+            negative_balance_threshold: value.negative_balance_threshold,
+            negative_balance_days: value.negative_balance_days,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PatchNotificationPreferenceDto {
+    expense_created: Option<bool>,
+    settlement_received: Option<bool>,
+    expense_commented: Option<bool>,
+    negative_balance: Option<bool>,
+    /// In minor units.
+    negative_balance_threshold: Option<i32>,
+    negative_balance_days: Option<i32>,
+}
+
+fn notification_error(err: NotificationError) -> (StatusCode, String) {
+    match err {
+        NotificationError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+        _ => internal_error(err),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_me,
+    patch_me,
+    get_notification_preference,
+    patch_notification_preference
+))]
+pub struct MeApi;
+
 pub fn get_me_api() -> Router<App> {
-    Router::new().route("/", get(get_me).patch(patch_me))
+    Router::new().route("/", get(get_me).patch(patch_me)).route(
+        "/notifications",
+        get(get_notification_preference).patch(patch_notification_preference),
+    )
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/me",
+    tag = "me",
+    responses((status = 200, body = MeDto))
+)]
 async fn get_me(
     State(app): State<App>,
     JwtClaims(user): JwtClaims<MicrosoftClaims>,
@@ -37,13 +106,23 @@ async fn get_me(
         .map_err(internal_error)?;
 
     Ok(Json(MeDto {
-        email: me.email,
+        email: me.email.unwrap_or_default(),
         id: me.id,
         name: me.name,
         phone_number: me.phone_number,
     }))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/me",
+    tag = "me",
+    request_body = PatchMeDto,
+    responses(
+        (status = 200, body = MeDto),
+        (status = 400, body = String)
+    )
+)]
 async fn patch_me(
     State(app): State<App>,
     JwtClaims(user): JwtClaims<MicrosoftClaims>,
@@ -53,21 +132,88 @@ async fn patch_me(
         .await
         .map_err(internal_error)?;
 
+    let phone_number = match patch_dto.phone_number {
+        Some(phone_number) if phone_number.trim().is_empty() => Some(None),
+        Some(phone_number) => {
+            let normalized = swish::normalize_phone_number(&phone_number).ok_or((
+                StatusCode::BAD_REQUEST,
+                format!("'{}' is not a Swedish mobile number", phone_number),
+            ))?;
+            Some(Some(normalized))
+        }
+        None => None,
+    };
+
     let me = db::user::patch_user(
         &app.db,
         PatchUser {
             id: me.id,
             email: None,
-            phone_number: patch_dto.phone_number,
+            phone_number,
         },
     )
     .await
     .map_err(internal_error)?;
 
     Ok(Json(MeDto {
-        email: me.email,
+        email: me.email.unwrap_or_default(),
         id: me.id,
         name: me.name,
         phone_number: me.phone_number,
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/me/notifications",
+    tag = "me",
+    responses((status = 200, body = NotificationPreferenceDto))
+)]
+async fn get_notification_preference(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<NotificationPreferenceDto>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+
+    let preference = NotificationService::new(app.db, app.mailer)
+        .get_preference(&me)
+        .await
+        .map_err(notification_error)?;
+
+    Ok(Json((&preference).into()))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/me/notifications",
+    tag = "me",
+    request_body = PatchNotificationPreferenceDto,
+    responses(
+        (status = 200, body = NotificationPreferenceDto),
+        (status = 400, body = String)
+    )
+)]
+async fn patch_notification_preference(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(patch): Json<PatchNotificationPreferenceDto>,
+) -> Result<Json<NotificationPreferenceDto>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+
+    let preference = NotificationService::new(app.db, app.mailer)
+        .update_preference(
+            &me,
+            PatchNotificationPreference {
+                expense_created: patch.expense_created,
+                settlement_received: patch.settlement_received,
+                expense_commented: patch.expense_commented,
+                negative_balance: patch.negative_balance,
+                negative_balance_threshold: patch.negative_balance_threshold,
+                negative_balance_days: patch.negative_balance_days,
+            },
+        )
+        .await
+        .map_err(notification_error)?;
+
+    Ok(Json((&preference).into()))
+}
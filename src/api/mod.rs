@@ -1,8 +1,23 @@
 pub mod expense;
 pub mod user;
 pub mod expense_category;
+pub mod export;
 pub mod balance;
+pub mod budget;
+pub mod change;
 pub mod auth;
 pub mod me;
+pub mod push;
 pub mod image;
+pub mod import;
+pub mod group;
+pub mod report;
+pub mod settlement;
+pub mod sync;
+pub mod webhook;
+pub mod guest;
+pub mod invitation;
+pub mod split_profile;
+pub mod period_close;
+pub mod openapi;
 mod util;
@@ -0,0 +1,125 @@
+use axum::{response::Html, routing::get, Json, Router};
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::server::application::App;
+
+use super::{
+    auth::AuthApi, balance::BalanceApi, budget::BudgetApi, change::ChangeApi, expense::ExpenseApi,
+    expense_category::ExpenseCategoryApi, export::ExportApi, group::GroupApi, guest::GuestApi,
+    image::ImageApi, import::ImportApi, invitation::InvitationApi, me::MeApi,
+    period_close::PeriodCloseApi, push::PushApi, report::ReportApi, settlement::SettlementApi,
+    split_profile::SplitProfileApi, sync::SyncApi, user::UserApi, webhook::WebhookApi,
+};
+
+/// Every route requires an access token unless it says otherwise.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "jostrid", description = "Shared expenses and balances."),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
+)]
+struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// The document is assembled from the `#[utoipa::path]` annotations of the
+/// handlers, so it changes together with them.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    for api in [
+        AuthApi::openapi(),
+        BalanceApi::openapi(),
+        BudgetApi::openapi(),
+        ChangeApi::openapi(),
+        ExpenseApi::openapi(),
+        ExpenseCategoryApi::openapi(),
+        ExportApi::openapi(),
+        GroupApi::openapi(),
+        GuestApi::openapi(),
+        ImageApi::openapi(),
+        ImportApi::openapi(),
+        InvitationApi::openapi(),
+        MeApi::openapi(),
+        PeriodCloseApi::openapi(),
+        PushApi::openapi(),
+        ReportApi::openapi(),
+        SettlementApi::openapi(),
+        SplitProfileApi::openapi(),
+        SyncApi::openapi(),
+        UserApi::openapi(),
+        WebhookApi::openapi(),
+    ] {
+        openapi.merge(api);
+    }
+
+    openapi
+}
+
+/// Scalar's API reference, loaded from their CDN and pointed at our document.
+static API_REFERENCE: &str = r#"<!doctype html>
+<html>
+  <head>
+    <title>jostrid API</title>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+  </head>
+  <body>
+    <script id="api-reference" data-url="/api/openapi.json"></script>
+    <script src="https://cdn.jsdelivr.net/npm/@scalar/api-reference"></script>
+  </body>
+</html>
+"#;
+
+/// Served without authentication so the documentation can be browsed before
+/// signing in.
+pub fn get_openapi_api() -> Router<App> {
+    Router::new()
+        .route("/api/docs", get(get_api_reference))
+        .route("/api/openapi.json", get(get_openapi))
+}
+
+async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(openapi())
+}
+
+async fn get_api_reference() -> Html<&'static str> {
+    Html(API_REFERENCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::openapi;
+
+    /// The committed document, which clients are generated from.
+    const COMMITTED: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    /// Fails when a handler changed without the committed document. Run with
+    /// `UPDATE_OPENAPI=1` to write the current document instead.
+    #[test]
+    fn committed_document_is_up_to_date() {
+        let generated = openapi().to_pretty_json().expect("The document serializes") + "\n";
+
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(COMMITTED, &generated).expect("Failed to write openapi.json");
+            return;
+        }
+        let committed = std::fs::read_to_string(COMMITTED).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "openapi.json is out of date, run `UPDATE_OPENAPI=1 cargo test` and commit it"
+        );
+    }
+}
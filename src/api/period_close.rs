@@ -0,0 +1,327 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{
+        group::DEFAULT_GROUP_ID,
+        period_close::{PeriodClose, StatementLine},
+    },
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        period_close_service::{
+            CurrencyStatement, PeriodCloseError, PeriodCloseService, Statement,
+        },
+        statement,
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct PeriodCloseDto {
+    id: i32,
+    group_id: i32,
+    /// Expenses of the group created before this can't be changed.
+    closed_until: chrono::DateTime<Utc>,
+    closed_by: i32,
+    created_at: chrono::DateTime<Utc>,
+}
+
+impl From<&PeriodClose> for PeriodCloseDto {
+    fn from(value: &PeriodClose) -> Self {
+        PeriodCloseDto {
+            id: value.id,
+            group_id: value.group_id,
+            closed_until: value.closed_until,
+            closed_by: value.closed_by,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ClosePeriodDto {
+    group_id: Option<i32>,
+    /// Has to be later than the group's previous close and not in the future.
+    closed_until: chrono::DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StatementDto {
+    close: PeriodCloseDto,
+    group_name: String,
+    user_id: i32,
+    user_name: String,
+    /// Where the previous close ended, not set for the group's first close.
+    from: Option<chrono::DateTime<Utc>>,
+    to: chrono::DateTime<Utc>,
+    currencies: Vec<CurrencyStatementDto>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CurrencyStatementDto {
+    currency: String,
+    opening_balance: i64,
+    expenses: Vec<StatementLineDto>,
+    /// The sum of the user's shares of the expenses.
+    expenses_total: i64,
+    settlements: Vec<StatementLineDto>,
+    /// The sum of the user's shares of the settlements.
+    settlements_total: i64,
+    closing_balance: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct StatementLineDto {
+    expense_id: i32,
+    created_at: chrono::DateTime<Utc>,
+    name: String,
+    total: i32,
+    paid_by: i32,
+    paid_by_name: String,
+    /// The user's share, negative when the user owes.
+    share: i64,
+}
+
+impl From<&StatementLine> for StatementLineDto {
+    fn from(value: &StatementLine) -> Self {
+        StatementLineDto {
+            expense_id: value.expense_id,
+            created_at: value.created_at,
+            name: value.name.clone(),
+            total: value.total,
+            paid_by: value.paid_by,
+            paid_by_name: value.paid_by_name.clone(),
+            share: value.share,
+        }
+    }
+}
+
+impl From<&CurrencyStatement> for CurrencyStatementDto {
+    fn from(value: &CurrencyStatement) -> Self {
+        CurrencyStatementDto {
+            currency: value.currency.clone(),
+            opening_balance: value.opening_balance,
+            expenses: value.expenses.iter().map(|line| line.into()).collect(),
+            expenses_total: value.expenses.iter().map(|line| line.share).sum(),
+            settlements: value.settlements.iter().map(|line| line.into()).collect(),
+            settlements_total: value.settlements.iter().map(|line| line.share).sum(),
+            closing_balance: value.closing_balance,
+        }
+    }
+}
+
+impl From<&Statement> for StatementDto {
+    fn from(value: &Statement) -> Self {
+        StatementDto {
+            close: (&value.close).into(),
+            group_name: value.group.name.clone(),
+            user_id: value.user.id,
+            user_name: value.user.name.clone(),
+            from: value.from,
+            to: value.close.closed_until,
+            currencies: value
+                .currencies
+                .iter()
+                .map(|currency| currency.into())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetPeriodClosesQuery {
+    group_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StatementQuery {
+    /// Defaults to the current user.
+    user_id: Option<i32>,
+}
+
+fn period_close_error(err: PeriodCloseError) -> (StatusCode, String) {
+    match err {
+        PeriodCloseError::Sqlx(err) => internal_error(err),
+        PeriodCloseError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        PeriodCloseError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        PeriodCloseError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_period_closes,
+    close_period,
+    get_statement,
+    get_statement_html,
+    get_statement_pdf
+))]
+pub struct PeriodCloseApi;
+
+pub fn get_period_close_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_period_closes).post(close_period))
+        .route("/:id/statement", get(get_statement))
+        .route("/:id/statement.html", get(get_statement_html))
+        .route("/:id/statement.pdf", get(get_statement_pdf))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/period_close",
+    tag = "period_close",
+    params(GetPeriodClosesQuery),
+    responses(
+        (status = 200, body = [PeriodCloseDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_period_closes(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetPeriodClosesQuery>,
+) -> Result<Json<Vec<PeriodCloseDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let closes = PeriodCloseService::new(app.db)
+        .get_closes(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(period_close_error)?;
+
+    Ok(Json(closes.iter().map(|close| close.into()).collect()))
+}
+
+/// Locks the group's expenses created before `closed_until` against changes
+/// and snapshots the balances at that point.
+#[utoipa::path(
+    post,
+    path = "/api/period_close",
+    tag = "period_close",
+    request_body = ClosePeriodDto,
+    responses(
+        (status = 200, body = PeriodCloseDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn close_period(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(close): Json<ClosePeriodDto>,
+) -> Result<Json<PeriodCloseDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let close = PeriodCloseService::new(app.db)
+        .close_period(
+            &actor,
+            close.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            close.closed_until,
+        )
+        .await
+        .map_err(period_close_error)?;
+
+    Ok(Json((&close).into()))
+}
+
+async fn load_statement(
+    app: App,
+    claims: &MicrosoftClaims,
+    close_id: i32,
+    query: StatementQuery,
+) -> Result<Statement, (StatusCode, String)> {
+    let actor = current_user(&app.db, claims).await?;
+
+    PeriodCloseService::new(app.db)
+        .get_statement(&actor, close_id, query.user_id.unwrap_or(actor.id))
+        .await
+        .map_err(period_close_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/period_close/{id}/statement",
+    tag = "period_close",
+    params(("id" = i32, Path, description = "Period close id"), StatementQuery),
+    responses(
+        (status = 200, body = StatementDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_statement(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Json<StatementDto>, (StatusCode, String)> {
+    let statement = load_statement(app, &claims, id, query).await?;
+
+    Ok(Json((&statement).into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/period_close/{id}/statement.html",
+    tag = "period_close",
+    params(("id" = i32, Path, description = "Period close id"), StatementQuery),
+    responses(
+        (status = 200, content_type = "text/html", body = String),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_statement_html(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let statement = load_statement(app, &claims, id, query).await?;
+
+    Ok(Html(statement::render_html(&statement)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/period_close/{id}/statement.pdf",
+    tag = "period_close",
+    params(("id" = i32, Path, description = "Period close id"), StatementQuery),
+    responses(
+        (status = 200, content_type = "application/pdf", body = Vec<u8>),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_statement_pdf(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<StatementQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let statement = load_statement(app, &claims, id, query).await?;
+    let disposition = format!(
+        "attachment; filename=\"statement-{}-{}.pdf\"",
+        id, statement.user.id
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        statement::render_pdf(&statement),
+    ))
+}
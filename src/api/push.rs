@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::push_subscription::PushSubscription,
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        push_service::{NewPushSubscription, PushError, PushService},
+        web_push::WebPushError,
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct VapidPublicKeyDto {
+    public_key: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PushSubscriptionKeysDto {
+    p256dh: String,
+    auth: String,
+}
+
+/// Matches `PushSubscription.toJSON()` in the browser.
+#[derive(Deserialize, ToSchema)]
+struct NewPushSubscriptionDto {
+    endpoint: String,
+    keys: PushSubscriptionKeysDto,
+    device_name: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct PushSubscriptionDto {
+    id: i32,
+    user_id: i32,
+    endpoint: String,
+    device_name: Option<String>,
+    created_at: chrono::DateTime<Utc>,
+}
+
+impl From<&PushSubscription> for PushSubscriptionDto {
+    fn from(value: &PushSubscription) -> Self {
+        PushSubscriptionDto {
+            id: value.id,
+            user_id: value.user_id,
+            endpoint: value.endpoint.clone(),
+            device_name: value.device_name.clone(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+fn push_error(err: PushError) -> (StatusCode, String) {
+    match err {
+        PushError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        PushError::Invalid(_) | PushError::WebPush(WebPushError::InvalidSubscription(_)) => {
+            (StatusCode::BAD_REQUEST, err.to_string())
+        }
+        _ => internal_error(err),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_vapid_public_key,
+    get_subscriptions,
+    create_subscription,
+    delete_subscription
+))]
+pub struct PushApi;
+
+pub fn get_push_api() -> Router<App> {
+    Router::new()
+        .route("/vapid_public_key", get(get_vapid_public_key))
+        .route(
+            "/subscription",
+            get(get_subscriptions).post(create_subscription),
+        )
+        .route("/subscription/:id", delete(delete_subscription))
+}
+
+/// The application server key to pass to `pushManager.subscribe`.
+#[utoipa::path(
+    get,
+    path = "/api/push/vapid_public_key",
+    tag = "push",
+    responses((status = 200, body = VapidPublicKeyDto))
+)]
+async fn get_vapid_public_key(State(app): State<App>) -> Json<VapidPublicKeyDto> {
+    Json(VapidPublicKeyDto {
+        public_key: PushService::new(app.db, app.vapid_key)
+            .public_key()
+            .to_string(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/push/subscription",
+    tag = "push",
+    responses((status = 200, body = [PushSubscriptionDto]))
+)]
+async fn get_subscriptions(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<Json<Vec<PushSubscriptionDto>>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+
+    let subscriptions = PushService::new(app.db, app.vapid_key)
+        .get_subscriptions(&me)
+        .await
+        .map_err(push_error)?;
+
+    Ok(Json(subscriptions.iter().map(|s| s.into()).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/push/subscription",
+    tag = "push",
+    request_body = NewPushSubscriptionDto,
+    responses(
+        (status = 200, body = PushSubscriptionDto),
+        (status = 400, body = String)
+    )
+)]
+async fn create_subscription(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(subscription): Json<NewPushSubscriptionDto>,
+) -> Result<Json<PushSubscriptionDto>, (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+
+    let subscription = PushService::new(app.db, app.vapid_key)
+        .subscribe(
+            &me,
+            NewPushSubscription {
+                endpoint: subscription.endpoint,
+                p256dh: subscription.keys.p256dh,
+                auth: subscription.keys.auth,
+                device_name: subscription.device_name,
+            },
+        )
+        .await
+        .map_err(push_error)?;
+
+    Ok(Json((&subscription).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/push/subscription/{id}",
+    tag = "push",
+    params(("id" = i32, Path, description = "Subscription id")),
+    responses(
+        (status = 200),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_subscription(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Path(id): Path<i32>,
+) -> Result<(), (StatusCode, String)> {
+    let me = current_user(&app.db, &claims).await?;
+
+    PushService::new(app.db, app.vapid_key)
+        .unsubscribe(&me, id)
+        .await
+        .map_err(push_error)
+}
@@ -0,0 +1,215 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{
+        group::DEFAULT_GROUP_ID,
+        report::{CategoryTotal, PeriodTotal, ReportFilter, UserTotal},
+    },
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        report_service::{
+            ParticipantTotal, ReportError, ReportInterval, ReportService, SpendingReport,
+        },
+    },
+};
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum IntervalDto {
+    Week,
+    #[default]
+    Month,
+}
+
+impl From<IntervalDto> for ReportInterval {
+    fn from(value: IntervalDto) -> Self {
+        match value {
+            IntervalDto::Week => ReportInterval::Week,
+            IntervalDto::Month => ReportInterval::Month,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SpendingReportQuery {
+    currency: String,
+    group_id: Option<i32>,
+    /// Defaults to a year before `to`.
+    from: Option<chrono::DateTime<Utc>>,
+    /// Defaults to now.
+    to: Option<chrono::DateTime<Utc>>,
+    user_id: Option<i32>,
+    #[serde(default)]
+    interval: IntervalDto,
+}
+
+#[derive(Serialize, ToSchema)]
+struct CategoryTotalDto {
+    category_id: Option<i32>,
+    category_name: Option<String>,
+    parent_id: Option<i32>,
+    amount: i64,
+}
+
+impl From<&CategoryTotal> for CategoryTotalDto {
+    fn from(value: &CategoryTotal) -> Self {
+        CategoryTotalDto {
+            category_id: value.category_id,
+            category_name: value.category_name.clone(),
+            parent_id: value.parent_id,
+            amount: value.amount,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct UserTotalDto {
+    user_id: i32,
+    amount: i64,
+}
+
+impl From<&UserTotal> for UserTotalDto {
+    fn from(value: &UserTotal) -> Self {
+        UserTotalDto {
+            user_id: value.user_id,
+            amount: value.amount,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct ParticipantTotalDto {
+    user_id: i32,
+    consumed: i64,
+    paid: i64,
+    settlements_paid: i64,
+    settlements_received: i64,
+}
+
+impl From<&ParticipantTotal> for ParticipantTotalDto {
+    fn from(value: &ParticipantTotal) -> Self {
+        ParticipantTotalDto {
+            user_id: value.user_id,
+            consumed: value.consumed,
+            paid: value.paid,
+            settlements_paid: value.settlements_paid,
+            settlements_received: value.settlements_received,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct PeriodTotalDto {
+    period_start: chrono::DateTime<Utc>,
+    amount: i64,
+}
+
+impl From<&PeriodTotal> for PeriodTotalDto {
+    fn from(value: &PeriodTotal) -> Self {
+        PeriodTotalDto {
+            period_start: value.period_start,
+            amount: value.amount,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct SpendingReportDto {
+    group_id: i32,
+    currency: String,
+    from: chrono::DateTime<Utc>,
+    to: chrono::DateTime<Utc>,
+    user_id: Option<i32>,
+    total_consumed: i64,
+    by_category: Vec<CategoryTotalDto>,
+    by_payer: Vec<UserTotalDto>,
+    by_participant: Vec<ParticipantTotalDto>,
+    by_period: Vec<PeriodTotalDto>,
+}
+
+impl From<&SpendingReport> for SpendingReportDto {
+    fn from(value: &SpendingReport) -> Self {
+        SpendingReportDto {
+            group_id: value.filter.group_id,
+            currency: value.filter.currency.clone(),
+            from: value.filter.from,
+            to: value.filter.to,
+            user_id: value.filter.user_id,
+            total_consumed: value.total_consumed,
+            by_category: value.by_category.iter().map(|total| total.into()).collect(),
+            by_payer: value.by_payer.iter().map(|total| total.into()).collect(),
+            by_participant: value
+                .by_participant
+                .iter()
+                .map(|total| total.into())
+                .collect(),
+            by_period: value.by_period.iter().map(|total| total.into()).collect(),
+        }
+    }
+}
+
+fn report_error(err: ReportError) -> (StatusCode, String) {
+    match err {
+        ReportError::Sqlx(err) => internal_error(err),
+        ReportError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        ReportError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_spending_report), components(schemas(IntervalDto)))]
+pub struct ReportApi;
+
+pub fn get_report_api() -> Router<App> {
+    Router::new().route("/spending", get(get_spending_report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/report/spending",
+    tag = "report",
+    params(SpendingReportQuery),
+    responses(
+        (status = 200, body = SpendingReportDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn get_spending_report(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SpendingReportQuery>,
+) -> Result<Json<SpendingReportDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let to = query.to.unwrap_or(Utc::now());
+    let from = query.from.unwrap_or(to - Duration::days(365));
+
+    let report = ReportService::new(app.db)
+        .get_spending_report(
+            &actor,
+            ReportFilter {
+                group_id: query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+                currency: query.currency,
+                from,
+                to,
+                user_id: query.user_id,
+            },
+            query.interval.into(),
+        )
+        .await
+        .map_err(report_error)?;
+
+    Ok(Json((&report).into()))
+}
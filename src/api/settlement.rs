@@ -0,0 +1,254 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::group::DEFAULT_GROUP_ID,
+    server::application::App,
+    service::{
+        amount::format_amount,
+        auth_service::MicrosoftClaims,
+        settlement_service::{
+            SettlementError, SettlementService, SuggestedSettlement, SwishPayment,
+        },
+        swish,
+    },
+};
+
+/// Pixels per QR module in rendered PNGs.
+const QR_PNG_SCALE: usize = 8;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SuggestedSettlementQuery {
+    group_id: Option<i32>,
+    currency: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SwishQuery {
+    group_id: Option<i32>,
+    receiver_id: i32,
+    /// Defaults to the suggested settlement.
+    amount: Option<i64>,
+    message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SuggestedSettlementDto {
+    payer_id: i32,
+    receiver_id: i32,
+    amount: i64,
+    currency: String,
+}
+
+impl From<&SuggestedSettlement> for SuggestedSettlementDto {
+    fn from(value: &SuggestedSettlement) -> Self {
+        SuggestedSettlementDto {
+            payer_id: value.payer_id,
+            receiver_id: value.receiver_id,
+            amount: value.amount,
+            currency: value.currency.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct SwishFieldDto {
+    value: String,
+    editable: bool,
+}
+
+/// The prefilled payment format of the Swish QR code API.
+#[derive(Serialize, ToSchema)]
+struct SwishPayloadDto {
+    payee: SwishFieldDto,
+    amount: SwishFieldDto,
+    message: SwishFieldDto,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SwishPaymentDto {
+    #[serde(flatten)]
+    settlement: SuggestedSettlementDto,
+    payload: SwishPayloadDto,
+    deep_link: String,
+    qr_svg: String,
+}
+
+impl From<&SwishPayment> for SwishPaymentDto {
+    fn from(value: &SwishPayment) -> Self {
+        SwishPaymentDto {
+            settlement: (&value.settlement).into(),
+            payload: SwishPayloadDto {
+                payee: SwishFieldDto {
+                    value: value.payee.clone(),
+                    editable: false,
+                },
+                amount: SwishFieldDto {
+                    value: format_amount(value.settlement.amount, swish::DECIMAL_SEPARATOR),
+                    editable: true,
+                },
+                message: SwishFieldDto {
+                    value: value.message.clone(),
+                    editable: true,
+                },
+            },
+            deep_link: value.deep_link.clone(),
+            qr_svg: swish::qr_svg(&value.deep_link),
+        }
+    }
+}
+
+fn settlement_error(err: SettlementError) -> (StatusCode, String) {
+    match err {
+        SettlementError::Sqlx(err) => internal_error(err),
+        SettlementError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        SettlementError::NoSwishNumber(_) => (StatusCode::CONFLICT, err.to_string()),
+        SettlementError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_suggested_settlements,
+    get_swish_payment,
+    get_swish_qr_png,
+    get_swish_qr_svg
+))]
+pub struct SettlementApi;
+
+pub fn get_settlement_api() -> Router<App> {
+    Router::new()
+        .route("/suggested", get(get_suggested_settlements))
+        .route("/swish", get(get_swish_payment))
+        .route("/swish/qr.png", get(get_swish_qr_png))
+        .route("/swish/qr.svg", get(get_swish_qr_svg))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement/suggested",
+    tag = "settlement",
+    params(SuggestedSettlementQuery),
+    responses(
+        (status = 200, body = [SuggestedSettlementDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_suggested_settlements(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SuggestedSettlementQuery>,
+) -> Result<Json<Vec<SuggestedSettlementDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    let settlements = SettlementService::new(app.db)
+        .suggest_settlements(
+            &actor,
+            query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            &query.currency,
+        )
+        .await
+        .map_err(settlement_error)?;
+
+    Ok(Json(settlements.iter().map(|s| s.into()).collect()))
+}
+
+async fn swish_payment(
+    app: App,
+    claims: &MicrosoftClaims,
+    query: SwishQuery,
+) -> Result<SwishPayment, (StatusCode, String)> {
+    let actor = current_user(&app.db, claims).await?;
+
+    SettlementService::new(app.db)
+        .swish_payment(
+            &actor,
+            query.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            query.receiver_id,
+            query.amount,
+            query.message,
+        )
+        .await
+        .map_err(settlement_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement/swish",
+    tag = "settlement",
+    params(SwishQuery),
+    responses(
+        (status = 200, body = SwishPaymentDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 409, body = String)
+    )
+)]
+async fn get_swish_payment(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SwishQuery>,
+) -> Result<Json<SwishPaymentDto>, (StatusCode, String)> {
+    let payment = swish_payment(app, &claims, query).await?;
+
+    Ok(Json((&payment).into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement/swish/qr.png",
+    tag = "settlement",
+    params(SwishQuery),
+    responses(
+        (status = 200, content_type = "image/png", body = Vec<u8>),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 409, body = String)
+    )
+)]
+async fn get_swish_qr_png(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SwishQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let payment = swish_payment(app, &claims, query).await?;
+    let png = swish::qr_png(&payment.deep_link, QR_PNG_SCALE).map_err(internal_error)?;
+
+    Ok(([(header::CONTENT_TYPE, "image/png")], png))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/settlement/swish/qr.svg",
+    tag = "settlement",
+    params(SwishQuery),
+    responses(
+        (status = 200, content_type = "image/svg+xml", body = String),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 409, body = String)
+    )
+)]
+async fn get_swish_qr_svg(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<SwishQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let payment = swish_payment(app, &claims, query).await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        swish::qr_svg(&payment.deep_link),
+    ))
+}
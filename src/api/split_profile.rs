@@ -0,0 +1,229 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{group::DEFAULT_GROUP_ID, split_profile::SplitProfileWithWeights},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        split_profile_service::{
+            NewSplitProfile, NewSplitProfileWeight, SplitProfileError, SplitProfileService,
+        },
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct SplitProfileDto {
+    id: i32,
+    group_id: i32,
+    name: String,
+    /// Applied to new expenses of this category or its sub-categories that
+    /// come without shares.
+    category_id: Option<i32>,
+    /// Applied to new expenses without shares that no category profile
+    /// matches.
+    is_default: bool,
+    weights: Vec<SplitProfileWeightDto>,
+    created_at: chrono::DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+struct SplitProfileWeightDto {
+    user_id: i32,
+    weight: i32,
+}
+
+impl From<&SplitProfileWithWeights> for SplitProfileDto {
+    fn from((profile, weights): &SplitProfileWithWeights) -> Self {
+        SplitProfileDto {
+            id: profile.id,
+            group_id: profile.group_id,
+            name: profile.name.clone(),
+            category_id: profile.category_id,
+            is_default: profile.is_default,
+            weights: weights
+                .iter()
+                .map(|weight| SplitProfileWeightDto {
+                    user_id: weight.user_id,
+                    weight: weight.weight,
+                })
+                .collect(),
+            created_at: profile.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpsertSplitProfileDto {
+    group_id: Option<i32>,
+    name: String,
+    category_id: Option<i32>,
+    /// Setting a new default replaces the group's previous one.
+    #[serde(default)]
+    is_default: bool,
+    weights: Vec<SplitProfileWeightDto>,
+}
+
+impl From<UpsertSplitProfileDto> for NewSplitProfile {
+    fn from(value: UpsertSplitProfileDto) -> Self {
+        NewSplitProfile {
+            group_id: value.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            name: value.name,
+            category_id: value.category_id,
+            is_default: value.is_default,
+            weights: value
+                .weights
+                .into_iter()
+                .map(|weight| NewSplitProfileWeight {
+                    user_id: weight.user_id,
+                    weight: weight.weight,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetSplitProfilesQuery {
+    group_id: Option<i32>,
+}
+
+fn split_profile_error(err: SplitProfileError) -> (StatusCode, String) {
+    match err {
+        SplitProfileError::Sqlx(err) => internal_error(err),
+        SplitProfileError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        SplitProfileError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        SplitProfileError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_split_profiles,
+    create_split_profile,
+    update_split_profile,
+    delete_split_profile
+))]
+pub struct SplitProfileApi;
+
+pub fn get_split_profile_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_split_profiles).post(create_split_profile))
+        .route(
+            "/:id",
+            put(update_split_profile).delete(delete_split_profile),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/split_profile",
+    tag = "split_profile",
+    params(GetSplitProfilesQuery),
+    responses(
+        (status = 200, body = [SplitProfileDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_split_profiles(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetSplitProfilesQuery>,
+) -> Result<Json<Vec<SplitProfileDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let profiles = SplitProfileService::new(app.db)
+        .get_profiles(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(split_profile_error)?;
+
+    Ok(Json(
+        profiles.iter().map(|profile| profile.into()).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/split_profile",
+    tag = "split_profile",
+    request_body = UpsertSplitProfileDto,
+    responses(
+        (status = 200, body = SplitProfileDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_split_profile(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(profile): Json<UpsertSplitProfileDto>,
+) -> Result<Json<SplitProfileDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let profile = SplitProfileService::new(app.db)
+        .create_profile(&actor, profile.into())
+        .await
+        .map_err(split_profile_error)?;
+
+    Ok(Json((&profile).into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/split_profile/{id}",
+    tag = "split_profile",
+    params(("id" = i32, Path, description = "Split profile id")),
+    request_body = UpsertSplitProfileDto,
+    responses(
+        (status = 200, body = SplitProfileDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn update_split_profile(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(profile): Json<UpsertSplitProfileDto>,
+) -> Result<Json<SplitProfileDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let profile = SplitProfileService::new(app.db)
+        .update_profile(&actor, id, profile.into())
+        .await
+        .map_err(split_profile_error)?;
+
+    Ok(Json((&profile).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/split_profile/{id}",
+    tag = "split_profile",
+    params(("id" = i32, Path, description = "Split profile id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_split_profile(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    SplitProfileService::new(app.db)
+        .delete_profile(&actor, id)
+        .await
+        .map_err(split_profile_error)
+}
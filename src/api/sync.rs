@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    api::util::{current_user, internal_error},
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        sync_service::{
+            Mutation, MutationKind, MutationResult, MutationStatus, MutationTarget, SyncChanges,
+            SyncError, SyncService, SyncedExpense,
+        },
+    },
+};
+
+use super::{
+    expense::{ExpenseWithEverythingDto, UpsertExpenseDto},
+    expense_category::ExpenseCategoryDto,
+};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetChangesQuery {
+    /// The cursor returned by the previous sync, omitted on the first one.
+    cursor: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SyncedExpenseDto {
+    #[serde(flatten)]
+    expense: ExpenseWithEverythingDto,
+    version: Option<i64>,
+}
+
+impl From<&SyncedExpense> for SyncedExpenseDto {
+    fn from(value: &SyncedExpense) -> Self {
+        SyncedExpenseDto {
+            expense: (&value.expense).into(),
+            version: value.version,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct SyncChangesDto {
+    cursor: i64,
+    full: bool,
+    expenses: Vec<SyncedExpenseDto>,
+    deleted_expense_ids: Vec<i32>,
+    categories: Vec<ExpenseCategoryDto>,
+    deleted_category_ids: Vec<i32>,
+}
+
+impl From<&SyncChanges> for SyncChangesDto {
+    fn from(value: &SyncChanges) -> Self {
+        SyncChangesDto {
+            cursor: value.cursor,
+            full: value.full,
+            expenses: value
+                .expenses
+                .iter()
+                .map(|expense| expense.into())
+                .collect(),
+            deleted_expense_ids: value.deleted_expense_ids.clone(),
+            categories: value
+                .categories
+                .iter()
+                .map(|category| category.into())
+                .collect(),
+            deleted_category_ids: value.deleted_category_ids.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MutationsDto {
+    mutations: Vec<MutationDto>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct MutationDto {
+    client_id: Uuid,
+    #[serde(flatten)]
+    kind: MutationKindDto,
+}
+
+/// Update and delete refer to the expense either by `expense_id` or, if it
+/// was created offline and the client hasn't learnt its id yet, by the
+/// `client_id` of the mutation that created it.
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum MutationKindDto {
+    Create {
+        expense: UpsertExpenseDto,
+    },
+    Update {
+        expense_id: Option<i32>,
+        client_expense_id: Option<Uuid>,
+        base_version: Option<i64>,
+        expense: UpsertExpenseDto,
+    },
+    Delete {
+        expense_id: Option<i32>,
+        client_expense_id: Option<Uuid>,
+        base_version: Option<i64>,
+    },
+}
+
+#[derive(Serialize, ToSchema)]
+struct MutationResultDto {
+    client_id: Uuid,
+    #[schema(value_type = String)]
+    status: &'static str,
+    reason: Option<String>,
+    expense_id: Option<i32>,
+    expense: Option<SyncedExpenseDto>,
+}
+
+impl From<&MutationResult> for MutationResultDto {
+    fn from(value: &MutationResult) -> Self {
+        let (status, reason) = match &value.status {
+            MutationStatus::Applied => ("applied", None),
+            MutationStatus::Duplicate => ("duplicate", None),
+            MutationStatus::Conflict => ("conflict", None),
+            MutationStatus::Rejected(reason) => ("rejected", Some(reason.clone())),
+        };
+
+        MutationResultDto {
+            client_id: value.client_id,
+            status,
+            reason,
+            expense_id: value.expense_id,
+            expense: value.expense.as_ref().map(|expense| expense.into()),
+        }
+    }
+}
+
+impl TryFrom<MutationDto> for Mutation {
+    type Error = (StatusCode, String);
+
+    fn try_from(value: MutationDto) -> Result<Self, Self::Error> {
+        let kind = match value.kind {
+            MutationKindDto::Create { expense } => MutationKind::Create(expense.into()),
+            MutationKindDto::Update {
+                expense_id,
+                client_expense_id,
+                base_version,
+                expense,
+            } => MutationKind::Update {
+                target: mutation_target(value.client_id, expense_id, client_expense_id)?,
+                base_version,
+                expense: expense.into(),
+            },
+            MutationKindDto::Delete {
+                expense_id,
+                client_expense_id,
+                base_version,
+            } => MutationKind::Delete {
+                target: mutation_target(value.client_id, expense_id, client_expense_id)?,
+                base_version,
+            },
+        };
+
+        Ok(Mutation {
+            client_id: value.client_id,
+            kind,
+        })
+    }
+}
+
+fn mutation_target(
+    client_id: Uuid,
+    expense_id: Option<i32>,
+    client_expense_id: Option<Uuid>,
+) -> Result<MutationTarget, (StatusCode, String)> {
+    match (expense_id, client_expense_id) {
+        (Some(expense_id), None) => Ok(MutationTarget::Expense(expense_id)),
+        (None, Some(client_expense_id)) => Ok(MutationTarget::Created(client_expense_id)),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Mutation {} must have either an expense_id or a client_expense_id",
+                client_id
+            ),
+        )),
+    }
+}
+
+fn sync_error(err: SyncError) -> (StatusCode, String) {
+    match err {
+        SyncError::Sqlx(err) => internal_error(err),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(get_changes, apply_mutations))]
+pub struct SyncApi;
+
+pub fn get_sync_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_changes))
+        .route("/mutations", post(apply_mutations))
+}
+
+/// Everything visible to the user that changed after `cursor`.
+#[utoipa::path(
+    get,
+    path = "/api/sync",
+    tag = "sync",
+    params(GetChangesQuery),
+    responses((status = 200, body = SyncChangesDto))
+)]
+async fn get_changes(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetChangesQuery>,
+) -> Result<Json<SyncChangesDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let changes = SyncService::new(app.db, app.events)
+        .get_changes(&actor, query.cursor)
+        .await
+        .map_err(sync_error)?;
+
+    Ok(Json((&changes).into()))
+}
+
+/// Applies mutations queued while offline, in order.
+#[utoipa::path(
+    post,
+    path = "/api/sync/mutations",
+    tag = "sync",
+    request_body = MutationsDto,
+    responses(
+        (status = 200, body = [MutationResultDto]),
+        (status = 400, body = String)
+    )
+)]
+async fn apply_mutations(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(body): Json<MutationsDto>,
+) -> Result<Json<Vec<MutationResultDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let mutations = body
+        .mutations
+        .into_iter()
+        .map(Mutation::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let results = SyncService::new(app.db, app.events)
+        .apply_mutations(&actor, mutations)
+        .await
+        .map_err(sync_error)?;
+
+    Ok(Json(results.iter().map(|result| result.into()).collect()))
+}
@@ -1,5 +1,6 @@
 use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
 use serde::{Deserialize, Serialize};
+use utoipa::{OpenApi, ToSchema};
 
 use crate::{
     api::util::internal_error,
@@ -7,12 +8,13 @@ use crate::{
     server::application::App,
 };
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct UserDto {
     id: i32,
     name: String,
-    email: String,
+    email: Option<String>,
     phone_number: Option<String>,
+    is_guest: bool,
 }
 
 impl From<&User> for UserDto {
@@ -22,14 +24,25 @@ impl From<&User> for UserDto {
             name: value.name.clone(),
             email: value.email.clone(),
             phone_number: value.phone_number.clone(),
+            is_guest: value.is_guest,
         }
     }
 }
 
+#[derive(OpenApi)]
+#[openapi(paths(get_users))]
+pub struct UserApi;
+
 pub fn get_user_api() -> Router<App> {
     Router::new().route("/", get(get_users))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/user",
+    tag = "user",
+    responses((status = 200, body = [UserDto]))
+)]
 async fn get_users(State(app): State<App>) -> Result<Json<Vec<UserDto>>, (StatusCode, String)> {
     let users = db::user::get_users(&app.db).await.map_err(internal_error)?;
 
@@ -1,4 +1,11 @@
 use axum::http::StatusCode;
+use serde::{Deserialize, Deserializer};
+use sqlx::PgPool;
+
+use crate::{
+    db::{self, user::User},
+    service::auth_service::MicrosoftClaims,
+};
 
 /// Utility function for mapping any error into a `500 Internal Server Error`
 /// response.
@@ -8,3 +15,31 @@ where
 {
     (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
+
+/// Looks up the user making the request from the claims of its token.
+pub async fn current_user(
+    pool: &PgPool,
+    claims: &MicrosoftClaims,
+) -> Result<User, (StatusCode, String)> {
+    match db::user::get_user_by_email(pool, &claims.preferred_username).await {
+        Ok(user) if user.disabled_at.is_some() => {
+            Err((StatusCode::FORBIDDEN, "The user is disabled".to_string()))
+        }
+        Ok(user) => Ok(user),
+        Err(sqlx::Error::RowNotFound) => Err((
+            StatusCode::FORBIDDEN,
+            "No user found for the given token".to_string(),
+        )),
+        Err(err) => Err(internal_error(err)),
+    }
+}
+
+/// Tells a missing field, `None`, apart from one set to `null`, `Some(None)`.
+/// Goes with `#[serde(default, deserialize_with = "double_option")]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
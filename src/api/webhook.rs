@@ -0,0 +1,281 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use jwt_authorizer::JwtClaims;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    api::util::{current_user, internal_error},
+    db::{
+        group::DEFAULT_GROUP_ID,
+        webhook::{Webhook, WebhookDelivery},
+    },
+    server::application::App,
+    service::{
+        auth_service::MicrosoftClaims,
+        webhook_service::{NewWebhook, WebhookError, WebhookService},
+    },
+};
+
+#[derive(Serialize, ToSchema)]
+struct WebhookDto {
+    id: i32,
+    group_id: i32,
+    url: String,
+    events: Vec<String>,
+    description: Option<String>,
+    active: bool,
+    created_by: i32,
+    created_at: chrono::DateTime<Utc>,
+    /// Only returned when the webhook is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl From<&Webhook> for WebhookDto {
+    fn from(value: &Webhook) -> Self {
+        WebhookDto {
+            id: value.id,
+            group_id: value.group_id,
+            url: value.url.clone(),
+            events: value.events.clone(),
+            description: value.description.clone(),
+            active: value.active,
+            created_by: value.created_by,
+            created_at: value.created_at,
+            secret: None,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct UpsertWebhookDto {
+    group_id: Option<i32>,
+    url: String,
+    events: Vec<String>,
+    description: Option<String>,
+    active: Option<bool>,
+}
+
+impl From<UpsertWebhookDto> for NewWebhook {
+    fn from(value: UpsertWebhookDto) -> Self {
+        NewWebhook {
+            group_id: value.group_id.unwrap_or(DEFAULT_GROUP_ID),
+            url: value.url,
+            events: value.events,
+            description: value.description,
+            active: value.active.unwrap_or(true),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct WebhookDeliveryDto {
+    id: i32,
+    webhook_id: i32,
+    event: String,
+    payload: String,
+    status: String,
+    attempts: i32,
+    next_attempt_at: Option<chrono::DateTime<Utc>>,
+    last_attempt_at: Option<chrono::DateTime<Utc>>,
+    response_status: Option<i32>,
+    last_error: Option<String>,
+    created_at: chrono::DateTime<Utc>,
+}
+
+impl From<&WebhookDelivery> for WebhookDeliveryDto {
+    fn from(value: &WebhookDelivery) -> Self {
+        WebhookDeliveryDto {
+            id: value.id,
+            webhook_id: value.webhook_id,
+            event: value.event.clone(),
+            payload: value.payload.clone(),
+            status: value.status.clone(),
+            attempts: value.attempts,
+            next_attempt_at: (value.status == "pending").then_some(value.next_attempt_at),
+            last_attempt_at: value.last_attempt_at,
+            response_status: value.response_status,
+            last_error: value.last_error.clone(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetWebhooksQuery {
+    group_id: Option<i32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GetDeliveriesQuery {
+    limit: Option<i64>,
+}
+
+fn webhook_error(err: WebhookError) -> (StatusCode, String) {
+    match err {
+        WebhookError::Sqlx(err) => internal_error(err),
+        WebhookError::NotFound(_) => (StatusCode::NOT_FOUND, err.to_string()),
+        WebhookError::NotMember(_, _) => (StatusCode::FORBIDDEN, err.to_string()),
+        WebhookError::Invalid(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(
+    get_webhooks,
+    create_webhook,
+    update_webhook,
+    delete_webhook,
+    get_deliveries
+))]
+pub struct WebhookApi;
+
+pub fn get_webhook_api() -> Router<App> {
+    Router::new()
+        .route("/", get(get_webhooks).post(create_webhook))
+        .route("/:id", put(update_webhook).delete(delete_webhook))
+        .route("/:id/delivery", get(get_deliveries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/webhook",
+    tag = "webhook",
+    params(GetWebhooksQuery),
+    responses(
+        (status = 200, body = [WebhookDto]),
+        (status = 403, body = String)
+    )
+)]
+async fn get_webhooks(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetWebhooksQuery>,
+) -> Result<Json<Vec<WebhookDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let webhooks = WebhookService::new(app.db)
+        .get_webhooks(&actor, query.group_id.unwrap_or(DEFAULT_GROUP_ID))
+        .await
+        .map_err(webhook_error)?;
+
+    Ok(Json(
+        webhooks.iter().map(|webhook| webhook.into()).collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhook",
+    tag = "webhook",
+    request_body = UpsertWebhookDto,
+    responses(
+        (status = 200, body = WebhookDto),
+        (status = 400, body = String),
+        (status = 403, body = String)
+    )
+)]
+async fn create_webhook(
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(webhook): Json<UpsertWebhookDto>,
+) -> Result<Json<WebhookDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let webhook = WebhookService::new(app.db)
+        .create_webhook(&actor, webhook.into())
+        .await
+        .map_err(webhook_error)?;
+
+    Ok(Json(WebhookDto {
+        secret: Some(webhook.secret.clone()),
+        ..(&webhook).into()
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/webhook/{id}",
+    tag = "webhook",
+    params(("id" = i32, Path, description = "Webhook id")),
+    request_body = UpsertWebhookDto,
+    responses(
+        (status = 200, body = WebhookDto),
+        (status = 400, body = String),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn update_webhook(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Json(webhook): Json<UpsertWebhookDto>,
+) -> Result<Json<WebhookDto>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let webhook = WebhookService::new(app.db)
+        .update_webhook(&actor, id, webhook.into())
+        .await
+        .map_err(webhook_error)?;
+
+    Ok(Json((&webhook).into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/webhook/{id}",
+    tag = "webhook",
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 200),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn delete_webhook(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+) -> Result<(), (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+
+    WebhookService::new(app.db)
+        .delete_webhook(&actor, id)
+        .await
+        .map_err(webhook_error)
+}
+
+/// The delivery log of a webhook, most recent first.
+#[utoipa::path(
+    get,
+    path = "/api/webhook/{id}/delivery",
+    tag = "webhook",
+    params(("id" = i32, Path, description = "Webhook id"), GetDeliveriesQuery),
+    responses(
+        (status = 200, body = [WebhookDeliveryDto]),
+        (status = 403, body = String),
+        (status = 404, body = String)
+    )
+)]
+async fn get_deliveries(
+    Path(id): Path<i32>,
+    State(app): State<App>,
+    JwtClaims(claims): JwtClaims<MicrosoftClaims>,
+    Query(query): Query<GetDeliveriesQuery>,
+) -> Result<Json<Vec<WebhookDeliveryDto>>, (StatusCode, String)> {
+    let actor = current_user(&app.db, &claims).await?;
+    let deliveries = WebhookService::new(app.db)
+        .get_deliveries(&actor, id, query.limit.unwrap_or(50).clamp(1, 500))
+        .await
+        .map_err(webhook_error)?;
+
+    Ok(Json(
+        deliveries.iter().map(|delivery| delivery.into()).collect(),
+    ))
+}
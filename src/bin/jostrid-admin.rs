@@ -0,0 +1,389 @@
+use std::{error::Error, fs, io::Write};
+
+use chrono::{Duration, Utc};
+use dotenvy::dotenv;
+use jostrid::service::{
+    admin_service::{AdminService, Backup},
+    invitation_service::{InvitationService, NewInvitation},
+};
+use sqlx::postgres::PgPoolOptions;
+use tracing_subscriber::EnvFilter;
+
+const USAGE: &str = "\
+Usage: jostrid-admin <command> [options]
+
+Operates the database at POSTGRES_URL.
+
+Commands:
+  migrations status                List migrations and whether they are applied
+  migrations run                   Apply pending migrations
+  migrations revert [--to <version>]
+                                   Revert the latest migration, or every one
+                                   applied after <version>
+  users list
+  users create --name <name> --email <email> [--group <group id>]...
+                                   Create a user that is linked to a Microsoft
+                                   account with the same email on first sign
+                                   in, as a member of the given groups.
+                                   The user can sign in without being listed
+                                   in ALLOWED_EMAILS
+  users disable <user id>          Keep a user from signing in or using the API
+  users enable <user id>
+  groups list
+  groups create <name>
+  groups members <group id>
+  groups add-member <group id> <user id>
+  groups remove-member <group id> <user id>
+  invitations list <group id>      List invitations that can still be accepted
+  invitations create <group id> --as <user id> [--email <email>]
+                     [--guest <user id>] [--days <days>]
+                                   Invite on behalf of a member of the group
+  invitations revoke <invitation id>
+  balances recompute [--group <group id>]
+                                   Snapshot the balances of period closes again
+                                   and list expenses whose shares are off
+  backup export [<file>]           Write everything as JSON to <file> or stdout
+  backup import <file> --replace   Replace everything with the backup, the
+                                   database has to be at the same migration
+  purge [--older-than <days>] [--dry-run]
+                                   Delete unused archived categories, expired
+                                   invitations, sync tombstones and recorded
+                                   sync mutations older than <days> (default
+                                   180). Clients that haven't synced for that
+                                   long sync from scratch the next time.
+";
+
+/// How old deleted data has to be to be purged unless told otherwise.
+const DEFAULT_PURGE_DAYS: i64 = 180;
+
+/// Options that don't take a value.
+const FLAGS: [&str; 3] = ["--replace", "--dry-run", "--help"];
+
+/// The command line split into the command with its arguments, and options.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+            flags: Vec::new(),
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg);
+            } else if FLAGS.contains(&arg.as_str()) {
+                parsed.flags.push(arg);
+            } else if let Some((name, value)) = arg.split_once('=') {
+                parsed.options.push((name.to_string(), value.to_string()));
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value", arg))?;
+                parsed.options.push((arg, value));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// The last value given for the option.
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn options(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.option(name)
+            .ok_or_else(|| format!("{} is required", name))
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid {}", value, what))
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    // Logs go to stderr to keep them out of backups written to stdout.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) if !args.positional.is_empty() && !args.flag("--help") => args,
+        Ok(_) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&args).await {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let db_url = std::env::var("POSTGRES_URL").map_err(|_| "POSTGRES_URL is not set")?;
+    let db = PgPoolOptions::new()
+        .max_connections(2)
+        .acquire_timeout(std::time::Duration::from_secs(3))
+        .connect(&db_url)
+        .await?;
+    let admin = AdminService::new(db.clone());
+
+    let positional: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    match positional.as_slice() {
+        ["migrations", "status"] => {
+            for migration in admin.get_migrations().await? {
+                let status = if migration.applied {
+                    "applied"
+                } else {
+                    "pending"
+                };
+                println!(
+                    "{}\t{}\t{}",
+                    migration.version, status, migration.description
+                );
+            }
+        }
+        ["migrations", "run"] => {
+            let applied = admin.run_migrations().await?;
+            println!("Applied {} migrations", applied.len());
+            for version in applied {
+                println!("{}", version);
+            }
+        }
+        ["migrations", "revert"] => {
+            let target = args
+                .option("--to")
+                .map(|to| parse(to, "version"))
+                .transpose()?;
+            let reverted = admin.revert_migrations(target).await?;
+            println!("Reverted {} migrations", reverted.len());
+            for version in reverted {
+                println!("{}", version);
+            }
+        }
+        ["users", "list"] => {
+            println!("id\tname\temail\tstatus");
+            for user in admin.get_users().await? {
+                let status = match (user.is_guest, user.disabled_at) {
+                    (_, Some(disabled_at)) => format!("disabled {}", disabled_at.to_rfc3339()),
+                    (true, None) => "guest".to_string(),
+                    (false, None) => "active".to_string(),
+                };
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    user.id,
+                    user.name,
+                    user.email.unwrap_or_default(),
+                    status
+                );
+            }
+        }
+        ["users", "create"] => {
+            let group_ids = args
+                .options("--group")
+                .into_iter()
+                .map(|group_id| parse(group_id, "group id"))
+                .collect::<Result<Vec<i32>, _>>()?;
+            let user = admin
+                .create_user(
+                    args.required("--name")?,
+                    args.required("--email")?,
+                    &group_ids,
+                )
+                .await?;
+            println!("Created user {}", user.id);
+        }
+        ["users", command @ ("disable" | "enable"), user_id] => {
+            let user = admin
+                .set_disabled(parse(user_id, "user id")?, *command == "disable")
+                .await?;
+            println!("User {} is {}d", user.id, command);
+        }
+        ["groups", "list"] => {
+            println!("id\tname\tmembers");
+            for group in admin.get_groups().await? {
+                let members = admin.get_members(group.id).await?;
+                println!("{}\t{}\t{}", group.id, group.name, members.len());
+            }
+        }
+        ["groups", "create", name] => {
+            let group = admin.create_group(name).await?;
+            println!("Created group {}", group.id);
+        }
+        ["groups", "members", group_id] => {
+            println!("id\tname\temail");
+            for user in admin.get_members(parse(group_id, "group id")?).await? {
+                println!(
+                    "{}\t{}\t{}",
+                    user.id,
+                    user.name,
+                    user.email.unwrap_or_default()
+                );
+            }
+        }
+        ["groups", "add-member", group_id, user_id] => {
+            admin
+                .add_member(parse(group_id, "group id")?, parse(user_id, "user id")?)
+                .await?;
+            println!("Added user {} to group {}", user_id, group_id);
+        }
+        ["groups", "remove-member", group_id, user_id] => {
+            admin
+                .remove_member(parse(group_id, "group id")?, parse(user_id, "user id")?)
+                .await?;
+            println!("Removed user {} from group {}", user_id, group_id);
+        }
+        ["invitations", "list", group_id] => {
+            println!("id\ttoken\temail\tguest\texpires");
+            for invitation in admin.get_invitations(parse(group_id, "group id")?).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    invitation.id,
+                    invitation.token,
+                    invitation.email.unwrap_or_default(),
+                    invitation
+                        .guest_id
+                        .map(|guest_id| guest_id.to_string())
+                        .unwrap_or_default(),
+                    invitation.expires_at.to_rfc3339()
+                );
+            }
+        }
+        ["invitations", "create", group_id] => {
+            let actor = admin
+                .get_user(parse(args.required("--as")?, "user id")?)
+                .await?;
+            let invitation = InvitationService::new(db.clone())
+                .create_invitation(
+                    &actor,
+                    NewInvitation {
+                        group_id: parse(group_id, "group id")?,
+                        guest_id: args
+                            .option("--guest")
+                            .map(|guest_id| parse(guest_id, "user id"))
+                            .transpose()?,
+                        email: args.option("--email").map(str::to_string),
+                        valid_days: args
+                            .option("--days")
+                            .map(|days| parse(days, "number of days"))
+                            .transpose()?,
+                    },
+                )
+                .await?;
+            println!(
+                "Created invitation {} with token {}, valid until {}",
+                invitation.id,
+                invitation.token,
+                invitation.expires_at.to_rfc3339()
+            );
+        }
+        ["invitations", "revoke", id] => {
+            admin.revoke_invitation(parse(id, "invitation id")?).await?;
+            println!("Revoked invitation {}", id);
+        }
+        ["balances", "recompute"] => {
+            let group_id = args
+                .option("--group")
+                .map(|group_id| parse(group_id, "group id"))
+                .transpose()?;
+            let recomputed = admin.recompute_balances(group_id).await?;
+            println!(
+                "Recomputed the balances of {} period closes",
+                recomputed.closes
+            );
+            if !recomputed.unbalanced.is_empty() {
+                println!("Expenses whose shares don't add up to zero:");
+                println!("id\tgroup\tsum");
+                for expense in recomputed.unbalanced {
+                    println!(
+                        "{}\t{}\t{}",
+                        expense.expense_id, expense.group_id, expense.sum
+                    );
+                }
+            }
+        }
+        ["backup", "export", file @ ..] if file.len() <= 1 => {
+            let backup = admin.export_backup().await?;
+            match file.first() {
+                Some(file) => {
+                    serde_json::to_writer(fs::File::create(file)?, &backup)?;
+                    eprintln!("Wrote backup of migration {} to {}", backup.version, file);
+                }
+                None => {
+                    let mut stdout = std::io::stdout().lock();
+                    serde_json::to_writer(&mut stdout, &backup)?;
+                    stdout.flush()?;
+                }
+            }
+        }
+        ["backup", "import", file] => {
+            if !args.flag("--replace") {
+                return Err(
+                    "importing replaces everything in the database, confirm with --replace".into(),
+                );
+            }
+            let backup: Backup =
+                serde_json::from_reader(std::io::BufReader::new(fs::File::open(file)?))?;
+            admin.import_backup(&backup).await?;
+            println!(
+                "Imported backup of migration {} taken at {}",
+                backup.version,
+                backup.created_at.to_rfc3339()
+            );
+        }
+        ["purge"] => {
+            let days = args
+                .option("--older-than")
+                .map(|days| parse(days, "number of days"))
+                .transpose()?
+                .unwrap_or(DEFAULT_PURGE_DAYS);
+            let age = Duration::try_days(days)
+                .filter(|age| *age >= Duration::zero())
+                .ok_or("--older-than has to be a number of days")?;
+            let dry_run = args.flag("--dry-run");
+            let purged = admin.purge(Utc::now() - age, dry_run).await?;
+            let verb = if dry_run { "Would purge" } else { "Purged" };
+            println!(
+                "{} {} categories, {} invitations, {} tombstones and {} mutations",
+                verb, purged.categories, purged.invitations, purged.tombstones, purged.mutations
+            );
+        }
+        _ => return Err(format!("unknown command\n\n{}", USAGE).into()),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use sqlx::{Connection, PgConnection};
+
+/// Belongs to sqlx, restoring it would undo the migrations of the database
+/// being restored into.
+const MIGRATIONS_TABLE: &str = "_sqlx_migrations";
+
+/// The rows of one table as a JSON array of objects keyed by column.
+pub struct TableRows {
+    pub table: String,
+    pub rows: String,
+}
+
+/// Every table of the application, ordered so that a table comes after the
+/// tables it references.
+async fn get_tables(conn: &mut PgConnection) -> Result<Vec<String>, sqlx::Error> {
+    let mut remaining: Vec<String> = sqlx::query_scalar(
+        r#"
+SELECT tablename::TEXT
+FROM pg_tables
+WHERE schemaname = 'public'
+AND tablename <> $1
+ORDER BY tablename;
+    "#,
+    )
+    .bind(MIGRATIONS_TABLE)
+    .fetch_all(&mut *conn)
+    .await?;
+    let references: Vec<(String, String)> = sqlx::query_as(
+        r#"
+SELECT referencing.relname::TEXT, referenced.relname::TEXT
+FROM pg_constraint as c
+INNER JOIN pg_class as referencing ON referencing.oid = c.conrelid
+INNER JOIN pg_class as referenced ON referenced.oid = c.confrelid
+INNER JOIN pg_namespace as n ON n.oid = c.connamespace
+WHERE c.contype = 'f'
+AND n.nspname = 'public'
+AND c.conrelid <> c.confrelid;
+    "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut tables = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<String>, Vec<String>) =
+            remaining.into_iter().partition(|table| {
+                references
+                    .iter()
+                    .filter(|(referencing, _)| referencing == table)
+                    .all(|(_, referenced)| tables.contains(referenced))
+            });
+        if ready.is_empty() {
+            // Tables referencing each other can't be ordered, restoring them
+            // fails on the foreign keys instead.
+            tables.extend(blocked);
+            break;
+        }
+        tables.extend(ready);
+        remaining = blocked;
+    }
+
+    Ok(tables)
+}
+
+fn quote(table: &str) -> String {
+    format!("\"{}\"", table.replace('"', "\"\""))
+}
+
+/// Every row of every table, read from a single snapshot so that the tables
+/// are consistent with each other.
+pub async fn export_tables(conn: &mut PgConnection) -> Result<Vec<TableRows>, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY;")
+        .execute(&mut *tx)
+        .await?;
+
+    let mut exported = Vec::new();
+    for table in get_tables(&mut tx).await? {
+        let rows = sqlx::query_scalar(&format!(
+            "SELECT COALESCE(json_agg(t), '[]')::TEXT FROM {} as t;",
+            quote(&table)
+        ))
+        .fetch_one(&mut *tx)
+        .await?;
+        exported.push(TableRows { table, rows });
+    }
+    tx.commit().await?;
+
+    Ok(exported)
+}
+
+/// Replaces the contents of every table with the given rows. Triggers are
+/// disabled while inserting, so nothing is notified and the rows are restored
+/// as they were, except that every entity is marked as changed for syncing.
+pub async fn import_tables(
+    conn: &mut PgConnection,
+    tables: &[TableRows],
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    let existing = get_tables(&mut tx).await?;
+    if !existing.is_empty() {
+        let existing: Vec<String> = existing.iter().map(|table| quote(table)).collect();
+        sqlx::query(&format!(
+            "TRUNCATE {} RESTART IDENTITY CASCADE;",
+            existing.join(", ")
+        ))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for table in tables {
+        let quoted = quote(&table.table);
+        sqlx::query(&format!("ALTER TABLE {} DISABLE TRIGGER USER;", quoted))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!(
+            "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1::JSON);",
+            quoted
+        ))
+        .bind(&table.rows)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!("ALTER TABLE {} ENABLE TRIGGER USER;", quoted))
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Continue the sequences of the ids after the restored rows.
+    let serials: Vec<(String, String)> = sqlx::query_as(
+        r#"
+SELECT table_name::TEXT, column_name::TEXT
+FROM information_schema.columns
+WHERE table_schema = 'public'
+AND column_default LIKE 'nextval(%';
+    "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for (table, column) in serials {
+        sqlx::query(&format!(
+            "SELECT setval(pg_get_serial_sequence($1, $2), COALESCE(MAX({}), 0) + 1, false) FROM {};",
+            quote(&column),
+            quote(&table)
+        ))
+        .bind(quote(&table))
+        .bind(&column)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Transaction ids of the backup mean nothing here, clients have to sync
+    // everything again.
+    sqlx::query("UPDATE sync_change SET tx_id = pg_current_xact_id();")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO sync_horizon (tx_id)
+VALUES (pg_current_xact_id())
+ON CONFLICT (id) DO UPDATE
+SET tx_id = EXCLUDED.tx_id, purged_at = EXCLUDED.purged_at;
+    "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
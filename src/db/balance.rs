@@ -1,19 +1,185 @@
+use chrono::Utc;
 use sqlx::{prelude::FromRow, PgPool};
 
+/// Only confirmed expenses count towards the balance, those awaiting approval
+/// are summed up separately.
 static GET_BALANCE: &str = r#"
-SELECT user_id, SUM(share) as balance, e.currency
+SELECT
+    user_id,
+    COALESCE(SUM(share) FILTER (WHERE e.status = 'confirmed'), 0)::BIGINT as balance,
+    COALESCE(SUM(share) FILTER (WHERE e.status <> 'confirmed'), 0)::BIGINT as pending,
+    e.currency
 FROM account_share
-LEFT JOIN expense as e ON e.id = expense_id
+INNER JOIN expense as e ON e.id = expense_id
+WHERE e.group_id = $1
 GROUP BY user_id, e.currency;
 "#;
 
+static GET_GROUP_BALANCE: &str = r#"
+SELECT
+    s.user_id,
+    COALESCE(SUM(s.share) FILTER (WHERE e.status = 'confirmed'), 0)::BIGINT as balance,
+    COALESCE(SUM(s.share) FILTER (WHERE e.status <> 'confirmed'), 0)::BIGINT as pending,
+    e.currency
+FROM account_share as s
+INNER JOIN expense as e ON e.id = s.expense_id
+WHERE e.group_id = $1 AND e.currency = $2
+GROUP BY s.user_id, e.currency
+ORDER BY s.user_id;
+"#;
+
+/// Running balance after every expense. The window has to cover all history,
+/// so the date range is applied on the outside.
+static GET_BALANCE_HISTORY_PER_EXPENSE: &str = r#"
+SELECT * FROM (
+    SELECT
+        s.user_id,
+        e.currency,
+        e.created_at as at,
+        e.id as expense_id,
+        s.share::BIGINT as change,
+        SUM(s.share) OVER (
+            PARTITION BY s.user_id, e.currency
+            ORDER BY e.created_at, e.id
+        )::BIGINT as balance
+    FROM account_share as s
+    INNER JOIN expense as e ON e.id = s.expense_id
+    WHERE ($1::INTEGER IS NULL OR e.group_id = $1)
+    AND e.group_id IN (SELECT group_id FROM group_member WHERE user_id = $6)
+    AND ($2::TEXT IS NULL OR e.currency = $2)
+    AND ($3::INTEGER IS NULL OR s.user_id = $3)
+    AND e.status = 'confirmed'
+) as history
+WHERE at >= $4 AND at < $5
+ORDER BY user_id, currency, at, expense_id;
+"#;
+
+/// Running balance at the end of every day with any change.
+static GET_BALANCE_HISTORY_DAILY: &str = r#"
+SELECT * FROM (
+    SELECT
+        user_id,
+        currency,
+        day as at,
+        NULL::INTEGER as expense_id,
+        change,
+        SUM(change) OVER (
+            PARTITION BY user_id, currency
+            ORDER BY day
+        )::BIGINT as balance
+    FROM (
+        SELECT
+            s.user_id,
+            e.currency,
+            date_trunc('day', e.created_at) as day,
+            SUM(s.share)::BIGINT as change
+        FROM account_share as s
+        INNER JOIN expense as e ON e.id = s.expense_id
+        WHERE ($1::INTEGER IS NULL OR e.group_id = $1)
+        AND e.group_id IN (SELECT group_id FROM group_member WHERE user_id = $6)
+        AND ($2::TEXT IS NULL OR e.currency = $2)
+        AND ($3::INTEGER IS NULL OR s.user_id = $3)
+        AND e.status = 'confirmed'
+        GROUP BY s.user_id, e.currency, day
+    ) as daily
+) as history
+WHERE at >= $4 AND at < $5
+ORDER BY user_id, currency, at;
+"#;
+
+/// Every expense's shares should add up to zero, the payer's share making up
+/// for everyone else's.
+static GET_UNBALANCED_EXPENSES: &str = r#"
+SELECT e.id as expense_id, e.group_id, SUM(s.share)::BIGINT as sum
+FROM expense as e
+INNER JOIN account_share as s ON s.expense_id = e.id
+GROUP BY e.id
+HAVING SUM(s.share) <> 0
+ORDER BY e.id;
+"#;
+
 #[derive(FromRow)]
 pub struct Balance {
+    /// Of confirmed expenses only.
     pub balance: i64,
+    /// Of expenses awaiting approval or disputed.
+    pub pending: i64,
+    pub user_id: i32,
+    pub currency: String,
+}
+
+pub async fn get_balance(pool: &PgPool, group_id: i32) -> Result<Vec<Balance>, sqlx::Error> {
+    sqlx::query_as(GET_BALANCE)
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_group_balance(
+    pool: &PgPool,
+    group_id: i32,
+    currency: &str,
+) -> Result<Vec<Balance>, sqlx::Error> {
+    sqlx::query_as(GET_GROUP_BALANCE)
+        .bind(group_id)
+        .bind(currency)
+        .fetch_all(pool)
+        .await
+}
+
+#[derive(FromRow)]
+pub struct UnbalancedExpense {
+    pub expense_id: i32,
+    pub group_id: i32,
+    /// What the shares add up to instead of zero.
+    pub sum: i64,
+}
+
+pub async fn get_unbalanced_expenses(pool: &PgPool) -> Result<Vec<UnbalancedExpense>, sqlx::Error> {
+    sqlx::query_as(GET_UNBALANCED_EXPENSES)
+        .fetch_all(pool)
+        .await
+}
+
+pub struct BalanceHistoryFilter {
+    /// Only the groups of this user are included.
+    pub member_id: i32,
+    pub group_id: Option<i32>,
+    pub currency: Option<String>,
+    pub user_id: Option<i32>,
+    pub from: chrono::DateTime<Utc>,
+    pub to: chrono::DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+pub struct BalancePoint {
     pub user_id: i32,
     pub currency: String,
+    pub at: chrono::DateTime<Utc>,
+    /// Only set when the history is per expense.
+    pub expense_id: Option<i32>,
+    pub change: i64,
+    pub balance: i64,
 }
 
-pub async fn get_balance(pool: &PgPool) -> Result<Vec<Balance>, sqlx::Error> {
-    sqlx::query_as(GET_BALANCE).fetch_all(pool).await
+pub async fn get_balance_history(
+    pool: &PgPool,
+    filter: &BalanceHistoryFilter,
+    daily: bool,
+) -> Result<Vec<BalancePoint>, sqlx::Error> {
+    let query = if daily {
+        GET_BALANCE_HISTORY_DAILY
+    } else {
+        GET_BALANCE_HISTORY_PER_EXPENSE
+    };
+
+    sqlx::query_as(query)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.user_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.member_id)
+        .fetch_all(pool)
+        .await
 }
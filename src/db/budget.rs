@@ -0,0 +1,220 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+/// Line items of itemised expenses count towards their own category, the rest
+/// of the expense towards the expense's. Refunds count negatively towards the
+/// date and category of the expense they refund. Only confirmed expenses count.
+static GET_BUDGET_SPENT: &str = r#"
+SELECT COALESCE(SUM(consumed), 0)::BIGINT
+FROM (
+    SELECT (CASE WHEN s.user_id = e.paid_by THEN e.total ELSE 0 END) - s.share as consumed
+    FROM expense as e
+    INNER JOIN account_share as s ON s.expense_id = e.id
+    LEFT JOIN expense as o ON o.id = e.refund_of
+    LEFT JOIN expense_category as ec ON ec.id = COALESCE(o.category_id, e.category_id)
+    WHERE e.group_id = $1
+    AND e.currency = $2
+    AND NOT e.is_payment
+    AND e.status = 'confirmed'
+    AND NOT EXISTS (SELECT 1 FROM expense_item as i WHERE i.expense_id = e.id)
+    AND (ec.id = $3 OR ec.parent_id = $3)
+    AND COALESCE(o.created_at, e.created_at) >= $4
+    AND COALESCE(o.created_at, e.created_at) < $5
+    AND ($6::INTEGER IS NULL OR s.user_id = $6)
+    UNION ALL
+    SELECT s.consumed
+    FROM expense as e
+    INNER JOIN expense_item as i ON i.expense_id = e.id
+    INNER JOIN expense_item_share as s ON s.item_id = i.id
+    LEFT JOIN expense_category as ec ON ec.id = COALESCE(i.category_id, e.category_id)
+    WHERE e.group_id = $1
+    AND e.currency = $2
+    AND NOT e.is_payment
+    AND e.status = 'confirmed'
+    AND (ec.id = $3 OR ec.parent_id = $3)
+    AND e.created_at >= $4
+    AND e.created_at < $5
+    AND ($6::INTEGER IS NULL OR s.user_id = $6)
+) as spent;
+"#;
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct Budget {
+    pub id: i32,
+    pub group_id: i32,
+    pub name: String,
+    pub category_id: i32,
+    /// One of `week`, `month` or `year`.
+    pub period: String,
+    pub amount: i32,
+    pub currency: String,
+    pub user_id: Option<i32>,
+    pub alert_thresholds: Vec<i32>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertBudget {
+    pub group_id: i32,
+    pub name: String,
+    pub category_id: i32,
+    pub period: String,
+    pub amount: i32,
+    pub currency: String,
+    pub user_id: Option<i32>,
+    pub alert_thresholds: Vec<i32>,
+}
+
+pub async fn get_budgets(pool: &PgPool, group_id: i32) -> Result<Vec<Budget>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM budget WHERE group_id = $1 ORDER BY name;")
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_budget(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<Budget>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM budget WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Budgets of a group that an expense in any of `category_ids` counts
+/// towards, either directly or through the category's parent.
+pub async fn get_budgets_for_categories(
+    pool: &PgPool,
+    group_id: i32,
+    category_ids: &[i32],
+    currency: &str,
+) -> Result<Vec<Budget>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT b.* FROM budget as b
+WHERE b.group_id = $1
+AND b.currency = $3
+AND EXISTS (
+    SELECT 1 FROM expense_category as ec
+    WHERE ec.id = ANY($2)
+    AND (b.category_id = ec.id OR b.category_id = ec.parent_id)
+)
+ORDER BY b.id;
+    "#,
+    )
+    .bind(group_id)
+    .bind(category_ids)
+    .bind(currency)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_budget(
+    executor: impl PgExecutor<'_>,
+    budget: InsertBudget,
+) -> Result<Budget, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO budget (group_id, name, category_id, period, amount, currency, user_id, alert_thresholds)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+RETURNING *;
+    "#,
+    )
+    .bind(budget.group_id)
+    .bind(budget.name)
+    .bind(budget.category_id)
+    .bind(budget.period)
+    .bind(budget.amount)
+    .bind(budget.currency)
+    .bind(budget.user_id)
+    .bind(budget.alert_thresholds)
+    .fetch_one(executor)
+    .await
+}
+
+/// Updates everything but the group of a budget.
+pub async fn update_budget(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    budget: InsertBudget,
+) -> Result<Budget, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE budget
+SET
+    name = $2,
+    category_id = $3,
+    period = $4,
+    amount = $5,
+    currency = $6,
+    user_id = $7,
+    alert_thresholds = $8
+WHERE id = $1
+RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(budget.name)
+    .bind(budget.category_id)
+    .bind(budget.period)
+    .bind(budget.amount)
+    .bind(budget.currency)
+    .bind(budget.user_id)
+    .bind(budget.alert_thresholds)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_budget(executor: impl PgExecutor<'_>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM budget WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// What has been consumed towards `budget` within `[from, to)`.
+pub async fn get_budget_spent(
+    pool: &PgPool,
+    budget: &Budget,
+    from: chrono::DateTime<Utc>,
+    to: chrono::DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(GET_BUDGET_SPENT)
+        .bind(budget.group_id)
+        .bind(&budget.currency)
+        .bind(budget.category_id)
+        .bind(from)
+        .bind(to)
+        .bind(budget.user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Records that a threshold has been passed, returning `false` if that was
+/// already recorded for the period.
+pub async fn insert_budget_alert(
+    executor: impl PgExecutor<'_>,
+    budget_id: i32,
+    period_start: chrono::DateTime<Utc>,
+    threshold: i32,
+    expense_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+INSERT INTO budget_alert (budget_id, period_start, threshold, expense_id)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT DO NOTHING;
+    "#,
+    )
+    .bind(budget_id)
+    .bind(period_start)
+    .bind(threshold)
+    .bind(expense_id)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,85 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct CategoryRule {
+    pub id: i32,
+    pub group_id: i32,
+    pub keyword: String,
+    pub category_id: i32,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertCategoryRule {
+    pub group_id: i32,
+    pub keyword: String,
+    pub category_id: i32,
+}
+
+pub async fn get_category_rules(
+    pool: &PgPool,
+    group_id: i32,
+) -> Result<Vec<CategoryRule>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT id, group_id, keyword, category_id, created_at
+FROM category_rule
+WHERE group_id = $1
+ORDER BY keyword;
+    "#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_category_rule(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<CategoryRule>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT id, group_id, keyword, category_id, created_at
+FROM category_rule
+WHERE id = $1;
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Inserts a rule, replacing the category of an existing rule with the same
+/// keyword.
+pub async fn upsert_category_rule(
+    executor: impl PgExecutor<'_>,
+    rule: InsertCategoryRule,
+) -> Result<CategoryRule, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO category_rule (group_id, keyword, category_id)
+VALUES ($1, $2, $3)
+ON CONFLICT (group_id, keyword) DO UPDATE
+SET category_id = EXCLUDED.category_id
+RETURNING id, group_id, keyword, category_id, created_at;
+    "#,
+    )
+    .bind(rule.group_id)
+    .bind(rule.keyword)
+    .bind(rule.category_id)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_category_rule(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM category_rule WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
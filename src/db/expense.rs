@@ -2,9 +2,12 @@ use std::collections::HashMap;
 
 use chrono::Utc;
 use serde::Serialize;
-use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use sqlx::{postgres::PgRow, Connection, FromRow, PgConnection, PgExecutor, PgPool, Row};
 
-use super::expense_category::ExpenseCategory;
+use super::{
+    expense_approval::{get_approvals, ExpenseApproval},
+    expense_category::ExpenseCategory,
+};
 
 static GET_ALL_EXPENSE: &str = r#"
 SELECT 
@@ -15,13 +18,79 @@ SELECT
     e.total,
     e.currency,
     e.is_payment,
+    e.group_id,
+    e.status,
+    e.refund_of,
+    e.note,
+    e.tags,
+    e.merchant,
+    e.place,
+    e.latitude,
+    e.longitude,
     u.name as paid_by_name, 
     u.email as paid_by_email, 
     e.category_id, 
-    ec.name as category_name 
+    ec.name as category_name,
+    ec.parent_id as category_parent_id,
+    ec.group_id as category_group_id,
+    ec.icon as category_icon,
+    ec.color as category_color,
+    ec.archived_at as category_archived_at,
+    (SELECT COUNT(*) FROM expense_comment WHERE expense_id = e.id) as comment_count,
+    ARRAY(SELECT r.id FROM expense as r WHERE r.refund_of = e.id ORDER BY r.id) as refund_ids
 FROM expense as e
 LEFT JOIN users as u ON e.paid_by = u.id
 LEFT JOIN expense_category as ec ON e.category_id = ec.id
+WHERE e.group_id IN (SELECT group_id FROM group_member WHERE user_id = $1)
+AND ($2::INTEGER IS NULL OR e.group_id = $2)
+AND e.tags @> $3
+AND ($6::TEXT IS NULL OR e.status = $6)
+AND ($4::TEXT IS NULL OR e.merchant ILIKE $4)
+AND (
+    $5::TEXT IS NULL
+    OR e.name ILIKE $5
+    OR e.note ILIKE $5
+    OR e.merchant ILIKE $5
+    OR e.place ILIKE $5
+    OR EXISTS (SELECT 1 FROM unnest(e.tags) as tag WHERE tag ILIKE $5)
+)
+ORDER BY e.created_at DESC;
+"#;
+
+static GET_EXPENSES_BY_ID: &str = r#"
+SELECT 
+    e.id, 
+    e.name, 
+    e.created_at, 
+    e.paid_by, 
+    e.total,
+    e.currency,
+    e.is_payment,
+    e.group_id,
+    e.status,
+    e.refund_of,
+    e.note,
+    e.tags,
+    e.merchant,
+    e.place,
+    e.latitude,
+    e.longitude,
+    u.name as paid_by_name, 
+    u.email as paid_by_email, 
+    e.category_id, 
+    ec.name as category_name,
+    ec.parent_id as category_parent_id,
+    ec.group_id as category_group_id,
+    ec.icon as category_icon,
+    ec.color as category_color,
+    ec.archived_at as category_archived_at,
+    (SELECT COUNT(*) FROM expense_comment WHERE expense_id = e.id) as comment_count,
+    ARRAY(SELECT r.id FROM expense as r WHERE r.refund_of = e.id ORDER BY r.id) as refund_ids
+FROM expense as e
+LEFT JOIN users as u ON e.paid_by = u.id
+LEFT JOIN expense_category as ec ON e.category_id = ec.id
+WHERE e.group_id IN (SELECT group_id FROM group_member WHERE user_id = $1)
+AND e.id = ANY($2)
 ORDER BY e.created_at DESC;
 "#;
 
@@ -34,10 +103,26 @@ SELECT
     e.total,
     e.currency,
     e.is_payment,
+    e.group_id,
+    e.status,
+    e.refund_of,
+    e.note,
+    e.tags,
+    e.merchant,
+    e.place,
+    e.latitude,
+    e.longitude,
     u.name as paid_by_name, 
     u.email as paid_by_email, 
     e.category_id, 
-    ec.name as category_name 
+    ec.name as category_name,
+    ec.parent_id as category_parent_id,
+    ec.group_id as category_group_id,
+    ec.icon as category_icon,
+    ec.color as category_color,
+    ec.archived_at as category_archived_at,
+    (SELECT COUNT(*) FROM expense_comment WHERE expense_id = e.id) as comment_count,
+    ARRAY(SELECT r.id FROM expense as r WHERE r.refund_of = e.id ORDER BY r.id) as refund_ids
 FROM expense as e
 LEFT JOIN users as u ON e.paid_by = u.id
 LEFT JOIN expense_category as ec ON e.category_id = ec.id
@@ -45,8 +130,11 @@ WHERE e.id = $1;
 "#;
 
 static INSERT_EXPENSE: &str = r#"
-INSERT INTO expense (name, created_at, paid_by, total, currency, category_id, is_payment)
-VALUES($1, $2, $3, $4, $5, $6, $7)
+INSERT INTO expense (
+    name, created_at, paid_by, total, currency, category_id, is_payment, group_id,
+    note, tags, merchant, place, latitude, longitude, refund_of
+)
+VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
 RETURNING id;
 "#;
 
@@ -59,7 +147,14 @@ SET
     total = $5,
     currency = $6,
     category_id = $7,
-    is_payment = $8
+    is_payment = $8,
+    group_id = $9,
+    note = $10,
+    tags = $11,
+    merchant = $12,
+    place = $13,
+    latitude = $14,
+    longitude = $15
 WHERE id = $1;
 "#;
 
@@ -75,6 +170,39 @@ ON CONFLICT (expense_id, user_id) DO UPDATE
 SET share = EXCLUDED.share;
 "#;
 
+static DELETE_STALE_SHARES: &str = r#"
+DELETE FROM account_share
+WHERE expense_id = $1 AND NOT user_id = ANY($2);
+"#;
+
+static INSERT_ITEM: &str = r#"
+INSERT INTO expense_item (expense_id, position, name, amount, category_id)
+VALUES($1, $2, $3, $4, $5)
+RETURNING id;
+"#;
+
+static INSERT_ITEM_SHARE: &str = r#"
+INSERT INTO expense_item_share (item_id, user_id, weight, consumed)
+VALUES($1, $2, $3, $4);
+"#;
+
+static INSERT_EXTRA: &str = r#"
+INSERT INTO expense_extra (expense_id, kind, amount)
+VALUES($1, $2, $3);
+"#;
+
+static GET_EXPENSES_WITH_TOTAL: &str = r#"
+SELECT id, name, currency, total, created_at, is_payment, group_id, status, refund_of,
+    note, tags, merchant, place, latitude, longitude
+FROM expense
+WHERE group_id = $1
+AND total = $2
+AND currency = $3
+AND created_at >= $4
+AND created_at < $5
+ORDER BY created_at, id;
+"#;
+
 #[derive(sqlx::FromRow, Serialize, Clone)]
 pub struct Expense {
     pub id: i32,
@@ -83,7 +211,42 @@ pub struct Expense {
     pub total: i32,
     pub created_at: chrono::DateTime<Utc>,
     pub is_payment: bool,
+    pub group_id: i32,
+    /// `confirmed`, or `pending` or `disputed` while the expense awaits the
+    /// approval of its participants and doesn't count towards balances.
+    pub status: String,
+    /// Set for refunds, which have a negative total, to the expense refunded.
+    pub refund_of: Option<i32>,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub details: ExpenseDetails,
+}
+
+/// What a user noted about an expense, none of which affects its balances.
+#[derive(sqlx::FromRow, Serialize, Clone, Default)]
+pub struct ExpenseDetails {
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+    pub merchant: Option<String>,
+    pub place: Option<String>,
+    /// Either both or neither of the coordinates are set.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// Narrows down a list of expenses, every set criterion has to match.
+#[derive(Default)]
+pub struct ExpenseFilter {
+    pub group_id: Option<i32>,
+    /// Expenses having all of these tags.
+    pub tags: Vec<String>,
+    /// Case-insensitive, but otherwise exact.
+    pub merchant: Option<String>,
+    /// Matches any part of the name, note, merchant, place or a tag.
+    pub search: Option<String>,
+    pub status: Option<String>,
 }
+
 pub struct InsertExpense {
     pub name: String,
     pub created_at: Option<chrono::DateTime<Utc>>,
@@ -93,6 +256,12 @@ pub struct InsertExpense {
     pub category_id: Option<i32>,
     pub shares: Vec<InsertAccountShare>,
     pub is_payment: bool,
+    pub group_id: i32,
+    /// Set for itemised expenses, whose shares are derived from the items.
+    pub receipt: Option<InsertReceipt>,
+    pub details: ExpenseDetails,
+    /// Only set when inserting a refund, an expense can't become one later.
+    pub refund_of: Option<i32>,
 }
 
 #[derive(sqlx::FromRow, Serialize, Clone, Copy)]
@@ -106,20 +275,94 @@ pub struct InsertAccountShare {
     pub share: i32,
 }
 
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct ExpenseItem {
+    pub id: i32,
+    pub expense_id: i32,
+    pub position: i32,
+    pub name: String,
+    pub amount: i32,
+    pub category_id: Option<i32>,
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone, Copy)]
+pub struct ExpenseItemShare {
+    pub item_id: i32,
+    pub user_id: i32,
+    pub weight: i32,
+    /// Including the user's part of the receipt's extras.
+    pub consumed: i32,
+}
+
+#[derive(sqlx::FromRow, Serialize, Clone)]
+pub struct ExpenseExtra {
+    pub id: i32,
+    pub expense_id: i32,
+    /// One of `tax`, `tip` or `discount`.
+    pub kind: String,
+    pub amount: i32,
+}
+
+/// The line items and extras of an itemised expense.
+#[derive(Clone, Default)]
+pub struct Receipt {
+    pub items: Vec<(ExpenseItem, Vec<ExpenseItemShare>)>,
+    pub extras: Vec<ExpenseExtra>,
+}
+
+pub struct InsertReceipt {
+    pub items: Vec<InsertExpenseItem>,
+    pub extras: Vec<InsertExpenseExtra>,
+}
+
+pub struct InsertExpenseItem {
+    pub name: String,
+    pub amount: i32,
+    pub category_id: Option<i32>,
+    pub shares: Vec<InsertExpenseItemShare>,
+}
+
+pub struct InsertExpenseItemShare {
+    pub user_id: i32,
+    pub weight: i32,
+    pub consumed: i32,
+}
+
+pub struct InsertExpenseExtra {
+    pub kind: String,
+    pub amount: i32,
+}
+
+pub type ExpenseWithShares = (ExpenseWithPayerAndCategory, Vec<AccountShare>);
+
 pub struct ExpenseWithPayerAndCategory {
     pub expense: Expense,
     pub paid_by: i32,
     pub category: Option<ExpenseCategory>,
+    /// Only loaded for expenses that have line items.
+    pub receipt: Option<Receipt>,
+    pub comment_count: i64,
+    /// The refunds of the expense, oldest first.
+    pub refund_ids: Vec<i32>,
+    /// Empty unless the expense needed approval.
+    pub approvals: Vec<ExpenseApproval>,
 }
 
 impl FromRow<'_, PgRow> for ExpenseWithPayerAndCategory {
     fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
         let expense = Expense::from_row(row)?;
         let paid_by = row.try_get("paid_by")?;
+        let comment_count = row.try_get("comment_count")?;
+        let refund_ids = row.try_get("refund_ids")?;
         let category = if let Ok(name) = row.try_get("category_name") {
             Some(ExpenseCategory {
                 id: row.try_get("category_id")?,
                 name,
+                parent_id: row.try_get("category_parent_id")?,
+                group_id: row.try_get("category_group_id")?,
+                icon: row.try_get("category_icon")?,
+                color: row.try_get("category_color")?,
+                archived_at: row.try_get("category_archived_at")?,
             })
         } else {
             None
@@ -129,17 +372,66 @@ impl FromRow<'_, PgRow> for ExpenseWithPayerAndCategory {
             expense,
             paid_by,
             category,
+            receipt: None,
+            comment_count,
+            refund_ids,
+            approvals: Vec::new(),
         })
     }
 }
 
+/// Lists the expenses of all groups `user_id` is a member of, optionally
+/// narrowed down by `filter`.
 pub async fn get_expenses(
     pool: &PgPool,
-) -> Result<Vec<(ExpenseWithPayerAndCategory, Vec<AccountShare>)>, sqlx::Error> {
+    user_id: i32,
+    filter: ExpenseFilter,
+) -> Result<Vec<ExpenseWithShares>, sqlx::Error> {
+    let merchant = filter.merchant.map(|merchant| escape_like(&merchant));
+    let search = filter
+        .search
+        .map(|search| format!("%{}%", escape_like(&search)));
     let expense_rows = sqlx::query_as::<_, ExpenseWithPayerAndCategory>(GET_ALL_EXPENSE)
+        .bind(user_id)
+        .bind(filter.group_id)
+        .bind(filter.tags)
+        .bind(merchant)
+        .bind(search)
+        .bind(filter.status)
+        .fetch_all(pool)
+        .await?;
+
+    with_shares(pool, expense_rows).await
+}
+
+/// The expenses among `expense_ids` that are visible to `user_id`, missing
+/// ones are left out.
+pub async fn get_expenses_by_id(
+    pool: &PgPool,
+    user_id: i32,
+    expense_ids: &[i32],
+) -> Result<Vec<ExpenseWithShares>, sqlx::Error> {
+    let expense_rows = sqlx::query_as::<_, ExpenseWithPayerAndCategory>(GET_EXPENSES_BY_ID)
+        .bind(user_id)
+        .bind(expense_ids)
         .fetch_all(pool)
         .await?;
 
+    with_shares(pool, expense_rows).await
+}
+
+/// Makes `LIKE` match the wildcards literally.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+async fn with_shares(
+    pool: &PgPool,
+    mut expense_rows: Vec<ExpenseWithPayerAndCategory>,
+) -> Result<Vec<ExpenseWithShares>, sqlx::Error> {
     let expense_id_map: HashMap<_, _> = expense_rows
         .iter()
         .enumerate()
@@ -149,10 +441,18 @@ pub async fn get_expenses(
 
     let shares =
         sqlx::query_as::<_, AccountShare>("SELECT * FROM account_share WHERE expense_id = ANY($1)")
-            .bind(expense_ids)
+            .bind(&expense_ids)
             .fetch_all(pool)
             .await?;
 
+    let mut conn = pool.acquire().await?;
+    let mut receipts = get_receipts(&mut conn, &expense_ids).await?;
+    let mut approvals = get_approvals(&mut *conn, &expense_ids).await?;
+    for row in expense_rows.iter_mut() {
+        row.receipt = receipts.remove(&row.expense.id);
+        row.approvals = approvals.remove(&row.expense.id).unwrap_or_default();
+    }
+
     let mut result = expense_rows.iter().map(|_| Vec::new()).collect::<Vec<_>>();
     for share in shares {
         result[expense_id_map[&share.expense_id]].push(share);
@@ -163,20 +463,27 @@ pub async fn get_expenses(
 
 pub async fn get_expense(
     expense_id: i32,
-    pool: &PgPool,
-) -> Result<Option<(ExpenseWithPayerAndCategory, Vec<AccountShare>)>, sqlx::Error> {
+    conn: &mut PgConnection,
+) -> Result<Option<ExpenseWithShares>, sqlx::Error> {
     let result: Result<ExpenseWithPayerAndCategory, _> = sqlx::query_as(GET_ONE_EXPENSE)
         .bind(expense_id)
-        .fetch_one(pool)
+        .fetch_one(&mut *conn)
         .await;
 
     match result {
-        Ok(expense) => {
+        Ok(mut expense) => {
             let shares: Vec<AccountShare> =
                 sqlx::query_as("SELECT * FROM account_share WHERE expense_id = $1")
                     .bind(expense_id)
-                    .fetch_all(pool)
+                    .fetch_all(&mut *conn)
                     .await?;
+            expense.receipt = get_receipts(&mut *conn, &[expense_id])
+                .await?
+                .remove(&expense_id);
+            expense.approvals = get_approvals(&mut *conn, &[expense_id])
+                .await?
+                .remove(&expense_id)
+                .unwrap_or_default();
 
             Ok(Some((expense, shares)))
         }
@@ -187,8 +494,10 @@ pub async fn get_expense(
 
 pub async fn insert_expense(
     expense: InsertExpense,
-    pool: &PgPool,
-) -> Result<(ExpenseWithPayerAndCategory, Vec<AccountShare>), sqlx::Error> {
+    conn: &mut PgConnection,
+) -> Result<ExpenseWithShares, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
     let expense_id: i32 = sqlx::query(INSERT_EXPENSE)
         .bind(expense.name)
         .bind(expense.created_at.unwrap_or(Utc::now()))
@@ -197,8 +506,16 @@ pub async fn insert_expense(
         .bind(expense.currency)
         .bind(expense.category_id)
         .bind(expense.is_payment)
+        .bind(expense.group_id)
+        .bind(expense.details.note)
+        .bind(expense.details.tags)
+        .bind(expense.details.merchant)
+        .bind(expense.details.place)
+        .bind(expense.details.latitude)
+        .bind(expense.details.longitude)
+        .bind(expense.refund_of)
         .map(|row| row.get("id"))
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?;
 
     for share in expense.shares {
@@ -206,20 +523,29 @@ pub async fn insert_expense(
             .bind(expense_id)
             .bind(share.user_id)
             .bind(share.share)
-            .execute(pool)
+            .execute(&mut *tx)
             .await?;
     }
 
-    Ok(get_expense(expense_id, pool)
+    if let Some(receipt) = expense.receipt {
+        insert_receipt(&mut tx, expense_id, receipt).await?;
+    }
+
+    let expense = get_expense(expense_id, &mut tx)
         .await?
-        .expect("Failed to fetch after insert"))
+        .expect("Failed to fetch after insert");
+    tx.commit().await?;
+
+    Ok(expense)
 }
 
 pub async fn update_expense(
     expense_id: i32,
     expense: InsertExpense,
-    pool: &PgPool,
-) -> Result<(ExpenseWithPayerAndCategory, Vec<AccountShare>), sqlx::Error> {
+    conn: &mut PgConnection,
+) -> Result<ExpenseWithShares, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
     sqlx::query(UPDATE_EXPENSE)
         .bind(expense_id)
         .bind(expense.name)
@@ -229,7 +555,21 @@ pub async fn update_expense(
         .bind(expense.currency)
         .bind(expense.category_id)
         .bind(expense.is_payment)
-        .execute(pool)
+        .bind(expense.group_id)
+        .bind(expense.details.note)
+        .bind(expense.details.tags)
+        .bind(expense.details.merchant)
+        .bind(expense.details.place)
+        .bind(expense.details.latitude)
+        .bind(expense.details.longitude)
+        .execute(&mut *tx)
+        .await?;
+
+    let user_ids: Vec<i32> = expense.shares.iter().map(|share| share.user_id).collect();
+    sqlx::query(DELETE_STALE_SHARES)
+        .bind(expense_id)
+        .bind(user_ids)
+        .execute(&mut *tx)
         .await?;
 
     for share in expense.shares {
@@ -237,20 +577,171 @@ pub async fn update_expense(
             .bind(expense_id)
             .bind(share.user_id)
             .bind(share.share)
-            .execute(pool)
+            .execute(&mut *tx)
             .await?;
     }
 
-    Ok(get_expense(expense_id, pool)
+    sqlx::query("DELETE FROM expense_item WHERE expense_id = $1;")
+        .bind(expense_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM expense_extra WHERE expense_id = $1;")
+        .bind(expense_id)
+        .execute(&mut *tx)
+        .await?;
+    if let Some(receipt) = expense.receipt {
+        insert_receipt(&mut tx, expense_id, receipt).await?;
+    }
+
+    let expense = get_expense(expense_id, &mut tx)
         .await?
-        .expect("Failed to fetch after upsert"))
+        .expect("Failed to fetch after upsert");
+    tx.commit().await?;
+
+    Ok(expense)
 }
 
-pub async fn delete_expense(expense_id: i32, pool: &PgPool) -> Result<(), sqlx::Error> {
+/// The receipts of those of `expense_ids` that are itemised, by expense id.
+async fn get_receipts(
+    conn: &mut PgConnection,
+    expense_ids: &[i32],
+) -> Result<HashMap<i32, Receipt>, sqlx::Error> {
+    let items: Vec<ExpenseItem> = sqlx::query_as(
+        "SELECT * FROM expense_item WHERE expense_id = ANY($1) ORDER BY expense_id, position;",
+    )
+    .bind(expense_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    if items.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let item_ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+    let item_shares: Vec<ExpenseItemShare> = sqlx::query_as(
+        "SELECT * FROM expense_item_share WHERE item_id = ANY($1) ORDER BY item_id, user_id;",
+    )
+    .bind(&item_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    let extras: Vec<ExpenseExtra> =
+        sqlx::query_as("SELECT * FROM expense_extra WHERE expense_id = ANY($1) ORDER BY id;")
+            .bind(expense_ids)
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut shares_by_item: HashMap<i32, Vec<ExpenseItemShare>> = HashMap::new();
+    for share in item_shares {
+        shares_by_item.entry(share.item_id).or_default().push(share);
+    }
+
+    let mut receipts: HashMap<i32, Receipt> = HashMap::new();
+    for item in items {
+        let shares = shares_by_item.remove(&item.id).unwrap_or_default();
+        receipts
+            .entry(item.expense_id)
+            .or_default()
+            .items
+            .push((item, shares));
+    }
+    for extra in extras {
+        if let Some(receipt) = receipts.get_mut(&extra.expense_id) {
+            receipt.extras.push(extra);
+        }
+    }
+
+    Ok(receipts)
+}
+
+async fn insert_receipt(
+    conn: &mut PgConnection,
+    expense_id: i32,
+    receipt: InsertReceipt,
+) -> Result<(), sqlx::Error> {
+    for (position, item) in receipt.items.into_iter().enumerate() {
+        let item_id: i32 = sqlx::query_scalar(INSERT_ITEM)
+            .bind(expense_id)
+            .bind(position as i32)
+            .bind(item.name)
+            .bind(item.amount)
+            .bind(item.category_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        for share in item.shares {
+            sqlx::query(INSERT_ITEM_SHARE)
+                .bind(item_id)
+                .bind(share.user_id)
+                .bind(share.weight)
+                .bind(share.consumed)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    for extra in receipt.extras {
+        sqlx::query(INSERT_EXTRA)
+            .bind(expense_id)
+            .bind(extra.kind)
+            .bind(extra.amount)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_expense(
+    expense_id: i32,
+    executor: impl PgExecutor<'_>,
+) -> Result<(), sqlx::Error> {
     sqlx::query(DELETE_EXPENSE)
         .bind(expense_id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
     Ok(())
 }
+
+/// Expenses of a group with exactly `total` created within `from..to`, used to
+/// recognise expenses that already exist when importing.
+pub async fn get_expenses_with_total(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    total: i32,
+    currency: &str,
+    from: chrono::DateTime<Utc>,
+    to: chrono::DateTime<Utc>,
+) -> Result<Vec<Expense>, sqlx::Error> {
+    sqlx::query_as(GET_EXPENSES_WITH_TOTAL)
+        .bind(group_id)
+        .bind(total)
+        .bind(currency)
+        .bind(from)
+        .bind(to)
+        .fetch_all(executor)
+        .await
+}
+
+/// What each participant got back by the refunds of an expense so far, the
+/// negation of what they consumed by them.
+pub async fn get_refunded(
+    executor: impl PgExecutor<'_>,
+    expense_id: i32,
+) -> Result<HashMap<i32, i64>, sqlx::Error> {
+    let rows: Vec<(i32, i64)> = sqlx::query_as(
+        r#"
+SELECT
+    s.user_id,
+    SUM(s.share - (CASE WHEN s.user_id = e.paid_by THEN e.total ELSE 0 END))::BIGINT
+FROM expense as e
+INNER JOIN account_share as s ON s.expense_id = e.id
+WHERE e.refund_of = $1
+GROUP BY s.user_id;
+    "#,
+    )
+    .bind(expense_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows.into_iter().collect())
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, Connection, PgConnection, PgExecutor};
+
+/// The status of an expense that counts towards balances.
+pub const CONFIRMED: &str = "confirmed";
+pub const PENDING: &str = "pending";
+pub const APPROVED: &str = "approved";
+pub const DISPUTED: &str = "disputed";
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct ExpenseApproval {
+    pub expense_id: i32,
+    pub user_id: i32,
+    /// One of `pending`, `approved` or `disputed`.
+    pub status: String,
+    /// Only set for disputes.
+    pub reason: Option<String>,
+    pub decided_at: Option<chrono::DateTime<Utc>>,
+}
+
+pub async fn get_approvals(
+    executor: impl PgExecutor<'_>,
+    expense_ids: &[i32],
+) -> Result<HashMap<i32, Vec<ExpenseApproval>>, sqlx::Error> {
+    let approvals: Vec<ExpenseApproval> = sqlx::query_as(
+        "SELECT * FROM expense_approval WHERE expense_id = ANY($1) ORDER BY user_id;",
+    )
+    .bind(expense_ids)
+    .fetch_all(executor)
+    .await?;
+
+    let mut approvals_by_expense: HashMap<i32, Vec<ExpenseApproval>> = HashMap::new();
+    for approval in approvals {
+        approvals_by_expense
+            .entry(approval.expense_id)
+            .or_default()
+            .push(approval);
+    }
+
+    Ok(approvals_by_expense)
+}
+
+/// Replaces the approvals of the expense with pending ones for `user_ids`,
+/// which confirms the expense when there are none.
+pub async fn require_approvals(
+    conn: &mut PgConnection,
+    expense_id: i32,
+    user_ids: &[i32],
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("DELETE FROM expense_approval WHERE expense_id = $1;")
+        .bind(expense_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO expense_approval (expense_id, user_id)
+SELECT $1, user_id FROM unnest($2::INTEGER[]) as user_id;
+    "#,
+    )
+    .bind(expense_id)
+    .bind(user_ids)
+    .execute(&mut *tx)
+    .await?;
+    refresh_status(&mut tx, expense_id).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Records the decision of a participant asked for approval. Returns `false`
+/// when the user wasn't asked.
+pub async fn decide(
+    conn: &mut PgConnection,
+    expense_id: i32,
+    user_id: i32,
+    status: &str,
+    reason: Option<String>,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    let result = sqlx::query(
+        r#"
+UPDATE expense_approval
+SET
+    status = $3,
+    reason = $4,
+    decided_at = NOW()
+WHERE expense_id = $1 AND user_id = $2;
+    "#,
+    )
+    .bind(expense_id)
+    .bind(user_id)
+    .bind(status)
+    .bind(reason)
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    refresh_status(&mut tx, expense_id).await?;
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Derives the status of the expense from its approvals. Always updates the
+/// expense, so that clients learn about every decision.
+async fn refresh_status(conn: &mut PgConnection, expense_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+UPDATE expense
+SET status = CASE
+    WHEN EXISTS (SELECT 1 FROM expense_approval WHERE expense_id = $1 AND status = 'disputed')
+        THEN 'disputed'
+    WHEN EXISTS (SELECT 1 FROM expense_approval WHERE expense_id = $1 AND status = 'pending')
+        THEN 'pending'
+    ELSE 'confirmed'
+END
+WHERE id = $1;
+    "#,
+    )
+    .bind(expense_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -1,24 +1,249 @@
+use chrono::Utc;
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::{Connection, PgConnection, PgExecutor, PgPool};
 
 #[derive(sqlx::FromRow, Serialize, Clone)]
 pub struct ExpenseCategory {
     pub id: i32,
     pub name: String,
+    pub parent_id: Option<i32>,
+    /// `None` for the global defaults shared by every group.
+    pub group_id: Option<i32>,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+    pub archived_at: Option<chrono::DateTime<Utc>>,
 }
 
-pub async fn get_expense_categories(pool: &PgPool) -> Result<Vec<ExpenseCategory>, sqlx::Error> {
+pub struct InsertExpenseCategory {
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub group_id: Option<i32>,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+pub struct UpdateExpenseCategory {
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+/// Lists the global categories together with the custom ones of `group_id`,
+/// most used first.
+pub async fn get_expense_categories(
+    pool: &PgPool,
+    group_id: Option<i32>,
+    include_archived: bool,
+) -> Result<Vec<ExpenseCategory>, sqlx::Error> {
     let categories = sqlx::query_as::<_, ExpenseCategory>(
         r#"
     SELECT ec.* FROM expense_category as ec
     LEFT JOIN expense as e
     ON e.category_id = ec.id
+    WHERE (ec.group_id IS NULL OR ec.group_id = $1)
+    AND ($2 OR ec.archived_at IS NULL)
     GROUP BY ec.id
     ORDER BY count(e.category_id) DESC;
     "#,
     )
+    .bind(group_id)
+    .bind(include_archived)
     .fetch_all(pool)
     .await?;
 
     Ok(categories)
 }
+
+pub async fn get_expense_category(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<ExpenseCategory>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM expense_category WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn insert_expense_category(
+    executor: impl PgExecutor<'_>,
+    category: InsertExpenseCategory,
+) -> Result<ExpenseCategory, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+    INSERT INTO expense_category (name, parent_id, group_id, icon, color)
+    VALUES ($1, $2, $3, $4, $5)
+    RETURNING *;
+    "#,
+    )
+    .bind(category.name)
+    .bind(category.parent_id)
+    .bind(category.group_id)
+    .bind(category.icon)
+    .bind(category.color)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn update_expense_category(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    category: UpdateExpenseCategory,
+) -> Result<ExpenseCategory, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+    UPDATE expense_category
+    SET
+        name = $2,
+        parent_id = $3,
+        icon = $4,
+        color = $5
+    WHERE id = $1
+    RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(category.name)
+    .bind(category.parent_id)
+    .bind(category.icon)
+    .bind(category.color)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn set_expense_category_archived(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    archived: bool,
+) -> Result<ExpenseCategory, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+    UPDATE expense_category
+    SET archived_at = CASE WHEN $2 THEN COALESCE(archived_at, NOW()) ELSE NULL END
+    WHERE id = $1
+    RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(archived)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn count_child_categories(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM expense_category WHERE parent_id = $1;")
+        .bind(id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Moves all expenses, line items and sub-categories of `from_id` over to
+/// `into_id` and archives `from_id`.
+pub async fn merge_expense_category(
+    conn: &mut PgConnection,
+    from_id: i32,
+    into_id: i32,
+) -> Result<ExpenseCategory, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("UPDATE expense SET category_id = $2 WHERE category_id = $1;")
+        .bind(from_id)
+        .bind(into_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE expense_item SET category_id = $2 WHERE category_id = $1;")
+        .bind(from_id)
+        .bind(into_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+UPDATE split_profile as p
+SET category_id = $2
+WHERE p.category_id = $1
+AND NOT EXISTS (
+    SELECT 1 FROM split_profile as o WHERE o.group_id = p.group_id AND o.category_id = $2
+);
+    "#,
+    )
+    .bind(from_id)
+    .bind(into_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("UPDATE expense_category SET parent_id = $2 WHERE parent_id = $1;")
+        .bind(from_id)
+        .bind(into_id)
+        .execute(&mut *tx)
+        .await?;
+    set_expense_category_archived(&mut *tx, from_id, true).await?;
+
+    let into = get_expense_category(&mut *tx, into_id)
+        .await?
+        .expect("Failed to fetch after merge");
+    tx.commit().await?;
+
+    Ok(into)
+}
+
+#[derive(sqlx::FromRow)]
+pub struct CategorizedExpense {
+    pub name: String,
+    pub category_id: i32,
+    pub paid_by: i32,
+    pub total: i32,
+}
+
+/// The most recent categorised expenses of a group, used as history when
+/// suggesting categories.
+pub async fn get_categorized_expenses(
+    pool: &PgPool,
+    group_id: i32,
+    limit: i64,
+) -> Result<Vec<CategorizedExpense>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+    SELECT e.name, e.category_id, e.paid_by, e.total
+    FROM expense as e
+    INNER JOIN expense_category as ec ON ec.id = e.category_id
+    WHERE e.group_id = $1
+    AND NOT e.is_payment
+    AND e.refund_of IS NULL
+    AND ec.archived_at IS NULL
+    ORDER BY e.created_at DESC
+    LIMIT $2;
+    "#,
+    )
+    .bind(group_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes categories archived before `before` that nothing refers to
+/// anymore, returning how many were deleted. A parent is only deleted once
+/// its children are gone, so a run may leave parents for the next one.
+pub async fn delete_unused_archived(
+    executor: impl PgExecutor<'_>,
+    before: chrono::DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+DELETE FROM expense_category as c
+WHERE c.archived_at < $1
+AND NOT EXISTS (SELECT 1 FROM expense_category WHERE parent_id = c.id)
+AND NOT EXISTS (SELECT 1 FROM expense WHERE category_id = c.id)
+AND NOT EXISTS (SELECT 1 FROM expense_item WHERE category_id = c.id)
+AND NOT EXISTS (SELECT 1 FROM budget WHERE category_id = c.id)
+AND NOT EXISTS (SELECT 1 FROM category_rule WHERE category_id = c.id)
+AND NOT EXISTS (SELECT 1 FROM split_profile WHERE category_id = c.id);
+    "#,
+    )
+    .bind(before)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,91 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct ExpenseComment {
+    pub id: i32,
+    pub expense_id: i32,
+    pub user_id: i32,
+    pub body: String,
+    pub created_at: chrono::DateTime<Utc>,
+    /// `None` until the comment is edited.
+    pub updated_at: Option<chrono::DateTime<Utc>>,
+}
+
+pub struct InsertExpenseComment {
+    pub expense_id: i32,
+    pub user_id: i32,
+    pub body: String,
+}
+
+/// Oldest first, the way a conversation reads.
+pub async fn get_comments(
+    executor: impl PgExecutor<'_>,
+    expense_id: i32,
+) -> Result<Vec<ExpenseComment>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT * FROM expense_comment WHERE expense_id = $1 ORDER BY created_at ASC, id ASC;",
+    )
+    .bind(expense_id)
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn get_comment(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<ExpenseComment>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM expense_comment WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn insert_comment(
+    executor: impl PgExecutor<'_>,
+    comment: InsertExpenseComment,
+) -> Result<ExpenseComment, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO expense_comment (expense_id, user_id, body)
+VALUES ($1, $2, $3)
+RETURNING *;
+    "#,
+    )
+    .bind(comment.expense_id)
+    .bind(comment.user_id)
+    .bind(comment.body)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn update_comment(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    body: String,
+) -> Result<ExpenseComment, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE expense_comment
+SET
+    body = $2,
+    updated_at = NOW()
+WHERE id = $1
+RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(body)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_comment(executor: impl PgExecutor<'_>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM expense_comment WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
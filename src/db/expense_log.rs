@@ -0,0 +1,53 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct ExpenseLog {
+    pub id: i32,
+    pub expense_id: i32,
+    pub user_id: i32,
+    pub action: String,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertExpenseLog {
+    pub expense_id: i32,
+    pub user_id: i32,
+    pub action: String,
+}
+
+pub async fn get_expense_log(
+    pool: &PgPool,
+    expense_id: i32,
+) -> Result<Vec<ExpenseLog>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT id, expense_id, user_id, action, created_at
+FROM expense_log
+WHERE expense_id = $1
+ORDER BY created_at ASC;
+    "#,
+    )
+    .bind(expense_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_expense_log(
+    executor: impl PgExecutor<'_>,
+    log: InsertExpenseLog,
+) -> Result<ExpenseLog, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO expense_log (expense_id, user_id, action)
+VALUES ($1, $2, $3)
+RETURNING id, expense_id, user_id, action, created_at;
+    "#,
+    )
+    .bind(log.expense_id)
+    .bind(log.user_id)
+    .bind(log.action)
+    .fetch_one(executor)
+    .await
+}
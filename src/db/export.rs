@@ -0,0 +1,78 @@
+use chrono::Utc;
+use futures::stream::BoxStream;
+use sqlx::{prelude::FromRow, PgPool};
+
+/// With `$6` set every expense is repeated once per share, otherwise the share
+/// columns are all `NULL`.
+static GET_EXPORT_ROWS: &str = r#"
+SELECT
+    e.id as expense_id,
+    e.created_at,
+    e.name,
+    e.total,
+    e.currency,
+    e.is_payment,
+    e.refund_of,
+    e.paid_by,
+    payer.name as paid_by_name,
+    ec.name as category_name,
+    parent.name as parent_category_name,
+    s.user_id,
+    participant.name as user_name,
+    s.share
+FROM expense as e
+INNER JOIN users as payer ON payer.id = e.paid_by
+LEFT JOIN expense_category as ec ON ec.id = e.category_id
+LEFT JOIN expense_category as parent ON parent.id = ec.parent_id
+LEFT JOIN account_share as s ON s.expense_id = e.id AND $6
+LEFT JOIN users as participant ON participant.id = s.user_id
+WHERE e.group_id = $1
+AND e.created_at >= $2
+AND e.created_at < $3
+AND ($4::INTEGER IS NULL OR e.category_id = $4 OR ec.parent_id = $4)
+AND ($5 OR NOT e.is_payment)
+ORDER BY e.created_at, e.id, s.user_id;
+"#;
+
+#[derive(Clone)]
+pub struct ExportFilter {
+    pub group_id: i32,
+    pub from: chrono::DateTime<Utc>,
+    pub to: chrono::DateTime<Utc>,
+    /// Also matches the sub-categories of the category.
+    pub category_id: Option<i32>,
+    pub include_payments: bool,
+    pub per_share: bool,
+}
+
+#[derive(FromRow)]
+pub struct ExportRow {
+    pub expense_id: i32,
+    pub created_at: chrono::DateTime<Utc>,
+    pub name: String,
+    pub total: i32,
+    pub currency: String,
+    pub is_payment: bool,
+    pub refund_of: Option<i32>,
+    pub paid_by: i32,
+    pub paid_by_name: String,
+    pub category_name: Option<String>,
+    pub parent_category_name: Option<String>,
+    pub user_id: Option<i32>,
+    pub user_name: Option<String>,
+    pub share: Option<i32>,
+}
+
+pub fn get_export_rows<'a>(
+    pool: &'a PgPool,
+    filter: &ExportFilter,
+) -> BoxStream<'a, Result<ExportRow, sqlx::Error>> {
+    sqlx::query_as(GET_EXPORT_ROWS)
+        .bind(filter.group_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.category_id)
+        .bind(filter.include_payments)
+        .bind(filter.per_share)
+        .fetch(pool)
+}
@@ -0,0 +1,162 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+use super::user::User;
+
+/// The group every user belongs to, holding everything that was shared before
+/// groups were introduced.
+pub const DEFAULT_GROUP_ID: i32 = 1;
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct Group {
+    pub id: i32,
+    pub name: String,
+    pub created_at: chrono::DateTime<Utc>,
+    /// In minor units, expenses with a larger total need to be approved by
+    /// their participants. `None` when no approval is needed.
+    pub approval_threshold: Option<i32>,
+}
+
+pub async fn get_groups_for_user(pool: &PgPool, user_id: i32) -> Result<Vec<Group>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT g.id, g.name, g.created_at, g.approval_threshold
+FROM expense_group as g
+INNER JOIN group_member as gm ON gm.group_id = g.id
+WHERE gm.user_id = $1
+ORDER BY g.id;
+    "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_groups(pool: &PgPool) -> Result<Vec<Group>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM expense_group ORDER BY id;")
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_group(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+) -> Result<Option<Group>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM expense_group WHERE id = $1;")
+        .bind(group_id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn insert_group(executor: impl PgExecutor<'_>, name: &str) -> Result<Group, sqlx::Error> {
+    sqlx::query_as("INSERT INTO expense_group (name) VALUES ($1) RETURNING *;")
+        .bind(name)
+        .fetch_one(executor)
+        .await
+}
+
+/// Only applies to expenses created or changed from now on.
+pub async fn set_approval_threshold(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    approval_threshold: Option<i32>,
+) -> Result<Group, sqlx::Error> {
+    sqlx::query_as("UPDATE expense_group SET approval_threshold = $2 WHERE id = $1 RETURNING *;")
+        .bind(group_id)
+        .bind(approval_threshold)
+        .fetch_one(executor)
+        .await
+}
+
+pub async fn get_member_ids(pool: &PgPool, group_id: i32) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT user_id FROM group_member WHERE group_id = $1 ORDER BY user_id;")
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_members(pool: &PgPool, group_id: i32) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT u.*
+FROM users as u
+INNER JOIN group_member as gm ON gm.user_id = u.id
+WHERE gm.group_id = $1
+ORDER BY u.id;
+    "#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn is_member(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM group_member WHERE group_id = $1 AND user_id = $2);",
+    )
+    .bind(group_id)
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+}
+
+/// Those of `user_ids` that are not members of the group.
+pub async fn get_non_members(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    user_ids: &[i32],
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+SELECT id
+FROM UNNEST($2::INTEGER[]) as id
+WHERE NOT EXISTS (SELECT 1 FROM group_member WHERE group_id = $1 AND user_id = id)
+ORDER BY id;
+    "#,
+    )
+    .bind(group_id)
+    .bind(user_ids)
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn add_member(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    user_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+INSERT INTO group_member (group_id, user_id)
+VALUES ($1, $2)
+ON CONFLICT DO NOTHING;
+    "#,
+    )
+    .bind(group_id)
+    .bind(user_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// The user keeps their shares of the group's expenses, so their balance in
+/// the group stays the same. Returns `false` if the user wasn't a member.
+pub async fn remove_member(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM group_member WHERE group_id = $1 AND user_id = $2;")
+        .bind(group_id)
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
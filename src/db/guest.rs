@@ -0,0 +1,283 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, Connection, PgConnection, PgExecutor, PgPool};
+
+use super::group;
+
+static GET_GUESTS: &str = r#"
+SELECT u.id, u.name, g.email, u.phone_number, g.group_id, g.created_by, g.created_at
+FROM guest as g
+INNER JOIN users as u ON u.id = g.user_id
+WHERE g.group_id = $1
+ORDER BY u.id;
+"#;
+
+static GET_GUEST: &str = r#"
+SELECT u.id, u.name, g.email, u.phone_number, g.group_id, g.created_by, g.created_at
+FROM guest as g
+INNER JOIN users as u ON u.id = g.user_id
+WHERE g.user_id = $1;
+"#;
+
+/// A participant without an account, backed by a user with `is_guest` set.
+#[derive(FromRow, Serialize, Clone)]
+pub struct Guest {
+    /// The id of the guest's user.
+    pub id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub group_id: i32,
+    pub created_by: i32,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertGuest {
+    pub group_id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub created_by: i32,
+}
+
+pub struct UpdateGuest {
+    pub name: String,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+}
+
+pub async fn get_guests(pool: &PgPool, group_id: i32) -> Result<Vec<Guest>, sqlx::Error> {
+    sqlx::query_as(GET_GUESTS)
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_guest(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<Guest>, sqlx::Error> {
+    sqlx::query_as(GET_GUEST)
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Creates the guest's user and adds it to the group.
+pub async fn insert_guest(
+    conn: &mut PgConnection,
+    guest: InsertGuest,
+) -> Result<Guest, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO users (name, phone_number, is_guest) VALUES ($1, $2, TRUE) RETURNING id;",
+    )
+    .bind(guest.name)
+    .bind(guest.phone_number)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO guest (user_id, group_id, email, created_by) VALUES ($1, $2, $3, $4);",
+    )
+    .bind(id)
+    .bind(guest.group_id)
+    .bind(guest.email)
+    .bind(guest.created_by)
+    .execute(&mut *tx)
+    .await?;
+    group::add_member(&mut *tx, guest.group_id, id).await?;
+
+    let guest = get_guest(&mut *tx, id)
+        .await?
+        .expect("Failed to fetch after insert");
+    tx.commit().await?;
+
+    Ok(guest)
+}
+
+pub async fn update_guest(
+    conn: &mut PgConnection,
+    id: i32,
+    guest: UpdateGuest,
+) -> Result<Guest, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("UPDATE users SET name = $2, phone_number = $3 WHERE id = $1;")
+        .bind(id)
+        .bind(guest.name)
+        .bind(guest.phone_number)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE guest SET email = $2 WHERE user_id = $1;")
+        .bind(id)
+        .bind(guest.email)
+        .execute(&mut *tx)
+        .await?;
+
+    let guest = get_guest(&mut *tx, id)
+        .await?
+        .expect("Failed to fetch after update");
+    tx.commit().await?;
+
+    Ok(guest)
+}
+
+/// Whether the guest pays for or shares in any expense.
+pub async fn has_expenses(executor: impl PgExecutor<'_>, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+SELECT EXISTS(SELECT 1 FROM account_share WHERE user_id = $1)
+OR EXISTS(SELECT 1 FROM expense WHERE paid_by = $1);
+    "#,
+    )
+    .bind(id)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_guest(conn: &mut PgConnection, id: i32) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("DELETE FROM group_member WHERE user_id = $1;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM users WHERE id = $1 AND is_guest;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Hands everything of the guest over to `user_id` and deletes the guest.
+/// Where both took part in the same expense or line item their shares are
+/// added up.
+pub async fn merge_guest(
+    conn: &mut PgConnection,
+    guest_id: i32,
+    user_id: i32,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    // Touches every affected expense as well, so that their changes reach
+    // syncing and listening clients.
+    sqlx::query(
+        r#"
+UPDATE expense
+SET paid_by = CASE WHEN paid_by = $1 THEN $2 ELSE paid_by END
+WHERE paid_by = $1
+OR id IN (SELECT expense_id FROM account_share WHERE user_id = $1);
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+INSERT INTO account_share (expense_id, user_id, share)
+SELECT expense_id, $2, share FROM account_share WHERE user_id = $1
+ON CONFLICT (expense_id, user_id) DO UPDATE
+SET share = account_share.share + EXCLUDED.share;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM account_share WHERE user_id = $1;")
+        .bind(guest_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO expense_item_share (item_id, user_id, weight, consumed)
+SELECT item_id, $2, weight, consumed FROM expense_item_share WHERE user_id = $1
+ON CONFLICT (item_id, user_id) DO UPDATE
+SET
+    weight = expense_item_share.weight + EXCLUDED.weight,
+    consumed = expense_item_share.consumed + EXCLUDED.consumed;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM expense_item_share WHERE user_id = $1;")
+        .bind(guest_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO split_profile_weight (profile_id, user_id, weight)
+SELECT profile_id, $2, weight FROM split_profile_weight WHERE user_id = $1
+ON CONFLICT (profile_id, user_id) DO UPDATE
+SET weight = split_profile_weight.weight + EXCLUDED.weight;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM split_profile_weight WHERE user_id = $1;")
+        .bind(guest_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO period_close_balance (close_id, user_id, currency, balance)
+SELECT close_id, $2, currency, balance FROM period_close_balance WHERE user_id = $1
+ON CONFLICT (close_id, user_id, currency) DO UPDATE
+SET balance = period_close_balance.balance + EXCLUDED.balance;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM period_close_balance WHERE user_id = $1;")
+        .bind(guest_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE budget SET user_id = $2 WHERE user_id = $1;")
+        .bind(guest_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE expense_log SET user_id = $2 WHERE user_id = $1;")
+        .bind(guest_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+INSERT INTO group_member (group_id, user_id)
+SELECT group_id, $2 FROM group_member WHERE user_id = $1
+ON CONFLICT DO NOTHING;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+UPDATE users
+SET phone_number = COALESCE(phone_number, (SELECT phone_number FROM users WHERE id = $1))
+WHERE id = $2;
+    "#,
+    )
+    .bind(guest_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    delete_guest(&mut tx, guest_id).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgConnection, PgExecutor, PgPool};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct Invitation {
+    pub id: i32,
+    pub group_id: i32,
+    pub guest_id: Option<i32>,
+    pub token: String,
+    pub email: Option<String>,
+    pub created_by: i32,
+    pub created_at: chrono::DateTime<Utc>,
+    pub expires_at: chrono::DateTime<Utc>,
+    pub accepted_by: Option<i32>,
+    pub accepted_at: Option<chrono::DateTime<Utc>>,
+}
+
+pub struct InsertInvitation {
+    pub group_id: i32,
+    pub guest_id: Option<i32>,
+    pub token: String,
+    pub email: Option<String>,
+    pub created_by: i32,
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+/// Invitations of a group that can still be accepted.
+pub async fn get_pending_invitations(
+    pool: &PgPool,
+    group_id: i32,
+) -> Result<Vec<Invitation>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT * FROM invitation
+WHERE group_id = $1
+AND accepted_at IS NULL
+AND expires_at > NOW()
+ORDER BY created_at DESC, id DESC;
+    "#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_invitation(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<Invitation>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM invitation WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Locks the invitation so that it can only be accepted once.
+pub async fn lock_invitation_by_token(
+    conn: &mut PgConnection,
+    token: &str,
+) -> Result<Option<Invitation>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM invitation WHERE token = $1 FOR UPDATE;")
+        .bind(token)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn insert_invitation(
+    executor: impl PgExecutor<'_>,
+    invitation: InsertInvitation,
+) -> Result<Invitation, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO invitation (group_id, guest_id, token, email, created_by, expires_at)
+VALUES ($1, $2, $3, $4, $5, $6)
+RETURNING *;
+    "#,
+    )
+    .bind(invitation.group_id)
+    .bind(invitation.guest_id)
+    .bind(invitation.token)
+    .bind(invitation.email)
+    .bind(invitation.created_by)
+    .bind(invitation.expires_at)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn mark_accepted(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    user_id: i32,
+) -> Result<Invitation, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE invitation
+SET accepted_by = $2, accepted_at = NOW()
+WHERE id = $1
+RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_invitation(executor: impl PgExecutor<'_>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM invitation WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Deletes invitations that expired before `before` without being accepted,
+/// returning how many were deleted.
+pub async fn delete_expired(
+    executor: impl PgExecutor<'_>,
+    before: chrono::DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result =
+        sqlx::query("DELETE FROM invitation WHERE accepted_at IS NULL AND expires_at < $1;")
+            .bind(before)
+            .execute(executor)
+            .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,20 @@
+use sqlx::PgExecutor;
+
+/// Versions of the migrations applied to the database, oldest first. Empty
+/// before the first migration has run.
+pub async fn get_applied_versions(executor: impl PgExecutor<'_>) -> Result<Vec<i64>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+SELECT version FROM _sqlx_migrations
+WHERE success
+ORDER BY version;
+    "#,
+    )
+    .fetch_all(executor)
+    .await
+    .or_else(|err| match err {
+        // The table is created by the first run of the migrations.
+        sqlx::Error::Database(err) if err.code().as_deref() == Some("42P01") => Ok(Vec::new()),
+        err => Err(err),
+    })
+}
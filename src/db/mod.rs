@@ -1,5 +1,23 @@
 pub mod expense_category;
 pub mod user;
 pub mod expense;
+pub mod expense_log;
+pub mod export;
+pub mod group;
 pub mod balance;
+pub mod budget;
+pub mod category_rule;
 pub mod image;
+pub mod notification;
+pub mod push_subscription;
+pub mod report;
+pub mod sync;
+pub mod webhook;
+pub mod guest;
+pub mod invitation;
+pub mod split_profile;
+pub mod expense_comment;
+pub mod expense_approval;
+pub mod period_close;
+pub mod backup;
+pub mod migration;
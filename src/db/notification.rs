@@ -0,0 +1,194 @@
+use chrono::Utc;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+/// Every group and currency in which the user's balance is below
+/// `-threshold`, along with when it last went below.
+static GET_NEGATIVE_BALANCES: &str = r#"
+WITH history AS (
+    SELECT
+        e.id as expense_id,
+        e.group_id,
+        e.currency,
+        e.created_at,
+        SUM(s.share) OVER (
+            PARTITION BY e.group_id, e.currency
+            ORDER BY e.created_at, e.id
+        )::BIGINT as balance
+    FROM account_share as s
+    INNER JOIN expense as e ON e.id = s.expense_id
+    WHERE s.user_id = $1
+    AND e.status = 'confirmed'
+),
+latest AS (
+    SELECT DISTINCT ON (group_id, currency) group_id, currency, balance
+    FROM history
+    ORDER BY group_id, currency, created_at DESC, expense_id DESC
+)
+SELECT
+    l.group_id,
+    l.currency,
+    l.balance,
+    (
+        SELECT MIN(h.created_at)
+        FROM history as h
+        WHERE h.group_id = l.group_id
+        AND h.currency = l.currency
+        AND h.created_at > COALESCE(
+            (
+                SELECT MAX(ok.created_at)
+                FROM history as ok
+                WHERE ok.group_id = l.group_id
+                AND ok.currency = l.currency
+                AND ok.balance >= -$2
+            ),
+            '-infinity'::TIMESTAMPTZ
+        )
+    ) as negative_since
+FROM latest as l
+WHERE l.balance < -$2;
+"#;
+
+#[derive(FromRow, Clone)]
+pub struct NotificationPreference {
+    pub user_id: i32,
+    pub expense_created: bool,
+    pub settlement_received: bool,
+    pub expense_commented: bool,
+    pub negative_balance: bool,
+    /// In minor units.
+    pub negative_balance_threshold: i32,
+    pub negative_balance_days: i32,
+}
+
+impl NotificationPreference {
+    /// What users get before changing anything, matching the column defaults.
+    pub fn default_for(user_id: i32) -> Self {
+        NotificationPreference {
+            user_id,
+            expense_created: true,
+            settlement_received: true,
+            expense_commented: true,
+            negative_balance: true,
+            negative_balance_threshold: 50000,
+            negative_balance_days: 7,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct NegativeBalance {
+    pub group_id: i32,
+    pub currency: String,
+    pub balance: i64,
+    pub negative_since: chrono::DateTime<Utc>,
+}
+
+pub async fn get_notification_preference(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+) -> Result<NotificationPreference, sqlx::Error> {
+    let preference = sqlx::query_as("SELECT * FROM notification_preference WHERE user_id = $1;")
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await?;
+
+    Ok(preference.unwrap_or(NotificationPreference::default_for(user_id)))
+}
+
+pub async fn upsert_notification_preference(
+    executor: impl PgExecutor<'_>,
+    preference: NotificationPreference,
+) -> Result<NotificationPreference, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO notification_preference (
+    user_id,
+    expense_created,
+    settlement_received,
+    negative_balance,
+    negative_balance_threshold,
+    negative_balance_days,
+    expense_commented
+)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT (user_id) DO UPDATE
+SET
+    expense_created = EXCLUDED.expense_created,
+    settlement_received = EXCLUDED.settlement_received,
+    negative_balance = EXCLUDED.negative_balance,
+    negative_balance_threshold = EXCLUDED.negative_balance_threshold,
+    negative_balance_days = EXCLUDED.negative_balance_days,
+    expense_commented = EXCLUDED.expense_commented
+RETURNING *;
+    "#,
+    )
+    .bind(preference.user_id)
+    .bind(preference.expense_created)
+    .bind(preference.settlement_received)
+    .bind(preference.negative_balance)
+    .bind(preference.negative_balance_threshold)
+    .bind(preference.negative_balance_days)
+    .bind(preference.expense_commented)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn get_negative_balances(
+    pool: &PgPool,
+    user_id: i32,
+    threshold: i32,
+) -> Result<Vec<NegativeBalance>, sqlx::Error> {
+    sqlx::query_as(GET_NEGATIVE_BALANCES)
+        .bind(user_id)
+        .bind(threshold as i64)
+        .fetch_all(pool)
+        .await
+}
+
+/// Records that the reminder is being sent, unless one was already sent within
+/// the last `days` days. Only one of several instances claims it.
+pub async fn claim_balance_reminder(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+    group_id: i32,
+    currency: &str,
+    days: i32,
+) -> Result<bool, sqlx::Error> {
+    let claimed: Option<chrono::DateTime<Utc>> = sqlx::query_scalar(
+        r#"
+INSERT INTO balance_reminder (user_id, group_id, currency)
+VALUES ($1, $2, $3)
+ON CONFLICT (user_id, group_id, currency) DO UPDATE
+SET sent_at = NOW()
+WHERE balance_reminder.sent_at <= NOW() - make_interval(days => $4)
+RETURNING sent_at;
+    "#,
+    )
+    .bind(user_id)
+    .bind(group_id)
+    .bind(currency)
+    .bind(days)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// Forgets a claimed reminder that couldn't be sent.
+pub async fn release_balance_reminder(
+    executor: impl PgExecutor<'_>,
+    user_id: i32,
+    group_id: i32,
+    currency: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM balance_reminder WHERE user_id = $1 AND group_id = $2 AND currency = $3;",
+    )
+    .bind(user_id)
+    .bind(group_id)
+    .bind(currency)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, Connection, PgConnection, PgExecutor, PgPool};
+
+/// The balance of every participant of the group's confirmed expenses created
+/// before the end of the period.
+static INSERT_SNAPSHOT: &str = r#"
+INSERT INTO period_close_balance (close_id, user_id, currency, balance)
+SELECT $1, s.user_id, e.currency, SUM(s.share)::BIGINT
+FROM account_share as s
+INNER JOIN expense as e ON e.id = s.expense_id
+WHERE e.group_id = $2
+AND e.created_at < $3
+AND e.status = 'confirmed'
+GROUP BY s.user_id, e.currency;
+"#;
+
+static GET_STATEMENT_LINES: &str = r#"
+SELECT
+    e.id as expense_id,
+    e.created_at,
+    e.name,
+    e.total,
+    e.currency,
+    e.is_payment,
+    e.paid_by,
+    u.name as paid_by_name,
+    s.share::BIGINT as share
+FROM expense as e
+INNER JOIN account_share as s ON s.expense_id = e.id
+INNER JOIN users as u ON u.id = e.paid_by
+WHERE e.group_id = $1
+AND s.user_id = $2
+AND ($3::TIMESTAMPTZ IS NULL OR e.created_at >= $3)
+AND e.created_at < $4
+AND e.status = 'confirmed'
+ORDER BY e.created_at, e.id;
+"#;
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct PeriodClose {
+    pub id: i32,
+    pub group_id: i32,
+    /// Expenses created before this are closed.
+    pub closed_until: chrono::DateTime<Utc>,
+    pub closed_by: i32,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct PeriodCloseBalance {
+    pub close_id: i32,
+    pub user_id: i32,
+    pub currency: String,
+    pub balance: i64,
+}
+
+pub struct InsertPeriodClose {
+    pub group_id: i32,
+    pub closed_until: chrono::DateTime<Utc>,
+    pub closed_by: i32,
+}
+
+/// An expense or settlement as one participant sees it.
+#[derive(FromRow, Serialize, Clone)]
+pub struct StatementLine {
+    pub expense_id: i32,
+    pub created_at: chrono::DateTime<Utc>,
+    pub name: String,
+    pub total: i32,
+    pub currency: String,
+    pub is_payment: bool,
+    pub paid_by: i32,
+    pub paid_by_name: String,
+    /// The participant's share, negative when the participant owes.
+    pub share: i64,
+}
+
+/// Latest first.
+pub async fn get_closes(pool: &PgPool, group_id: i32) -> Result<Vec<PeriodClose>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM period_close WHERE group_id = $1 ORDER BY closed_until DESC;")
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_close(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<PeriodClose>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM period_close WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// The close with the latest `closed_until` before `before`, or the latest of
+/// all without `before`.
+pub async fn get_latest_close(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    before: Option<chrono::DateTime<Utc>>,
+) -> Result<Option<PeriodClose>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT * FROM period_close
+WHERE group_id = $1
+AND ($2::TIMESTAMPTZ IS NULL OR closed_until < $2)
+ORDER BY closed_until DESC
+LIMIT 1;
+    "#,
+    )
+    .bind(group_id)
+    .bind(before)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Expenses of the group created before `until` that are still pending or
+/// disputed.
+pub async fn count_unconfirmed(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    until: chrono::DateTime<Utc>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+SELECT COUNT(*) FROM expense
+WHERE group_id = $1
+AND created_at < $2
+AND status <> 'confirmed';
+    "#,
+    )
+    .bind(group_id)
+    .bind(until)
+    .fetch_one(executor)
+    .await
+}
+
+/// Closes the period and snapshots the balances at its end.
+pub async fn insert_close(
+    conn: &mut PgConnection,
+    close: InsertPeriodClose,
+) -> Result<PeriodClose, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    let inserted: PeriodClose = sqlx::query_as(
+        r#"
+INSERT INTO period_close (group_id, closed_until, closed_by)
+VALUES ($1, $2, $3)
+RETURNING *;
+    "#,
+    )
+    .bind(close.group_id)
+    .bind(close.closed_until)
+    .bind(close.closed_by)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(INSERT_SNAPSHOT)
+        .bind(inserted.id)
+        .bind(close.group_id)
+        .bind(close.closed_until)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(inserted)
+}
+
+pub async fn get_balances(
+    executor: impl PgExecutor<'_>,
+    close_id: i32,
+) -> Result<Vec<PeriodCloseBalance>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT * FROM period_close_balance WHERE close_id = $1 ORDER BY user_id, currency;",
+    )
+    .bind(close_id)
+    .fetch_all(executor)
+    .await
+}
+
+/// The confirmed expenses and settlements of the group the user has a share
+/// in, created from `from`, or the beginning without it, until before `to`.
+pub async fn get_statement_lines(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    user_id: i32,
+    from: Option<chrono::DateTime<Utc>>,
+    to: chrono::DateTime<Utc>,
+) -> Result<Vec<StatementLine>, sqlx::Error> {
+    sqlx::query_as(GET_STATEMENT_LINES)
+        .bind(group_id)
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(executor)
+        .await
+}
+
+/// Replaces the snapshot of the close with the balances of the current
+/// expenses, for when they were corrected after the period was closed.
+pub async fn recompute_balances(
+    conn: &mut PgConnection,
+    close: &PeriodClose,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("DELETE FROM period_close_balance WHERE close_id = $1;")
+        .bind(close.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(INSERT_SNAPSHOT)
+        .bind(close.id)
+        .bind(close.group_id)
+        .bind(close.closed_until)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use chrono::Utc;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+#[derive(FromRow, Clone)]
+pub struct PushSubscription {
+    pub id: i32,
+    pub user_id: i32,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub device_name: Option<String>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertPushSubscription {
+    pub user_id: i32,
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub device_name: Option<String>,
+}
+
+pub async fn get_push_subscriptions(
+    pool: &PgPool,
+    user_id: i32,
+) -> Result<Vec<PushSubscription>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM push_subscription WHERE user_id = $1 ORDER BY id;")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Browsers reuse the endpoint when a subscription is renewed, so registering
+/// it again replaces the keys and moves it to the current user.
+pub async fn upsert_push_subscription(
+    executor: impl PgExecutor<'_>,
+    subscription: InsertPushSubscription,
+) -> Result<PushSubscription, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO push_subscription (user_id, endpoint, p256dh, auth, device_name)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT (endpoint) DO UPDATE
+SET
+    user_id = EXCLUDED.user_id,
+    p256dh = EXCLUDED.p256dh,
+    auth = EXCLUDED.auth,
+    device_name = EXCLUDED.device_name
+RETURNING *;
+    "#,
+    )
+    .bind(subscription.user_id)
+    .bind(subscription.endpoint)
+    .bind(subscription.p256dh)
+    .bind(subscription.auth)
+    .bind(subscription.device_name)
+    .fetch_one(executor)
+    .await
+}
+
+/// Returns whether a subscription was deleted.
+pub async fn delete_push_subscription(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM push_subscription WHERE id = $1 AND user_id = $2;")
+        .bind(id)
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_push_subscription_by_endpoint(
+    executor: impl PgExecutor<'_>,
+    endpoint: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM push_subscription WHERE endpoint = $1;")
+        .bind(endpoint)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_vapid_private_key(
+    executor: impl PgExecutor<'_>,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT private_key FROM vapid_key WHERE id = 1;")
+        .fetch_optional(executor)
+        .await
+}
+
+/// Stores the key unless one already exists, and returns the stored key.
+pub async fn insert_vapid_private_key(
+    executor: impl PgExecutor<'_>,
+    private_key: &str,
+) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+INSERT INTO vapid_key (id, private_key)
+VALUES (1, $1)
+ON CONFLICT (id) DO UPDATE SET id = vapid_key.id
+RETURNING private_key;
+    "#,
+    )
+    .bind(private_key)
+    .fetch_one(executor)
+    .await
+}
@@ -0,0 +1,217 @@
+use chrono::Utc;
+use sqlx::{prelude::FromRow, PgPool};
+
+/// One row per participant and expense with what that participant consumed,
+/// i.e. what they paid minus their share. Itemised expenses instead get one
+/// row per participant and line item, so that items count towards their own
+/// category. Settlements are left out since they move money around rather
+/// than spend it, as are expenses that aren't confirmed. Refunds count
+/// negatively towards the date and category of the expense they refund.
+macro_rules! consumption_cte {
+    () => {
+        r#"
+WITH consumption AS (
+    SELECT
+        e.id as expense_id,
+        COALESCE(o.category_id, e.category_id) as category_id,
+        COALESCE(o.created_at, e.created_at) as created_at,
+        s.user_id,
+        (CASE WHEN s.user_id = e.paid_by THEN e.total ELSE 0 END) - s.share as consumed
+    FROM expense as e
+    INNER JOIN account_share as s ON s.expense_id = e.id
+    LEFT JOIN expense as o ON o.id = e.refund_of
+    WHERE e.group_id = $1
+    AND e.currency = $2
+    AND COALESCE(o.created_at, e.created_at) >= $3
+    AND COALESCE(o.created_at, e.created_at) < $4
+    AND NOT e.is_payment
+    AND e.status = 'confirmed'
+    AND NOT EXISTS (SELECT 1 FROM expense_item as i WHERE i.expense_id = e.id)
+    AND ($5::INTEGER IS NULL OR s.user_id = $5)
+    UNION ALL
+    SELECT
+        e.id as expense_id,
+        COALESCE(i.category_id, e.category_id) as category_id,
+        e.created_at,
+        s.user_id,
+        s.consumed
+    FROM expense as e
+    INNER JOIN expense_item as i ON i.expense_id = e.id
+    INNER JOIN expense_item_share as s ON s.item_id = i.id
+    WHERE e.group_id = $1
+    AND e.currency = $2
+    AND e.created_at >= $3
+    AND e.created_at < $4
+    AND NOT e.is_payment
+    AND e.status = 'confirmed'
+    AND ($5::INTEGER IS NULL OR s.user_id = $5)
+)
+"#
+    };
+}
+
+static GET_CONSUMPTION_BY_CATEGORY: &str = concat!(
+    consumption_cte!(),
+    r#"
+SELECT
+    c.category_id,
+    ec.name as category_name,
+    ec.parent_id,
+    SUM(c.consumed)::BIGINT as amount
+FROM consumption as c
+LEFT JOIN expense_category as ec ON ec.id = c.category_id
+GROUP BY c.category_id, ec.name, ec.parent_id
+ORDER BY amount DESC;
+"#
+);
+
+static GET_CONSUMPTION_BY_USER: &str = concat!(
+    consumption_cte!(),
+    r#"
+SELECT user_id, SUM(consumed)::BIGINT as amount
+FROM consumption
+GROUP BY user_id
+ORDER BY user_id;
+"#
+);
+
+static GET_CONSUMPTION_BY_PERIOD: &str = concat!(
+    consumption_cte!(),
+    r#"
+SELECT date_trunc($6, created_at) as period_start, SUM(consumed)::BIGINT as amount
+FROM consumption
+GROUP BY period_start
+ORDER BY period_start;
+"#
+);
+
+static GET_PAID_BY_USER: &str = r#"
+SELECT e.paid_by as user_id, SUM(e.total)::BIGINT as amount
+FROM expense as e
+LEFT JOIN expense as o ON o.id = e.refund_of
+WHERE e.group_id = $1
+AND e.currency = $2
+AND COALESCE(o.created_at, e.created_at) >= $3
+AND COALESCE(o.created_at, e.created_at) < $4
+AND e.is_payment = $5
+GROUP BY e.paid_by
+ORDER BY e.paid_by;
+"#;
+
+static GET_SETTLEMENTS_RECEIVED_BY_USER: &str = r#"
+SELECT s.user_id, SUM(-s.share)::BIGINT as amount
+FROM expense as e
+INNER JOIN account_share as s ON s.expense_id = e.id
+WHERE e.group_id = $1
+AND e.currency = $2
+AND e.created_at >= $3
+AND e.created_at < $4
+AND e.is_payment
+AND s.share < 0
+GROUP BY s.user_id
+ORDER BY s.user_id;
+"#;
+
+pub struct ReportFilter {
+    pub group_id: i32,
+    pub currency: String,
+    pub from: chrono::DateTime<Utc>,
+    pub to: chrono::DateTime<Utc>,
+    /// Only count what this user consumed.
+    pub user_id: Option<i32>,
+}
+
+#[derive(FromRow)]
+pub struct CategoryTotal {
+    pub category_id: Option<i32>,
+    pub category_name: Option<String>,
+    pub parent_id: Option<i32>,
+    pub amount: i64,
+}
+
+#[derive(FromRow)]
+pub struct UserTotal {
+    pub user_id: i32,
+    pub amount: i64,
+}
+
+#[derive(FromRow)]
+pub struct PeriodTotal {
+    pub period_start: chrono::DateTime<Utc>,
+    pub amount: i64,
+}
+
+pub async fn get_consumption_by_category(
+    pool: &PgPool,
+    filter: &ReportFilter,
+) -> Result<Vec<CategoryTotal>, sqlx::Error> {
+    sqlx::query_as(GET_CONSUMPTION_BY_CATEGORY)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.user_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_consumption_by_user(
+    pool: &PgPool,
+    filter: &ReportFilter,
+) -> Result<Vec<UserTotal>, sqlx::Error> {
+    sqlx::query_as(GET_CONSUMPTION_BY_USER)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.user_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// `interval` is anything `date_trunc` accepts, e.g. `"week"` or `"month"`.
+pub async fn get_consumption_by_period(
+    pool: &PgPool,
+    filter: &ReportFilter,
+    interval: &str,
+) -> Result<Vec<PeriodTotal>, sqlx::Error> {
+    sqlx::query_as(GET_CONSUMPTION_BY_PERIOD)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.user_id)
+        .bind(interval)
+        .fetch_all(pool)
+        .await
+}
+
+/// What each user has paid out of pocket, either for expenses or, with
+/// `settlements` set, to settle up with others.
+pub async fn get_paid_by_user(
+    pool: &PgPool,
+    filter: &ReportFilter,
+    settlements: bool,
+) -> Result<Vec<UserTotal>, sqlx::Error> {
+    sqlx::query_as(GET_PAID_BY_USER)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(settlements)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_settlements_received_by_user(
+    pool: &PgPool,
+    filter: &ReportFilter,
+) -> Result<Vec<UserTotal>, sqlx::Error> {
+    sqlx::query_as(GET_SETTLEMENTS_RECEIVED_BY_USER)
+        .bind(filter.group_id)
+        .bind(&filter.currency)
+        .bind(filter.from)
+        .bind(filter.to)
+        .fetch_all(pool)
+        .await
+}
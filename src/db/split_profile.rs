@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, Connection, PgConnection, PgExecutor, PgPool};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct SplitProfile {
+    pub id: i32,
+    pub group_id: i32,
+    pub name: String,
+    /// Applied to new expenses of this category or its sub-categories.
+    pub category_id: Option<i32>,
+    /// Applied to new expenses no category profile matches.
+    pub is_default: bool,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+#[derive(FromRow, Serialize, Clone, Copy)]
+pub struct SplitProfileWeight {
+    pub profile_id: i32,
+    pub user_id: i32,
+    pub weight: i32,
+}
+
+pub type SplitProfileWithWeights = (SplitProfile, Vec<SplitProfileWeight>);
+
+pub struct InsertSplitProfile {
+    pub group_id: i32,
+    pub name: String,
+    pub category_id: Option<i32>,
+    pub is_default: bool,
+    pub weights: Vec<InsertSplitProfileWeight>,
+}
+
+pub struct InsertSplitProfileWeight {
+    pub user_id: i32,
+    pub weight: i32,
+}
+
+pub async fn get_profiles(
+    pool: &PgPool,
+    group_id: i32,
+) -> Result<Vec<SplitProfileWithWeights>, sqlx::Error> {
+    let profiles: Vec<SplitProfile> =
+        sqlx::query_as("SELECT * FROM split_profile WHERE group_id = $1 ORDER BY id;")
+            .bind(group_id)
+            .fetch_all(pool)
+            .await?;
+
+    with_weights(pool, profiles).await
+}
+
+pub async fn get_profile(
+    pool: &PgPool,
+    id: i32,
+) -> Result<Option<SplitProfileWithWeights>, sqlx::Error> {
+    let profile: Option<SplitProfile> =
+        sqlx::query_as("SELECT * FROM split_profile WHERE id = $1;")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(with_weights(pool, profile.into_iter().collect())
+        .await?
+        .pop())
+}
+
+/// The profile to split a new expense of the group by when it comes without
+/// shares. The profile of the category wins over that of its parent, which
+/// wins over the group's default.
+pub async fn get_applicable_profile(
+    pool: &PgPool,
+    group_id: i32,
+    category_id: Option<i32>,
+) -> Result<Option<SplitProfileWithWeights>, sqlx::Error> {
+    let profile: Option<SplitProfile> = sqlx::query_as(
+        r#"
+SELECT p.*
+FROM split_profile as p
+LEFT JOIN expense_category as ec ON ec.id = $2
+WHERE p.group_id = $1
+AND (p.is_default OR p.category_id = ec.id OR p.category_id = ec.parent_id)
+ORDER BY
+    CASE
+        WHEN p.category_id = ec.id THEN 0
+        WHEN p.category_id = ec.parent_id THEN 1
+        ELSE 2
+    END
+LIMIT 1;
+    "#,
+    )
+    .bind(group_id)
+    .bind(category_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(with_weights(pool, profile.into_iter().collect())
+        .await?
+        .pop())
+}
+
+async fn with_weights(
+    pool: &PgPool,
+    profiles: Vec<SplitProfile>,
+) -> Result<Vec<SplitProfileWithWeights>, sqlx::Error> {
+    let profile_ids: Vec<i32> = profiles.iter().map(|profile| profile.id).collect();
+    let weights: Vec<SplitProfileWeight> = sqlx::query_as(
+        "SELECT * FROM split_profile_weight WHERE profile_id = ANY($1) ORDER BY user_id;",
+    )
+    .bind(profile_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut weights_by_profile: HashMap<i32, Vec<SplitProfileWeight>> = HashMap::new();
+    for weight in weights {
+        weights_by_profile
+            .entry(weight.profile_id)
+            .or_default()
+            .push(weight);
+    }
+
+    Ok(profiles
+        .into_iter()
+        .map(|profile| {
+            let weights = weights_by_profile.remove(&profile.id).unwrap_or_default();
+            (profile, weights)
+        })
+        .collect())
+}
+
+/// A new default profile takes over from the group's previous one.
+pub async fn insert_profile(
+    conn: &mut PgConnection,
+    profile: InsertSplitProfile,
+) -> Result<i32, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    if profile.is_default {
+        clear_default(&mut tx, profile.group_id).await?;
+    }
+    let id: i32 = sqlx::query_scalar(
+        r#"
+INSERT INTO split_profile (group_id, name, category_id, is_default)
+VALUES ($1, $2, $3, $4)
+RETURNING id;
+    "#,
+    )
+    .bind(profile.group_id)
+    .bind(profile.name)
+    .bind(profile.category_id)
+    .bind(profile.is_default)
+    .fetch_one(&mut *tx)
+    .await?;
+    insert_weights(&mut tx, id, profile.weights).await?;
+
+    tx.commit().await?;
+
+    Ok(id)
+}
+
+/// Replaces the profile and its weights. The group of a profile can't be
+/// changed.
+pub async fn update_profile(
+    conn: &mut PgConnection,
+    id: i32,
+    profile: InsertSplitProfile,
+) -> Result<(), sqlx::Error> {
+    let mut tx = conn.begin().await?;
+
+    if profile.is_default {
+        clear_default(&mut tx, profile.group_id).await?;
+    }
+    sqlx::query(
+        r#"
+UPDATE split_profile
+SET
+    name = $2,
+    category_id = $3,
+    is_default = $4
+WHERE id = $1;
+    "#,
+    )
+    .bind(id)
+    .bind(profile.name)
+    .bind(profile.category_id)
+    .bind(profile.is_default)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM split_profile_weight WHERE profile_id = $1;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    insert_weights(&mut tx, id, profile.weights).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn clear_default(conn: &mut PgConnection, group_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE split_profile SET is_default = FALSE WHERE group_id = $1 AND is_default;")
+        .bind(group_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn insert_weights(
+    conn: &mut PgConnection,
+    profile_id: i32,
+    weights: Vec<InsertSplitProfileWeight>,
+) -> Result<(), sqlx::Error> {
+    for weight in weights {
+        sqlx::query(
+            "INSERT INTO split_profile_weight (profile_id, user_id, weight) VALUES ($1, $2, $3);",
+        )
+        .bind(profile_id)
+        .bind(weight.user_id)
+        .bind(weight.weight)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_profile(executor: impl PgExecutor<'_>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM split_profile WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
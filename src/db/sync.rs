@@ -0,0 +1,221 @@
+use chrono::Utc;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use super::expense_category::ExpenseCategory;
+
+/// Entries in `sync_change`, see the `sync` migration.
+pub const EXPENSE_ENTITY: &str = "expense";
+pub const CATEGORY_ENTITY: &str = "category";
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct SyncChange {
+    pub entity: String,
+    pub entity_id: i32,
+    /// Id of the transaction that made the change.
+    pub version: i64,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct SyncMutation {
+    pub client_id: Uuid,
+    pub user_id: i32,
+    pub expense_id: Option<i32>,
+}
+
+/// Every transaction older than the returned cursor has finished, so their
+/// changes are visible to anyone reading from now on. Changes of transactions
+/// that are still running get a newer id and are picked up by the next sync.
+pub async fn get_cursor(executor: impl PgExecutor<'_>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT pg_snapshot_xmin(pg_current_snapshot())::TEXT::BIGINT;")
+        .fetch_one(executor)
+        .await
+}
+
+/// Changes to expenses and categories visible to `user_id` made since
+/// `cursor`, including those moved out of the user's groups.
+pub async fn get_changes_since(
+    pool: &PgPool,
+    user_id: i32,
+    cursor: i64,
+) -> Result<Vec<SyncChange>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT entity, entity_id, tx_id::TEXT::BIGINT as version
+FROM sync_change
+WHERE tx_id >= $2::TEXT::XID8
+AND (
+    group_id IS NULL
+    OR group_id IN (SELECT group_id FROM group_member WHERE user_id = $1)
+    OR previous_group_ids && ARRAY(SELECT group_id FROM group_member WHERE user_id = $1)
+)
+ORDER BY tx_id, entity, entity_id;
+    "#,
+    )
+    .bind(user_id)
+    .bind(cursor)
+    .fetch_all(pool)
+    .await
+}
+
+/// Current versions of the given entities. Rows that haven't changed since
+/// syncing was introduced have none.
+pub async fn get_versions(
+    executor: impl PgExecutor<'_>,
+    entity: &str,
+    entity_ids: &[i32],
+) -> Result<Vec<SyncChange>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT entity, entity_id, tx_id::TEXT::BIGINT as version
+FROM sync_change
+WHERE entity = $1
+AND entity_id = ANY($2);
+    "#,
+    )
+    .bind(entity)
+    .bind(entity_ids)
+    .fetch_all(executor)
+    .await
+}
+
+/// Locks the expense for the rest of the transaction and returns its current
+/// version, `None` if the expense doesn't exist.
+pub async fn lock_expense_version(
+    executor: impl PgExecutor<'_>,
+    expense_id: i32,
+) -> Result<Option<Option<i64>>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+SELECT sc.tx_id::TEXT::BIGINT
+FROM expense as e
+LEFT JOIN sync_change as sc ON sc.entity = 'expense' AND sc.entity_id = e.id
+WHERE e.id = $1
+FOR UPDATE OF e;
+    "#,
+    )
+    .bind(expense_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Categories visible to `user_id`: the global ones and those of the user's
+/// groups.
+pub async fn get_visible_categories(
+    pool: &PgPool,
+    user_id: i32,
+) -> Result<Vec<ExpenseCategory>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT * FROM expense_category
+WHERE group_id IS NULL
+OR group_id IN (SELECT group_id FROM group_member WHERE user_id = $1)
+ORDER BY id;
+    "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_categories_by_id(
+    pool: &PgPool,
+    category_ids: &[i32],
+) -> Result<Vec<ExpenseCategory>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM expense_category WHERE id = ANY($1) ORDER BY id;")
+        .bind(category_ids)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_mutation(
+    executor: impl PgExecutor<'_>,
+    client_id: Uuid,
+) -> Result<Option<SyncMutation>, sqlx::Error> {
+    sqlx::query_as("SELECT client_id, user_id, expense_id FROM sync_mutation WHERE client_id = $1;")
+        .bind(client_id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Records an applied mutation, returns `false` if a mutation with the same
+/// client id has been recorded in the meantime.
+pub async fn insert_mutation(
+    executor: impl PgExecutor<'_>,
+    mutation: SyncMutation,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+INSERT INTO sync_mutation (client_id, user_id, expense_id)
+VALUES ($1, $2, $3)
+ON CONFLICT (client_id) DO NOTHING;
+    "#,
+    )
+    .bind(mutation.client_id)
+    .bind(mutation.user_id)
+    .bind(mutation.expense_id)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Deletes tombstones of expenses and categories deleted before `before`,
+/// returning how many were deleted. The horizon is moved past them, so that
+/// clients that haven't synced since then sync from scratch.
+pub async fn delete_tombstones(
+    executor: impl PgExecutor<'_>,
+    before: chrono::DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let deleted: i64 = sqlx::query_scalar(
+        r#"
+WITH deleted AS (
+    DELETE FROM sync_change as sc
+    WHERE sc.changed_at < $1
+    AND (
+        (sc.entity = $2 AND NOT EXISTS (SELECT 1 FROM expense WHERE id = sc.entity_id))
+        OR (sc.entity = $3 AND NOT EXISTS (SELECT 1 FROM expense_category WHERE id = sc.entity_id))
+    )
+    RETURNING sc.tx_id
+), horizon AS (
+    INSERT INTO sync_horizon (tx_id)
+    SELECT MAX(tx_id) FROM deleted
+    HAVING COUNT(*) > 0
+    ON CONFLICT (id) DO UPDATE
+    SET
+        tx_id = GREATEST(sync_horizon.tx_id, EXCLUDED.tx_id),
+        purged_at = EXCLUDED.purged_at
+)
+SELECT COUNT(*) FROM deleted;
+    "#,
+    )
+    .bind(before)
+    .bind(EXPENSE_ENTITY)
+    .bind(CATEGORY_ENTITY)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(deleted as u64)
+}
+
+/// The version of the latest change whose tombstone was purged, if any.
+/// Cursors up to it may have missed deletions.
+pub async fn get_horizon(executor: impl PgExecutor<'_>) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT tx_id::TEXT::BIGINT FROM sync_horizon;")
+        .fetch_optional(executor)
+        .await
+}
+
+/// Deletes mutations recorded before `before`, returning how many were
+/// deleted. A batch retried after that is applied again.
+pub async fn delete_mutations(
+    executor: impl PgExecutor<'_>,
+    before: chrono::DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sync_mutation WHERE created_at < $1;")
+        .bind(before)
+        .execute(executor)
+        .await?;
+
+    Ok(result.rows_affected())
+}
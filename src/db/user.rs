@@ -1,12 +1,20 @@
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
 #[derive(Debug, sqlx::FromRow, Serialize, Deserialize, Clone)]
 pub struct User {
     pub id: i32,
     pub name: String,
-    pub email: String,
+    /// Not set for guests.
+    pub email: Option<String>,
     pub phone_number: Option<String>,
+    pub is_guest: bool,
+    /// Disabled users keep their expenses and balances but can't sign in.
+    pub disabled_at: Option<chrono::DateTime<Utc>>,
+    /// Can sign in without being in `ALLOWED_EMAILS`, set for users created by
+    /// an admin.
+    pub sign_in_allowed: bool,
 }
 
 #[derive(Debug)]
@@ -16,11 +24,20 @@ pub struct UpsertUser {
     pub email: String,
 }
 
+/// A user created ahead of their first sign in, which links the account. They
+/// are allowed to sign in without being in `ALLOWED_EMAILS`.
+#[derive(Debug)]
+pub struct InsertUser {
+    pub name: String,
+    pub email: String,
+}
+
 #[derive(Debug)]
 pub struct PatchUser {
     pub id: i32,
     pub email: Option<String>,
-    pub phone_number: Option<String>,
+    /// `Some(None)` removes the phone number.
+    pub phone_number: Option<Option<String>>,
 }
 
 pub async fn get_users(pool: &PgPool) -> Result<Vec<User>, sqlx::Error> {
@@ -31,6 +48,13 @@ pub async fn get_users(pool: &PgPool) -> Result<Vec<User>, sqlx::Error> {
     Ok(users)
 }
 
+pub async fn get_user_by_id(pool: &PgPool, id: i32) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
 pub async fn get_user_by_email(pool: &PgPool, email: &str) -> Result<User, sqlx::Error> {
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1;")
         .bind(email)
@@ -40,7 +64,36 @@ pub async fn get_user_by_email(pool: &PgPool, email: &str) -> Result<User, sqlx:
     Ok(user)
 }
 
+pub async fn insert_user(
+    executor: impl PgExecutor<'_>,
+    user: InsertUser,
+) -> Result<User, sqlx::Error> {
+    sqlx::query_as(
+        "INSERT INTO users (name, email, sign_in_allowed) VALUES ($1, $2, TRUE) RETURNING *;",
+    )
+    .bind(user.name)
+    .bind(user.email)
+    .fetch_one(executor)
+    .await
+}
+
+/// Signs in a user, first linking a user created for the same email that has
+/// not signed in yet.
 pub async fn upsert_user(pool: &PgPool, user: UpsertUser) -> Result<User, sqlx::Error> {
+    sqlx::query(
+        r#"
+UPDATE users
+SET microsoft_id = $1
+WHERE email = $2
+AND microsoft_id IS NULL
+AND NOT is_guest;
+    "#,
+    )
+    .bind(&user.microsoft_id)
+    .bind(&user.email)
+    .execute(pool)
+    .await?;
+
     let user = sqlx::query_as::<_, User>(
         "
         INSERT INTO users (microsoft_id, name, email) 
@@ -65,16 +118,37 @@ pub async fn patch_user(pool: &PgPool, user: PatchUser) -> Result<User, sqlx::Er
         UPDATE users
         SET
             email = COALESCE($1, email),
-            phone_number = COALESCE($2, phone_number)
-        WHERE id = $3
+            phone_number = CASE WHEN $2 THEN $3 ELSE phone_number END
+        WHERE id = $4
         RETURNING *;
     ",
     )
     .bind(user.email)
-    .bind(user.phone_number)
+    .bind(user.phone_number.is_some())
+    .bind(user.phone_number.flatten())
     .bind(user.id)
     .fetch_one(pool)
     .await?;
 
     Ok(user)
 }
+
+/// Keeps the time a user was first disabled when disabling them again.
+pub async fn set_disabled(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    disabled: bool,
+) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE users
+SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) END
+WHERE id = $1
+RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(disabled)
+    .fetch_optional(executor)
+    .await
+}
@@ -0,0 +1,266 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{prelude::FromRow, PgExecutor, PgPool};
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct Webhook {
+    pub id: i32,
+    pub group_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    pub active: bool,
+    pub created_by: i32,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+pub struct InsertWebhook {
+    pub group_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    pub active: bool,
+    pub created_by: i32,
+}
+
+pub struct UpdateWebhook {
+    pub url: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    pub active: bool,
+}
+
+#[derive(FromRow, Serialize, Clone)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    /// One of `pending`, `delivered` or `failed`.
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: chrono::DateTime<Utc>,
+    pub last_attempt_at: Option<chrono::DateTime<Utc>>,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: chrono::DateTime<Utc>,
+}
+
+/// A delivery claimed for sending, together with where to send it.
+#[derive(FromRow, Clone)]
+pub struct DueDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    /// Including the one about to be made.
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+pub struct DeliveryResult {
+    pub status: String,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    /// When to retry, `None` once the delivery is done.
+    pub next_attempt_at: Option<chrono::DateTime<Utc>>,
+}
+
+pub async fn get_webhooks(pool: &PgPool, group_id: i32) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webhook WHERE group_id = $1 ORDER BY id;")
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn get_webhook(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+) -> Result<Option<Webhook>, sqlx::Error> {
+    sqlx::query_as("SELECT * FROM webhook WHERE id = $1;")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Active webhooks of a group subscribed to `event`.
+pub async fn get_subscribed_webhooks(
+    executor: impl PgExecutor<'_>,
+    group_id: i32,
+    event: &str,
+) -> Result<Vec<Webhook>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT * FROM webhook
+WHERE group_id = $1
+AND active
+AND $2 = ANY(events)
+ORDER BY id;
+    "#,
+    )
+    .bind(group_id)
+    .bind(event)
+    .fetch_all(executor)
+    .await
+}
+
+pub async fn insert_webhook(
+    executor: impl PgExecutor<'_>,
+    webhook: InsertWebhook,
+) -> Result<Webhook, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+INSERT INTO webhook (group_id, url, secret, events, description, active, created_by)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+RETURNING *;
+    "#,
+    )
+    .bind(webhook.group_id)
+    .bind(webhook.url)
+    .bind(webhook.secret)
+    .bind(webhook.events)
+    .bind(webhook.description)
+    .bind(webhook.active)
+    .bind(webhook.created_by)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn update_webhook(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    webhook: UpdateWebhook,
+) -> Result<Webhook, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE webhook
+SET
+    url = $2,
+    events = $3,
+    description = $4,
+    active = $5
+WHERE id = $1
+RETURNING *;
+    "#,
+    )
+    .bind(id)
+    .bind(webhook.url)
+    .bind(webhook.events)
+    .bind(webhook.description)
+    .bind(webhook.active)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn delete_webhook(executor: impl PgExecutor<'_>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM webhook WHERE id = $1;")
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn insert_delivery(
+    executor: impl PgExecutor<'_>,
+    webhook_id: i32,
+    event: &str,
+    payload: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO webhook_delivery (webhook_id, event, payload) VALUES ($1, $2, $3);")
+        .bind(webhook_id)
+        .bind(event)
+        .bind(payload)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Most recent deliveries first.
+pub async fn get_deliveries(
+    pool: &PgPool,
+    webhook_id: i32,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+SELECT * FROM webhook_delivery
+WHERE webhook_id = $1
+ORDER BY created_at DESC, id DESC
+LIMIT $2;
+    "#,
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Claims up to `limit` pending deliveries that are due by counting the
+/// attempt and pushing their next attempt `lease_seconds` into the future, so
+/// other instances leave them alone while they are being sent. A delivery
+/// whose result is never recorded is retried once the lease runs out.
+pub async fn claim_due_deliveries(
+    pool: &PgPool,
+    limit: i64,
+    lease_seconds: i64,
+) -> Result<Vec<DueDelivery>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+UPDATE webhook_delivery as d
+SET
+    attempts = d.attempts + 1,
+    last_attempt_at = NOW(),
+    next_attempt_at = NOW() + $2 * INTERVAL '1 second'
+FROM webhook as w
+WHERE w.id = d.webhook_id
+AND d.id IN (
+    SELECT pending.id
+    FROM webhook_delivery as pending
+    INNER JOIN webhook as active ON active.id = pending.webhook_id
+    WHERE pending.status = 'pending'
+    AND pending.next_attempt_at <= NOW()
+    AND active.active
+    ORDER BY pending.next_attempt_at
+    LIMIT $1
+    FOR UPDATE OF pending SKIP LOCKED
+)
+RETURNING d.id, d.webhook_id, d.event, d.payload, d.attempts, w.url, w.secret;
+    "#,
+    )
+    .bind(limit)
+    .bind(lease_seconds)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn record_delivery_result(
+    executor: impl PgExecutor<'_>,
+    id: i32,
+    result: DeliveryResult,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+UPDATE webhook_delivery
+SET
+    status = $2,
+    response_status = $3,
+    last_error = $4,
+    next_attempt_at = COALESCE($5, next_attempt_at)
+WHERE id = $1;
+    "#,
+    )
+    .bind(id)
+    .bind(result.status)
+    .bind(result.response_status)
+    .bind(result.last_error)
+    .bind(result.next_attempt_at)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
@@ -1,10 +1,5 @@
 use dotenvy::dotenv;
-use server::application::App;
-
-mod api;
-mod db;
-mod server;
-mod service;
+use jostrid::server::application::App;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
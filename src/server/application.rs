@@ -20,18 +20,45 @@ use crate::{
     api::{
         auth::{self},
         balance::get_balance_api,
+        budget::get_budget_api,
+        change::get_change_api,
         expense::get_expense_api,
         expense_category::get_expense_category_api,
+        export::get_export_api,
+        group::get_group_api,
+        guest::get_guest_api,
+        invitation::get_invitation_api,
+        split_profile::get_split_profile_api,
+        period_close::get_period_close_api,
+        import::get_import_api,
+        report::get_report_api,
+        settlement::get_settlement_api,
+        sync::get_sync_api,
+        webhook::get_webhook_api,
         me::get_me_api,
+        openapi::get_openapi_api,
+        push::get_push_api,
         user::get_user_api, image::get_image_api,
     },
-    service::auth_service::MicrosoftClaims,
+    service::{
+        auth_service::MicrosoftClaims,
+        change_service::ChangeService,
+        event_service::EventService,
+        notification_service::{Mailer, NotificationService},
+        push_service::PushService,
+        web_push::VapidKey,
+        webhook_service::WebhookService,
+    },
 };
 
 #[derive(Clone)]
 pub struct App {
     pub db: Pool<Postgres>,
     pub oauth_client: BasicClient,
+    pub events: EventService,
+    pub mailer: Option<Mailer>,
+    pub vapid_key: VapidKey,
+    pub changes: ChangeService,
 }
 
 impl App {
@@ -71,7 +98,23 @@ impl App {
                     .unwrap(),
                 );
 
-        Ok(App { db, oauth_client })
+        let events = EventService::new(256);
+        let mailer = Mailer::from_env()?;
+        NotificationService::new(db.clone(), mailer.clone()).spawn(&events);
+        let vapid_key = PushService::load_vapid_key(&db).await?;
+        PushService::new(db.clone(), vapid_key.clone()).spawn(&events);
+        WebhookService::new(db.clone()).spawn(&events);
+        let changes = ChangeService::new(256);
+        changes.spawn(db.clone());
+
+        Ok(App {
+            db,
+            oauth_client,
+            events,
+            mailer,
+            vapid_key,
+            changes,
+        })
     }
 
     pub async fn serve(self) -> Result<(), Box<dyn std::error::Error>> {
@@ -114,9 +157,24 @@ impl App {
             .nest("/api/user", get_user_api())
             .nest("/api/me", get_me_api())
             .nest("/api/image", get_image_api())
+            .nest("/api/group", get_group_api())
+            .nest("/api/report", get_report_api())
+            .nest("/api/budget", get_budget_api())
+            .nest("/api/export", get_export_api())
+            .nest("/api/import", get_import_api())
+            .nest("/api/settlement", get_settlement_api())
+            .nest("/api/push", get_push_api())
+            .nest("/api/changes", get_change_api())
+            .nest("/api/sync", get_sync_api())
+            .nest("/api/webhook", get_webhook_api())
+            .nest("/api/guest", get_guest_api())
+            .nest("/api/invitation", get_invitation_api())
+            .nest("/api/split_profile", get_split_profile_api())
+            .nest("/api/period_close", get_period_close_api())
             .layer(jwt_auth.into_layer())
             .route("/api", get(hello_world))
             .nest("/api/oauth", auth::router())
+            .merge(get_openapi_api())
             .with_state(self)
             .layer(session_layer)
             .layer(CookieManagerLayer::new())
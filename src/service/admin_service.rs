@@ -0,0 +1,484 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    Pool, Postgres,
+};
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    backup::TableRows,
+    balance::UnbalancedExpense,
+    group::Group,
+    invitation::Invitation,
+    user::{InsertUser, User},
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Operates a deployment on behalf of whoever runs it, so nothing here checks
+/// memberships. Only meant for the admin binary, never for the API.
+#[derive(Debug, Clone)]
+pub struct AdminService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error(transparent)]
+    Migrate(MigrateError),
+
+    #[error(transparent)]
+    Json(serde_json::Error),
+
+    #[error("{0} not found")]
+    NotFound(String),
+
+    #[error("Invalid: {0}")]
+    Invalid(String),
+}
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Everything in the database, restorable into a database at the same
+/// migration.
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    /// The latest migration applied to the database the backup was taken
+    /// from.
+    pub version: i64,
+    pub created_at: chrono::DateTime<Utc>,
+    /// Ordered so that a table comes after the tables it references.
+    pub tables: Vec<BackupTable>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BackupTable {
+    pub name: String,
+    /// Objects keyed by column.
+    pub rows: Vec<serde_json::Value>,
+}
+
+pub struct Recomputed {
+    /// How many period closes got their balances snapshotted again.
+    pub closes: usize,
+    /// Expenses whose shares don't add up to zero, which throws off the
+    /// balances of everyone involved. They have to be fixed by hand.
+    pub unbalanced: Vec<UnbalancedExpense>,
+}
+
+#[derive(Default)]
+pub struct Purged {
+    pub categories: u64,
+    pub invitations: u64,
+    pub tombstones: u64,
+    pub mutations: u64,
+}
+
+impl AdminService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Every migration known to this build, oldest first.
+    pub async fn get_migrations(&self) -> Result<Vec<MigrationStatus>, AdminError> {
+        let applied = db::migration::get_applied_versions(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)?;
+
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: applied.contains(&migration.version),
+            })
+            .collect())
+    }
+
+    /// Applies the pending migrations, returning their versions.
+    pub async fn run_migrations(&self) -> Result<Vec<i64>, AdminError> {
+        let pending: Vec<i64> = self
+            .get_migrations()
+            .await?
+            .into_iter()
+            .filter(|migration| !migration.applied)
+            .map(|migration| migration.version)
+            .collect();
+        MIGRATOR.run(&self.db).await.map_err(AdminError::Migrate)?;
+        event!(Level::INFO, count = pending.len(), "Ran migrations");
+
+        Ok(pending)
+    }
+
+    /// Reverts the migrations applied after `target`, or only the latest one
+    /// without a target, returning their versions latest first.
+    pub async fn revert_migrations(&self, target: Option<i64>) -> Result<Vec<i64>, AdminError> {
+        let applied = db::migration::get_applied_versions(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        let target = match target {
+            Some(target) => target,
+            None => match applied.as_slice() {
+                [] => {
+                    return Err(AdminError::Invalid(
+                        "no migration has been applied".to_string(),
+                    ))
+                }
+                [.., previous, _] => *previous,
+                [_] => 0,
+            },
+        };
+        let reverted: Vec<i64> = applied
+            .into_iter()
+            .rev()
+            .filter(|version| *version > target)
+            .collect();
+
+        MIGRATOR
+            .undo(&self.db, target)
+            .await
+            .map_err(AdminError::Migrate)?;
+        event!(
+            Level::INFO,
+            target,
+            count = reverted.len(),
+            "Reverted migrations"
+        );
+
+        Ok(reverted)
+    }
+
+    pub async fn get_users(&self) -> Result<Vec<User>, AdminError> {
+        let mut users = db::user::get_users(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        users.sort_by_key(|user| user.id);
+
+        Ok(users)
+    }
+
+    /// Creates a user ahead of their first sign in, which links the account
+    /// by email, and adds them to `group_ids`.
+    pub async fn create_user(
+        &self,
+        name: &str,
+        email: &str,
+        group_ids: &[i32],
+    ) -> Result<User, AdminError> {
+        let name = name.trim();
+        let email = email.trim();
+        if name.is_empty() {
+            return Err(AdminError::Invalid("name can't be empty".to_string()));
+        }
+        if !email.contains('@') {
+            return Err(AdminError::Invalid(format!(
+                "'{}' is not an email address",
+                email
+            )));
+        }
+        match db::user::get_user_by_email(&self.db, email).await {
+            Ok(user) => {
+                return Err(AdminError::Invalid(format!(
+                    "user {} already has the email {}",
+                    user.id, email
+                )))
+            }
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(err) => return Err(AdminError::Sqlx(err)),
+        }
+        for group_id in group_ids {
+            self.get_group(*group_id).await?;
+        }
+
+        let mut tx = self.db.begin().await.map_err(AdminError::Sqlx)?;
+        let user = db::user::insert_user(
+            &mut *tx,
+            InsertUser {
+                name: name.to_string(),
+                email: email.to_string(),
+            },
+        )
+        .await
+        .map_err(AdminError::Sqlx)?;
+        for group_id in group_ids {
+            db::group::add_member(&mut *tx, *group_id, user.id)
+                .await
+                .map_err(AdminError::Sqlx)?;
+        }
+        tx.commit().await.map_err(AdminError::Sqlx)?;
+        event!(Level::INFO, user_id = user.id, "Created user");
+
+        Ok(user)
+    }
+
+    pub async fn get_user(&self, user_id: i32) -> Result<User, AdminError> {
+        db::user::get_user_by_id(&self.db, user_id)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .ok_or(AdminError::NotFound(format!("User {}", user_id)))
+    }
+
+    /// Disabled users keep their expenses and balances, but can't sign in
+    /// or use the API.
+    pub async fn set_disabled(&self, user_id: i32, disabled: bool) -> Result<User, AdminError> {
+        let user = db::user::set_disabled(&self.db, user_id, disabled)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .ok_or(AdminError::NotFound(format!("User {}", user_id)))?;
+        event!(
+            Level::INFO,
+            user_id,
+            disabled,
+            "Changed whether user is disabled"
+        );
+
+        Ok(user)
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<Group>, AdminError> {
+        db::group::get_groups(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)
+    }
+
+    pub async fn get_group(&self, group_id: i32) -> Result<Group, AdminError> {
+        db::group::get_group(&self.db, group_id)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .ok_or(AdminError::NotFound(format!("Group {}", group_id)))
+    }
+
+    pub async fn get_members(&self, group_id: i32) -> Result<Vec<User>, AdminError> {
+        self.get_group(group_id).await?;
+
+        db::group::get_members(&self.db, group_id)
+            .await
+            .map_err(AdminError::Sqlx)
+    }
+
+    pub async fn create_group(&self, name: &str) -> Result<Group, AdminError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AdminError::Invalid("name can't be empty".to_string()));
+        }
+
+        let group = db::group::insert_group(&self.db, name)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        event!(Level::INFO, group_id = group.id, "Created group");
+
+        Ok(group)
+    }
+
+    /// Guests belong to the group they were added to and can't join others.
+    pub async fn add_member(&self, group_id: i32, user_id: i32) -> Result<(), AdminError> {
+        self.get_group(group_id).await?;
+        let user = self.get_user(user_id).await?;
+        if user.is_guest {
+            return Err(AdminError::Invalid(format!("user {} is a guest", user_id)));
+        }
+
+        db::group::add_member(&self.db, group_id, user_id)
+            .await
+            .map_err(AdminError::Sqlx)
+    }
+
+    /// The user keeps their shares of the group's expenses.
+    pub async fn remove_member(&self, group_id: i32, user_id: i32) -> Result<(), AdminError> {
+        let removed = db::group::remove_member(&self.db, group_id, user_id)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        if !removed {
+            return Err(AdminError::NotFound(format!(
+                "Member {} of group {}",
+                user_id, group_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_invitations(&self, group_id: i32) -> Result<Vec<Invitation>, AdminError> {
+        self.get_group(group_id).await?;
+
+        db::invitation::get_pending_invitations(&self.db, group_id)
+            .await
+            .map_err(AdminError::Sqlx)
+    }
+
+    pub async fn revoke_invitation(&self, id: i32) -> Result<(), AdminError> {
+        db::invitation::get_invitation(&self.db, id)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .ok_or(AdminError::NotFound(format!("Invitation {}", id)))?;
+
+        db::invitation::delete_invitation(&self.db, id)
+            .await
+            .map_err(AdminError::Sqlx)
+    }
+
+    /// Snapshots the balances of every period close of the group, or of all
+    /// groups, again from the current expenses, e.g. after correcting shares
+    /// by hand. Also finds expenses whose shares are off, which would end up
+    /// in the snapshots as well.
+    pub async fn recompute_balances(
+        &self,
+        group_id: Option<i32>,
+    ) -> Result<Recomputed, AdminError> {
+        let group_ids = match group_id {
+            Some(group_id) => vec![self.get_group(group_id).await?.id],
+            None => self
+                .get_groups()
+                .await?
+                .into_iter()
+                .map(|group| group.id)
+                .collect(),
+        };
+
+        let mut tx = self.db.begin().await.map_err(AdminError::Sqlx)?;
+        let mut closes = 0;
+        for group_id in group_ids {
+            for close in db::period_close::get_closes(&self.db, group_id)
+                .await
+                .map_err(AdminError::Sqlx)?
+            {
+                db::period_close::recompute_balances(&mut tx, &close)
+                    .await
+                    .map_err(AdminError::Sqlx)?;
+                closes += 1;
+            }
+        }
+        tx.commit().await.map_err(AdminError::Sqlx)?;
+
+        let unbalanced = db::balance::get_unbalanced_expenses(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .into_iter()
+            .filter(|expense| group_id.is_none_or(|group_id| expense.group_id == group_id))
+            .collect();
+        event!(Level::INFO, closes, "Recomputed period close balances");
+
+        Ok(Recomputed { closes, unbalanced })
+    }
+
+    pub async fn export_backup(&self) -> Result<Backup, AdminError> {
+        let version = self.get_schema_version().await?;
+        let mut conn = self.db.acquire().await.map_err(AdminError::Sqlx)?;
+        let tables = db::backup::export_tables(&mut conn)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .into_iter()
+            .map(|table| {
+                Ok(BackupTable {
+                    name: table.table,
+                    rows: serde_json::from_str(&table.rows).map_err(AdminError::Json)?,
+                })
+            })
+            .collect::<Result<_, AdminError>>()?;
+
+        Ok(Backup {
+            version,
+            created_at: Utc::now(),
+            tables,
+        })
+    }
+
+    /// Replaces everything in the database with the backup. The database has
+    /// to be migrated to the same version as the backup first.
+    pub async fn import_backup(&self, backup: &Backup) -> Result<(), AdminError> {
+        let version = self.get_schema_version().await?;
+        if version != backup.version {
+            return Err(AdminError::Invalid(format!(
+                "the backup is of migration {}, but the database is at {}",
+                backup.version, version
+            )));
+        }
+
+        let tables = backup
+            .tables
+            .iter()
+            .map(|table| {
+                Ok(TableRows {
+                    table: table.name.clone(),
+                    rows: serde_json::to_string(&table.rows).map_err(AdminError::Json)?,
+                })
+            })
+            .collect::<Result<Vec<_>, AdminError>>()?;
+        let mut conn = self.db.acquire().await.map_err(AdminError::Sqlx)?;
+        db::backup::import_tables(&mut conn, &tables)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        event!(Level::INFO, version, "Imported backup");
+
+        Ok(())
+    }
+
+    async fn get_schema_version(&self) -> Result<i64, AdminError> {
+        db::migration::get_applied_versions(&self.db)
+            .await
+            .map_err(AdminError::Sqlx)?
+            .last()
+            .copied()
+            .ok_or(AdminError::Invalid(
+                "no migration has been applied".to_string(),
+            ))
+    }
+
+    /// Deletes what has been kept around after being deleted, archived or
+    /// expired before `before`: unused archived categories, invitations
+    /// nobody accepted, the tombstones offline clients learn about deletions
+    /// from and the mutations kept to not apply a retried batch twice.
+    /// Nothing is deleted on a dry run, only counted.
+    pub async fn purge(
+        &self,
+        before: chrono::DateTime<Utc>,
+        dry_run: bool,
+    ) -> Result<Purged, AdminError> {
+        let mut tx = self.db.begin().await.map_err(AdminError::Sqlx)?;
+        let mut purged = Purged::default();
+        loop {
+            let categories = db::expense_category::delete_unused_archived(&mut *tx, before)
+                .await
+                .map_err(AdminError::Sqlx)?;
+            if categories == 0 {
+                break;
+            }
+            purged.categories += categories;
+        }
+        purged.invitations = db::invitation::delete_expired(&mut *tx, before)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        purged.tombstones = db::sync::delete_tombstones(&mut *tx, before)
+            .await
+            .map_err(AdminError::Sqlx)?;
+        purged.mutations = db::sync::delete_mutations(&mut *tx, before)
+            .await
+            .map_err(AdminError::Sqlx)?;
+
+        if dry_run {
+            tx.rollback().await.map_err(AdminError::Sqlx)?;
+        } else {
+            tx.commit().await.map_err(AdminError::Sqlx)?;
+            event!(
+                Level::INFO,
+                categories = purged.categories,
+                invitations = purged.invitations,
+                tombstones = purged.tombstones,
+                mutations = purged.mutations,
+                "Purged deleted data"
+            );
+        }
+
+        Ok(purged)
+    }
+}
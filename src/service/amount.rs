@@ -0,0 +1,16 @@
+/// Amounts are stored in minor units and written with two decimals.
+pub fn format_amount(amount: i64, decimal_separator: char) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    format!(
+        "{}{}{}{:02}",
+        sign,
+        amount.abs() / 100,
+        decimal_separator,
+        amount.abs() % 100
+    )
+}
+
+/// The amount written the Swedish way, followed by its currency.
+pub fn format_with_currency(amount: i64, currency: &str) -> String {
+    format!("{} {}", format_amount(amount, ','), currency)
+}
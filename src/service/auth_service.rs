@@ -91,6 +91,9 @@ pub enum AuthError {
 
     #[error("The given email '{0}' is not allowed to sign in")]
     ForbiddenEmail(String),
+
+    #[error("The user '{0}' is disabled")]
+    Disabled(String),
 }
 
 pub type JostridTokenResponse = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
@@ -201,11 +204,21 @@ impl AuthService {
             .await
             .map_err(AuthError::Reqwest)?;
 
-        let is_allowed = allowed_emails.any(|email| email == user_info.mail);
+        let existing = match db::user::get_user_by_email(&self.db, &user_info.mail).await {
+            Ok(user) => Some(user).filter(|user| !user.is_guest),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => return Err(AuthError::Sqlx(err)),
+        };
+        // Users created by an admin are allowed as if they were listed.
+        let is_allowed = allowed_emails.any(|email| email == user_info.mail)
+            || existing.as_ref().is_some_and(|user| user.sign_in_allowed);
 
         if !is_allowed {
             return Err(AuthError::ForbiddenEmail(user_info.mail));
         }
+        if existing.is_some_and(|user| user.disabled_at.is_some()) {
+            return Err(AuthError::Disabled(user_info.mail));
+        }
 
         let user = db::user::upsert_user(
             &self.db,
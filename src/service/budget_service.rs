@@ -0,0 +1,365 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    budget::{Budget, InsertBudget},
+    expense::ExpenseWithShares,
+    user::User,
+};
+
+use super::{
+    event_service::{EventService, LedgerEvent},
+    webhook_service,
+};
+
+#[derive(Debug, Clone)]
+pub struct BudgetService {
+    db: Pool<Postgres>,
+    events: EventService,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BudgetError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Budget {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid budget: {0}")]
+    Invalid(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BudgetPeriod {
+    Week,
+    Month,
+    Year,
+}
+
+impl BudgetPeriod {
+    pub fn parse(period: &str) -> Option<Self> {
+        match period {
+            "week" => Some(BudgetPeriod::Week),
+            "month" => Some(BudgetPeriod::Month),
+            "year" => Some(BudgetPeriod::Year),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Week => "week",
+            BudgetPeriod::Month => "month",
+            BudgetPeriod::Year => "year",
+        }
+    }
+
+    /// Start of the period containing `at`. Weeks start on Mondays and all
+    /// periods are aligned to UTC.
+    pub fn start_of(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let date = at.date_naive();
+        let start = match self {
+            BudgetPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            BudgetPeriod::Month => date.with_day(1).expect("Every month has a first day"),
+            BudgetPeriod::Year => date.with_ordinal(1).expect("Every year has a first day"),
+        };
+
+        start.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    pub fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            BudgetPeriod::Week => start + Duration::days(7),
+            BudgetPeriod::Month => start + Months::new(1),
+            BudgetPeriod::Year => start + Months::new(12),
+        }
+    }
+
+    pub fn previous(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            BudgetPeriod::Week => start - Duration::days(7),
+            BudgetPeriod::Month => start - Months::new(1),
+            BudgetPeriod::Year => start - Months::new(12),
+        }
+    }
+}
+
+pub struct NewBudget {
+    pub group_id: i32,
+    pub name: String,
+    pub category_id: i32,
+    pub period: BudgetPeriod,
+    pub amount: i32,
+    pub currency: String,
+    pub user_id: Option<i32>,
+    pub alert_thresholds: Vec<i32>,
+}
+
+pub struct BudgetPeriodStatus {
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub spent: i64,
+}
+
+pub struct BudgetStatus {
+    pub budget: Budget,
+    /// The current period first, followed by past periods.
+    pub periods: Vec<BudgetPeriodStatus>,
+}
+
+impl BudgetService {
+    pub fn new(db: Pool<Postgres>, events: EventService) -> Self {
+        Self { db, events }
+    }
+
+    pub async fn get_budgets(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<Budget>, BudgetError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::budget::get_budgets(&self.db, group_id)
+            .await
+            .map_err(BudgetError::Sqlx)
+    }
+
+    pub async fn create_budget(
+        &self,
+        actor: &User,
+        budget: NewBudget,
+    ) -> Result<Budget, BudgetError> {
+        self.ensure_member(actor.id, budget.group_id).await?;
+        let to_insert = self.prepare_budget(budget).await?;
+
+        db::budget::insert_budget(&self.db, to_insert)
+            .await
+            .map_err(BudgetError::Sqlx)
+    }
+
+    /// Replaces a budget. The group of a budget can't be changed.
+    pub async fn update_budget(
+        &self,
+        actor: &User,
+        id: i32,
+        budget: NewBudget,
+    ) -> Result<Budget, BudgetError> {
+        let existing = self.get_budget(actor, id).await?;
+        let to_insert = self
+            .prepare_budget(NewBudget {
+                group_id: existing.group_id,
+                ..budget
+            })
+            .await?;
+
+        db::budget::update_budget(&self.db, id, to_insert)
+            .await
+            .map_err(BudgetError::Sqlx)
+    }
+
+    pub async fn delete_budget(&self, actor: &User, id: i32) -> Result<(), BudgetError> {
+        self.get_budget(actor, id).await?;
+
+        db::budget::delete_budget(&self.db, id)
+            .await
+            .map_err(BudgetError::Sqlx)
+    }
+
+    /// Spending against every budget of the group for the current period and
+    /// `past_periods` periods before it.
+    pub async fn get_budget_status(
+        &self,
+        actor: &User,
+        group_id: i32,
+        past_periods: usize,
+    ) -> Result<Vec<BudgetStatus>, BudgetError> {
+        let budgets = self.get_budgets(actor, group_id).await?;
+        let now = Utc::now();
+
+        let mut statuses = Vec::new();
+        for budget in budgets {
+            let period = parse_period(&budget.period)?;
+            let mut period_start = period.start_of(now);
+            let mut periods = Vec::new();
+            for _ in 0..=past_periods {
+                let period_end = period.next(period_start);
+                let spent =
+                    db::budget::get_budget_spent(&self.db, &budget, period_start, period_end)
+                        .await
+                        .map_err(BudgetError::Sqlx)?;
+                periods.push(BudgetPeriodStatus {
+                    period_start,
+                    period_end,
+                    spent,
+                });
+                period_start = period.previous(period_start);
+            }
+
+            statuses.push(BudgetStatus { budget, periods });
+        }
+
+        Ok(statuses)
+    }
+
+    /// Publishes a [`LedgerEvent::BudgetExceeded`] for every alert threshold
+    /// that `expense` pushed a budget past within the expense's period. Each
+    /// threshold only alerts once per period.
+    pub async fn check_alerts(&self, (expense, _): &ExpenseWithShares) -> Result<(), BudgetError> {
+        let mut category_ids: Vec<i32> = expense.category.iter().map(|c| c.id).collect();
+        let item_category_ids = expense
+            .receipt
+            .iter()
+            .flat_map(|receipt| receipt.items.iter())
+            .filter_map(|(item, _)| item.category_id);
+        category_ids.extend(item_category_ids);
+        if category_ids.is_empty() || expense.expense.is_payment {
+            return Ok(());
+        }
+
+        let budgets = db::budget::get_budgets_for_categories(
+            &self.db,
+            expense.expense.group_id,
+            &category_ids,
+            &expense.expense.currency,
+        )
+        .await
+        .map_err(BudgetError::Sqlx)?;
+
+        for budget in budgets {
+            let period = parse_period(&budget.period)?;
+            let period_start = period.start_of(expense.expense.created_at);
+            let spent = db::budget::get_budget_spent(
+                &self.db,
+                &budget,
+                period_start,
+                period.next(period_start),
+            )
+            .await
+            .map_err(BudgetError::Sqlx)?;
+
+            for threshold in &budget.alert_thresholds {
+                if spent * 100 < budget.amount as i64 * *threshold as i64 {
+                    continue;
+                }
+
+                let mut tx = self.db.begin().await.map_err(BudgetError::Sqlx)?;
+                let is_new = db::budget::insert_budget_alert(
+                    &mut *tx,
+                    budget.id,
+                    period_start,
+                    *threshold,
+                    expense.expense.id,
+                )
+                .await
+                .map_err(BudgetError::Sqlx)?;
+                if !is_new {
+                    continue;
+                }
+
+                let ledger_event = LedgerEvent::BudgetExceeded {
+                    budget_id: budget.id,
+                    group_id: budget.group_id,
+                    expense_id: expense.expense.id,
+                    threshold: *threshold,
+                    spent,
+                    amount: budget.amount,
+                    currency: budget.currency.clone(),
+                    period_start,
+                };
+                webhook_service::enqueue(&mut tx, &ledger_event)
+                    .await
+                    .map_err(BudgetError::Sqlx)?;
+                tx.commit().await.map_err(BudgetError::Sqlx)?;
+                self.events.publish(ledger_event);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_budget(&self, actor: &User, id: i32) -> Result<Budget, BudgetError> {
+        let budget = db::budget::get_budget(&self.db, id)
+            .await
+            .map_err(BudgetError::Sqlx)?
+            .ok_or(BudgetError::NotFound(id))?;
+        self.ensure_member(actor.id, budget.group_id).await?;
+
+        Ok(budget)
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), BudgetError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(BudgetError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(BudgetError::NotMember(user_id, group_id))
+        }
+    }
+
+    async fn prepare_budget(&self, budget: NewBudget) -> Result<InsertBudget, BudgetError> {
+        let name = budget.name.trim().to_string();
+        if name.is_empty() {
+            return Err(BudgetError::Invalid("name must not be empty".to_string()));
+        }
+        if budget.amount <= 0 {
+            return Err(BudgetError::Invalid("amount must be positive".to_string()));
+        }
+        if budget
+            .alert_thresholds
+            .iter()
+            .any(|threshold| !(1..=1000).contains(threshold))
+        {
+            return Err(BudgetError::Invalid(
+                "alert thresholds must be between 1 and 1000 percent".to_string(),
+            ));
+        }
+
+        let category = db::expense_category::get_expense_category(&self.db, budget.category_id)
+            .await
+            .map_err(BudgetError::Sqlx)?;
+        let is_visible = category.is_some_and(|category| {
+            category
+                .group_id
+                .is_none_or(|group_id| group_id == budget.group_id)
+        });
+        if !is_visible {
+            return Err(BudgetError::Invalid(format!(
+                "category {} is not available in group {}",
+                budget.category_id, budget.group_id
+            )));
+        }
+        if let Some(user_id) = budget.user_id {
+            self.ensure_member(user_id, budget.group_id).await?;
+        }
+
+        let mut alert_thresholds = budget.alert_thresholds;
+        alert_thresholds.sort_unstable();
+        alert_thresholds.dedup();
+
+        Ok(InsertBudget {
+            group_id: budget.group_id,
+            name,
+            category_id: budget.category_id,
+            period: budget.period.as_str().to_string(),
+            amount: budget.amount,
+            currency: budget.currency,
+            user_id: budget.user_id,
+            alert_thresholds,
+        })
+    }
+}
+
+fn parse_period(period: &str) -> Result<BudgetPeriod, BudgetError> {
+    BudgetPeriod::parse(period)
+        .ok_or_else(|| BudgetError::Invalid(format!("'{}' is not a budget period", period)))
+}
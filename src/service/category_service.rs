@@ -0,0 +1,485 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{PgConnection, Pool, Postgres};
+
+use crate::db::{
+    self,
+    category_rule::{CategoryRule, InsertCategoryRule},
+    expense_category::{
+        CategorizedExpense, ExpenseCategory, InsertExpenseCategory, UpdateExpenseCategory,
+    },
+    user::User,
+};
+
+/// How many of the latest expenses of a group are used when suggesting.
+const SUGGESTION_HISTORY_SIZE: i64 = 1000;
+/// A matching keyword rule outweighs any amount of matching history.
+const RULE_SCORE: f64 = 100.0;
+
+#[derive(Debug, Clone)]
+pub struct CategoryService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CategoryError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Category {0} not found")]
+    NotFound(i32),
+
+    #[error("Category rule {0} not found")]
+    RuleNotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    Forbidden(i32, i32),
+
+    #[error("Category {0} is a global default and can't be changed")]
+    ReadOnly(i32),
+
+    #[error("Invalid category: {0}")]
+    Invalid(String),
+}
+
+pub struct NewCategory {
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub group_id: i32,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+/// Fields left as `None` are kept as they are.
+pub struct PatchCategory {
+    pub name: Option<String>,
+    /// `Some(None)` moves the category to the top level.
+    pub parent_id: Option<Option<i32>>,
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+pub struct SuggestionQuery {
+    pub name: String,
+    pub group_id: i32,
+    pub paid_by: Option<i32>,
+    pub total: Option<i32>,
+    pub limit: usize,
+}
+
+pub struct CategorySuggestion {
+    pub category: ExpenseCategory,
+    /// Share of the total score, between 0 and 1.
+    pub confidence: f64,
+    /// The keyword of the rule that matched, if any.
+    pub rule_keyword: Option<String>,
+}
+
+pub struct NewCategoryRule {
+    pub group_id: i32,
+    pub keyword: String,
+    pub category_id: i32,
+}
+
+impl CategoryService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_categories(
+        &self,
+        actor: &User,
+        group_id: Option<i32>,
+        include_archived: bool,
+    ) -> Result<Vec<ExpenseCategory>, CategoryError> {
+        if let Some(group_id) = group_id {
+            self.ensure_member(actor, group_id).await?;
+        }
+
+        db::expense_category::get_expense_categories(&self.db, group_id, include_archived)
+            .await
+            .map_err(CategoryError::Sqlx)
+    }
+
+    pub async fn create_category(
+        &self,
+        actor: &User,
+        category: NewCategory,
+    ) -> Result<ExpenseCategory, CategoryError> {
+        self.ensure_member(actor, category.group_id).await?;
+
+        let mut conn = self.db.acquire().await.map_err(CategoryError::Sqlx)?;
+        let name = validate_name(&category.name)?;
+        validate_color(category.color.as_deref())?;
+        if let Some(parent_id) = category.parent_id {
+            validate_parent(&mut conn, None, category.group_id, parent_id).await?;
+        }
+
+        db::expense_category::insert_expense_category(
+            &mut *conn,
+            InsertExpenseCategory {
+                name,
+                parent_id: category.parent_id,
+                group_id: Some(category.group_id),
+                icon: category.icon,
+                color: category.color,
+            },
+        )
+        .await
+        .map_err(CategoryError::Sqlx)
+    }
+
+    pub async fn update_category(
+        &self,
+        actor: &User,
+        id: i32,
+        patch: PatchCategory,
+    ) -> Result<ExpenseCategory, CategoryError> {
+        let mut conn = self.db.acquire().await.map_err(CategoryError::Sqlx)?;
+        let (existing, group_id) = self.get_editable(&mut conn, actor, id).await?;
+
+        let name = match patch.name {
+            Some(name) => validate_name(&name)?,
+            None => existing.name,
+        };
+        validate_color(patch.color.as_deref())?;
+        if let Some(Some(parent_id)) = patch.parent_id {
+            validate_parent(&mut conn, Some(id), group_id, parent_id).await?;
+        }
+
+        db::expense_category::update_expense_category(
+            &mut *conn,
+            id,
+            UpdateExpenseCategory {
+                name,
+                parent_id: patch.parent_id.unwrap_or(existing.parent_id),
+                icon: patch.icon.or(existing.icon),
+                color: patch.color.or(existing.color),
+            },
+        )
+        .await
+        .map_err(CategoryError::Sqlx)
+    }
+
+    pub async fn set_archived(
+        &self,
+        actor: &User,
+        id: i32,
+        archived: bool,
+    ) -> Result<ExpenseCategory, CategoryError> {
+        let mut conn = self.db.acquire().await.map_err(CategoryError::Sqlx)?;
+        self.get_editable(&mut conn, actor, id).await?;
+
+        db::expense_category::set_expense_category_archived(&mut *conn, id, archived)
+            .await
+            .map_err(CategoryError::Sqlx)
+    }
+
+    /// Merges the custom category `from_id` into `into_id`, which may be either
+    /// a global default or another category of the same group.
+    pub async fn merge_category(
+        &self,
+        actor: &User,
+        from_id: i32,
+        into_id: i32,
+    ) -> Result<ExpenseCategory, CategoryError> {
+        if from_id == into_id {
+            return Err(CategoryError::Invalid(
+                "can't merge a category into itself".to_string(),
+            ));
+        }
+
+        let mut conn = self.db.acquire().await.map_err(CategoryError::Sqlx)?;
+        let (_, group_id) = self.get_editable(&mut conn, actor, from_id).await?;
+        let into = get_visible(&mut conn, group_id, into_id).await?;
+        if into.parent_id == Some(from_id) {
+            return Err(CategoryError::Invalid(
+                "can't merge a category into one of its sub-categories".to_string(),
+            ));
+        }
+
+        let child_count = db::expense_category::count_child_categories(&mut *conn, from_id)
+            .await
+            .map_err(CategoryError::Sqlx)?;
+        if child_count > 0 && into.parent_id.is_some() {
+            return Err(CategoryError::Invalid(
+                "a category with sub-categories can only be merged into a top level category"
+                    .to_string(),
+            ));
+        }
+
+        db::expense_category::merge_expense_category(&mut conn, from_id, into_id)
+            .await
+            .map_err(CategoryError::Sqlx)
+    }
+
+    /// Suggests categories for an expense from keyword rules and from how
+    /// expenses with similar names have been categorised within the group.
+    pub async fn suggest_categories(
+        &self,
+        actor: &User,
+        query: SuggestionQuery,
+    ) -> Result<Vec<CategorySuggestion>, CategoryError> {
+        self.ensure_member(actor, query.group_id).await?;
+
+        let history = db::expense_category::get_categorized_expenses(
+            &self.db,
+            query.group_id,
+            SUGGESTION_HISTORY_SIZE,
+        )
+        .await
+        .map_err(CategoryError::Sqlx)?;
+        let rules = db::category_rule::get_category_rules(&self.db, query.group_id)
+            .await
+            .map_err(CategoryError::Sqlx)?;
+        let categories =
+            db::expense_category::get_expense_categories(&self.db, Some(query.group_id), false)
+                .await
+                .map_err(CategoryError::Sqlx)?;
+
+        let mut scores = score_history(&query, &history);
+        let mut rule_keywords = HashMap::new();
+        let name_words = words(&query.name);
+        for rule in rules
+            .iter()
+            .filter(|rule| matches_keyword(&name_words, &rule.keyword))
+        {
+            *scores.entry(rule.category_id).or_default() += RULE_SCORE;
+            rule_keywords.insert(rule.category_id, rule.keyword.clone());
+        }
+
+        let total_score: f64 = scores.values().sum();
+        let mut suggestions: Vec<CategorySuggestion> = categories
+            .into_iter()
+            .filter_map(|category| {
+                let score = scores.get(&category.id)?;
+                Some(CategorySuggestion {
+                    confidence: score / total_score,
+                    rule_keyword: rule_keywords.remove(&category.id),
+                    category,
+                })
+            })
+            .collect();
+        suggestions.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        suggestions.truncate(query.limit);
+
+        Ok(suggestions)
+    }
+
+    pub async fn get_rules(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<CategoryRule>, CategoryError> {
+        self.ensure_member(actor, group_id).await?;
+
+        db::category_rule::get_category_rules(&self.db, group_id)
+            .await
+            .map_err(CategoryError::Sqlx)
+    }
+
+    pub async fn create_rule(
+        &self,
+        actor: &User,
+        rule: NewCategoryRule,
+    ) -> Result<CategoryRule, CategoryError> {
+        self.ensure_member(actor, rule.group_id).await?;
+
+        let keyword = rule.keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return Err(CategoryError::Invalid(
+                "keyword must not be empty".to_string(),
+            ));
+        }
+        let mut conn = self.db.acquire().await.map_err(CategoryError::Sqlx)?;
+        get_visible(&mut conn, rule.group_id, rule.category_id).await?;
+
+        db::category_rule::upsert_category_rule(
+            &mut *conn,
+            InsertCategoryRule {
+                group_id: rule.group_id,
+                keyword,
+                category_id: rule.category_id,
+            },
+        )
+        .await
+        .map_err(CategoryError::Sqlx)
+    }
+
+    pub async fn delete_rule(&self, actor: &User, id: i32) -> Result<(), CategoryError> {
+        let rule = db::category_rule::get_category_rule(&self.db, id)
+            .await
+            .map_err(CategoryError::Sqlx)?
+            .ok_or(CategoryError::RuleNotFound(id))?;
+        self.ensure_member(actor, rule.group_id).await?;
+
+        db::category_rule::delete_category_rule(&self.db, id)
+            .await
+            .map_err(CategoryError::Sqlx)
+    }
+
+    async fn ensure_member(&self, actor: &User, group_id: i32) -> Result<(), CategoryError> {
+        let is_member = db::group::is_member(&self.db, group_id, actor.id)
+            .await
+            .map_err(CategoryError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(CategoryError::Forbidden(actor.id, group_id))
+        }
+    }
+
+    /// Fetches a category that `actor` is allowed to change, together with the
+    /// group it belongs to.
+    async fn get_editable(
+        &self,
+        conn: &mut PgConnection,
+        actor: &User,
+        id: i32,
+    ) -> Result<(ExpenseCategory, i32), CategoryError> {
+        let category = db::expense_category::get_expense_category(&mut *conn, id)
+            .await
+            .map_err(CategoryError::Sqlx)?
+            .ok_or(CategoryError::NotFound(id))?;
+        let group_id = category.group_id.ok_or(CategoryError::ReadOnly(id))?;
+        self.ensure_member(actor, group_id).await?;
+
+        Ok((category, group_id))
+    }
+}
+
+/// Fetches a category that can be used within `group_id`, i.e. a global
+/// default or one of the group's own categories.
+async fn get_visible(
+    conn: &mut PgConnection,
+    group_id: i32,
+    id: i32,
+) -> Result<ExpenseCategory, CategoryError> {
+    db::expense_category::get_expense_category(&mut *conn, id)
+        .await
+        .map_err(CategoryError::Sqlx)?
+        .filter(|category| category.group_id.is_none_or(|id| id == group_id))
+        .ok_or(CategoryError::NotFound(id))
+}
+
+/// Categories are at most two levels deep, so a parent has to be a top level
+/// category and a category with children can't be moved below another.
+async fn validate_parent(
+    conn: &mut PgConnection,
+    id: Option<i32>,
+    group_id: i32,
+    parent_id: i32,
+) -> Result<(), CategoryError> {
+    if id == Some(parent_id) {
+        return Err(CategoryError::Invalid(
+            "a category can't be its own parent".to_string(),
+        ));
+    }
+
+    let parent = get_visible(conn, group_id, parent_id).await?;
+    if parent.parent_id.is_some() {
+        return Err(CategoryError::Invalid(format!(
+            "'{}' is a sub-category and can't have sub-categories",
+            parent.name
+        )));
+    }
+
+    if let Some(id) = id {
+        let child_count = db::expense_category::count_child_categories(&mut *conn, id)
+            .await
+            .map_err(CategoryError::Sqlx)?;
+        if child_count > 0 {
+            return Err(CategoryError::Invalid(
+                "a category with sub-categories can't be moved below another".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The lowercase words of a name.
+pub fn tokenize(name: &str) -> HashSet<String> {
+    words(name).into_iter().collect()
+}
+
+/// The lowercase words of a name, in order.
+fn words(name: &str) -> Vec<String> {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+/// Whether the words of a keyword appear in a row among the words of a
+/// name, so that "ica" matches "ICA Maxi" but not "Musical".
+fn matches_keyword(name_words: &[String], keyword: &str) -> bool {
+    let keyword = words(keyword);
+    !keyword.is_empty()
+        && name_words
+            .windows(keyword.len())
+            .any(|window| window == keyword.as_slice())
+}
+
+/// Scores each category by the token similarity (Jaccard index) between the
+/// name in the query and the names of the expenses in that category. Matches
+/// with the same payer or a similar amount weigh a bit more.
+fn score_history(query: &SuggestionQuery, history: &[CategorizedExpense]) -> HashMap<i32, f64> {
+    let tokens = tokenize(&query.name);
+    let mut scores = HashMap::new();
+    if tokens.is_empty() {
+        return scores;
+    }
+
+    for expense in history {
+        let expense_tokens = tokenize(&expense.name);
+        let shared = tokens.intersection(&expense_tokens).count();
+        if shared == 0 {
+            continue;
+        }
+
+        let mut score = shared as f64 / tokens.union(&expense_tokens).count() as f64;
+        if query.paid_by == Some(expense.paid_by) {
+            score *= 1.2;
+        }
+        if let Some(total) = query.total.filter(|total| *total > 0 && expense.total > 0) {
+            let ratio = total.max(expense.total) as f64 / total.min(expense.total) as f64;
+            if ratio <= 1.5 {
+                score *= 1.2;
+            }
+        }
+
+        *scores.entry(expense.category_id).or_default() += score;
+    }
+
+    scores
+}
+
+fn validate_name(name: &str) -> Result<String, CategoryError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CategoryError::Invalid("name must not be empty".to_string()));
+    }
+
+    Ok(name.to_string())
+}
+
+fn validate_color(color: Option<&str>) -> Result<(), CategoryError> {
+    let Some(color) = color else {
+        return Ok(());
+    };
+
+    let is_hex = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !is_hex {
+        return Err(CategoryError::Invalid(format!(
+            "'{}' is not a colour on the form #rrggbb",
+            color
+        )));
+    }
+
+    Ok(())
+}
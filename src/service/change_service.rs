@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, Pool, Postgres};
+use tokio::sync::broadcast;
+use tracing::{event, Level};
+
+/// Channel the database triggers notify on.
+const CHANNEL: &str = "ledger_changes";
+/// Wait before listening again after the listener failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Entity of the change sent when changes may have been missed.
+pub const RESYNC_ENTITY: &str = "resync";
+
+/// A committed change to a row, as published by the database. Unlike
+/// [`super::event_service::LedgerEvent`] these are seen by every server
+/// instance, whoever made the change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// `expense`, `settlement`, `category`, `comment`, `balance` for the
+    /// balances of a group that changed along with an expense or settlement,
+    /// or `resync` when changes may have been missed while reconnecting.
+    pub entity: String,
+    /// `insert`, `update` or `delete`.
+    pub action: String,
+    pub id: i32,
+    /// `None` for changes visible in every group.
+    pub group_id: Option<i32>,
+    /// The expense a comment belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expense_id: Option<i32>,
+}
+
+impl Change {
+    /// Tells every client to reload, since notifications sent while the
+    /// listener was disconnected are lost.
+    pub fn resync() -> Change {
+        Change {
+            entity: RESYNC_ENTITY.to_string(),
+            action: "update".to_string(),
+            id: 0,
+            group_id: None,
+            expense_id: None,
+        }
+    }
+
+    /// Expenses and settlements move balances, so clients get a separate
+    /// balance change to refresh them by.
+    pub fn balance_change(&self) -> Option<Change> {
+        if self.entity != "expense" && self.entity != "settlement" {
+            return None;
+        }
+
+        Some(Change {
+            entity: "balance".to_string(),
+            action: "update".to_string(),
+            id: self.group_id?,
+            group_id: self.group_id,
+            expense_id: None,
+        })
+    }
+
+    pub fn is_visible_in(&self, group_ids: &[i32]) -> bool {
+        self.group_id
+            .is_none_or(|group_id| group_ids.contains(&group_id))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeService {
+    sender: broadcast::Sender<Change>,
+}
+
+impl ChangeService {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.sender.subscribe()
+    }
+
+    /// Forwards notifications from the database to subscribers for as long
+    /// as the server runs. Subscribers get a [`Change::resync`] after the
+    /// listener reconnected.
+    pub fn spawn(&self, db: Pool<Postgres>) {
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            let mut reconnecting = false;
+            loop {
+                match listen(&db, &sender, reconnecting).await {
+                    Ok(()) => event!(Level::WARN, "Lost the connection listening for changes"),
+                    Err(listen_error) => {
+                        event!(Level::ERROR, %listen_error, "Listening for changes failed");
+                    }
+                }
+                reconnecting = true;
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+}
+
+async fn listen(
+    db: &Pool<Postgres>,
+    sender: &broadcast::Sender<Change>,
+    reconnecting: bool,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen(CHANNEL).await?;
+    if reconnecting {
+        // Sending only fails when nobody is listening, which is fine.
+        let _ = sender.send(Change::resync());
+    }
+
+    loop {
+        // `None` when the connection was lost, listening starts over on a new
+        // one and subscribers are told to resync.
+        let Some(notification) = listener.try_recv().await? else {
+            return Ok(());
+        };
+        match serde_json::from_str::<Change>(notification.payload()) {
+            Ok(change) => {
+                let balance_change = change.balance_change();
+                // Sending only fails when nobody is listening, which is fine.
+                let _ = sender.send(change);
+                if let Some(balance_change) = balance_change {
+                    let _ = sender.send(balance_change);
+                }
+            }
+            Err(parse_error) => {
+                event!(Level::WARN, %parse_error, payload = notification.payload(), "Ignoring malformed change");
+            }
+        }
+    }
+}
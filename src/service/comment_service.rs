@@ -0,0 +1,199 @@
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    expense::ExpenseWithShares,
+    expense_comment::{ExpenseComment, InsertExpenseComment},
+    user::User,
+};
+
+use super::{
+    event_service::{EventService, LedgerEvent},
+    webhook_service,
+};
+
+/// Long enough for a discussion, short enough to fit in a notification.
+const MAX_BODY_LENGTH: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub struct CommentService {
+    db: Pool<Postgres>,
+    events: EventService,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommentError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Expense {0} not found")]
+    ExpenseNotFound(i32),
+
+    #[error("Comment {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not the author of the comment")]
+    Forbidden(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid comment: {0}")]
+    Invalid(String),
+}
+
+impl CommentService {
+    pub fn new(db: Pool<Postgres>, events: EventService) -> Self {
+        Self { db, events }
+    }
+
+    /// Every member of the expense's group can read its comments.
+    pub async fn get_comments(
+        &self,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<Vec<ExpenseComment>, CommentError> {
+        self.get_expense(actor, expense_id).await?;
+
+        db::expense_comment::get_comments(&self.db, expense_id)
+            .await
+            .map_err(CommentError::Sqlx)
+    }
+
+    /// Publishes a [`LedgerEvent::ExpenseCommented`] to the participants of
+    /// the expense and everyone who commented on it before.
+    pub async fn create_comment(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        body: String,
+    ) -> Result<ExpenseComment, CommentError> {
+        let (expense, shares) = self.get_expense(actor, expense_id).await?;
+        let body = validate_body(body)?;
+
+        let mut tx = self.db.begin().await.map_err(CommentError::Sqlx)?;
+        let comment = db::expense_comment::insert_comment(
+            &mut *tx,
+            InsertExpenseComment {
+                expense_id,
+                user_id: actor.id,
+                body,
+            },
+        )
+        .await
+        .map_err(CommentError::Sqlx)?;
+
+        let mut participant_ids: Vec<i32> = shares.iter().map(|share| share.user_id).collect();
+        let commenter_ids = db::expense_comment::get_comments(&mut *tx, expense_id)
+            .await
+            .map_err(CommentError::Sqlx)?
+            .into_iter()
+            .map(|comment| comment.user_id);
+        for user_id in std::iter::once(expense.paid_by).chain(commenter_ids) {
+            if !participant_ids.contains(&user_id) {
+                participant_ids.push(user_id);
+            }
+        }
+        let ledger_event = LedgerEvent::ExpenseCommented {
+            comment_id: comment.id,
+            expense_id,
+            group_id: expense.expense.group_id,
+            actor_id: actor.id,
+            participant_ids,
+            body: comment.body.clone(),
+        };
+        webhook_service::enqueue(&mut tx, &ledger_event)
+            .await
+            .map_err(CommentError::Sqlx)?;
+        tx.commit().await.map_err(CommentError::Sqlx)?;
+        self.events.publish(ledger_event);
+
+        Ok(comment)
+    }
+
+    /// Only the author can edit a comment.
+    pub async fn update_comment(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        id: i32,
+        body: String,
+    ) -> Result<ExpenseComment, CommentError> {
+        self.get_own_comment(actor, expense_id, id).await?;
+        let body = validate_body(body)?;
+
+        db::expense_comment::update_comment(&self.db, id, body)
+            .await
+            .map_err(CommentError::Sqlx)
+    }
+
+    /// Only the author can delete a comment.
+    pub async fn delete_comment(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        id: i32,
+    ) -> Result<(), CommentError> {
+        self.get_own_comment(actor, expense_id, id).await?;
+
+        db::expense_comment::delete_comment(&self.db, id)
+            .await
+            .map_err(CommentError::Sqlx)
+    }
+
+    async fn get_own_comment(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        id: i32,
+    ) -> Result<ExpenseComment, CommentError> {
+        self.get_expense(actor, expense_id).await?;
+        let comment = db::expense_comment::get_comment(&self.db, id)
+            .await
+            .map_err(CommentError::Sqlx)?
+            .filter(|comment| comment.expense_id == expense_id)
+            .ok_or(CommentError::NotFound(id))?;
+        if comment.user_id != actor.id {
+            return Err(CommentError::Forbidden(actor.id));
+        }
+
+        Ok(comment)
+    }
+
+    async fn get_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<ExpenseWithShares, CommentError> {
+        let mut conn = self.db.acquire().await.map_err(CommentError::Sqlx)?;
+        let expense = db::expense::get_expense(expense_id, &mut conn)
+            .await
+            .map_err(CommentError::Sqlx)?
+            .ok_or(CommentError::ExpenseNotFound(expense_id))?;
+
+        let group_id = expense.0.expense.group_id;
+        let is_member = db::group::is_member(&self.db, group_id, actor.id)
+            .await
+            .map_err(CommentError::Sqlx)?;
+        if !is_member {
+            return Err(CommentError::NotMember(actor.id, group_id));
+        }
+
+        Ok(expense)
+    }
+}
+
+fn validate_body(body: String) -> Result<String, CommentError> {
+    let body = body.trim().to_string();
+    if body.is_empty() {
+        return Err(CommentError::Invalid("body must not be empty".to_string()));
+    }
+    if body.chars().count() > MAX_BODY_LENGTH {
+        return Err(CommentError::Invalid(format!(
+            "body must be at most {} characters",
+            MAX_BODY_LENGTH
+        )));
+    }
+
+    Ok(body)
+}
@@ -0,0 +1,139 @@
+use chrono::Utc;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::{event, Level};
+
+/// Something that happened to the ledger. Published after the change has been
+/// committed, so consumers can rely on it being visible in the database.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LedgerEvent {
+    ExpenseCreated {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+    },
+    ExpenseUpdated {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+    },
+    ExpenseDeleted {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+    },
+    SettlementRecorded {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        payer_id: i32,
+        receiver_id: i32,
+        amount: i32,
+        currency: String,
+    },
+    /// The last participant asked for approval approved the expense, which
+    /// now counts towards balances.
+    ExpenseConfirmed {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+    },
+    ExpenseDisputed {
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+        reason: String,
+    },
+    ExpenseCommented {
+        comment_id: i32,
+        expense_id: i32,
+        group_id: i32,
+        actor_id: i32,
+        participant_ids: Vec<i32>,
+        body: String,
+    },
+    BudgetExceeded {
+        budget_id: i32,
+        group_id: i32,
+        /// The expense that pushed the budget past the threshold.
+        expense_id: i32,
+        /// Percentage of the budget amount.
+        threshold: i32,
+        spent: i64,
+        amount: i32,
+        currency: String,
+        period_start: chrono::DateTime<Utc>,
+    },
+}
+
+impl LedgerEvent {
+    /// The `type` the event is serialized with.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LedgerEvent::ExpenseCreated { .. } => "expense_created",
+            LedgerEvent::ExpenseUpdated { .. } => "expense_updated",
+            LedgerEvent::ExpenseDeleted { .. } => "expense_deleted",
+            LedgerEvent::SettlementRecorded { .. } => "settlement_recorded",
+            LedgerEvent::ExpenseConfirmed { .. } => "expense_confirmed",
+            LedgerEvent::ExpenseDisputed { .. } => "expense_disputed",
+            LedgerEvent::ExpenseCommented { .. } => "expense_commented",
+            LedgerEvent::BudgetExceeded { .. } => "budget_exceeded",
+        }
+    }
+
+    pub fn group_id(&self) -> i32 {
+        match self {
+            LedgerEvent::ExpenseCreated { group_id, .. }
+            | LedgerEvent::ExpenseUpdated { group_id, .. }
+            | LedgerEvent::ExpenseDeleted { group_id, .. }
+            | LedgerEvent::SettlementRecorded { group_id, .. }
+            | LedgerEvent::ExpenseConfirmed { group_id, .. }
+            | LedgerEvent::ExpenseDisputed { group_id, .. }
+            | LedgerEvent::ExpenseCommented { group_id, .. }
+            | LedgerEvent::BudgetExceeded { group_id, .. } => *group_id,
+        }
+    }
+
+    pub fn expense_id(&self) -> i32 {
+        match self {
+            LedgerEvent::ExpenseCreated { expense_id, .. }
+            | LedgerEvent::ExpenseUpdated { expense_id, .. }
+            | LedgerEvent::ExpenseDeleted { expense_id, .. }
+            | LedgerEvent::SettlementRecorded { expense_id, .. }
+            | LedgerEvent::ExpenseConfirmed { expense_id, .. }
+            | LedgerEvent::ExpenseDisputed { expense_id, .. }
+            | LedgerEvent::ExpenseCommented { expense_id, .. }
+            | LedgerEvent::BudgetExceeded { expense_id, .. } => *expense_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventService {
+    sender: broadcast::Sender<LedgerEvent>,
+}
+
+impl EventService {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Receives every event published from now on. Slow receivers miss events
+    /// once the channel's capacity is exceeded.
+    pub fn subscribe(&self) -> broadcast::Receiver<LedgerEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, ledger_event: LedgerEvent) {
+        event!(Level::DEBUG, ?ledger_event, "Publishing ledger event");
+        // Sending only fails when nobody is listening, which is fine.
+        let _ = self.sender.send(ledger_event);
+    }
+}
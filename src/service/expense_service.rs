@@ -0,0 +1,1671 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use sqlx::{Pool, Postgres, Transaction};
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    expense::{
+        ExpenseDetails, ExpenseFilter, ExpenseWithShares, InsertAccountShare, InsertExpense,
+        InsertExpenseExtra, InsertExpenseItem, InsertExpenseItemShare, InsertReceipt,
+    },
+    expense_approval::{APPROVED, CONFIRMED, DISPUTED},
+    expense_log::{ExpenseLog, InsertExpenseLog},
+    group::DEFAULT_GROUP_ID,
+    user::User,
+};
+
+use super::{
+    budget_service::BudgetService,
+    event_service::{EventService, LedgerEvent},
+    webhook_service,
+};
+
+const MAX_NOTE_LENGTH: usize = 2000;
+const MAX_NAME_LENGTH: usize = 200;
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct ExpenseService {
+    db: Pool<Postgres>,
+    events: EventService,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpenseError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Expense {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a participant of the expense")]
+    Forbidden(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid expense: {0}")]
+    Invalid(String),
+
+    #[error("Expenses of group {0} before {1} are closed")]
+    Closed(i32, chrono::DateTime<Utc>),
+}
+
+/// How the total of an expense is divided between the participants.
+pub enum Split {
+    /// Signed shares as stored in `account_share`: what the user paid minus
+    /// what the user consumed. Must sum to zero.
+    Shares(Vec<InsertAccountShare>),
+    /// Divide the total proportionally to each user's weight.
+    Weighted(Vec<SplitWeight>),
+    /// Split every line item by the weights of its own participants, after
+    /// distributing the extras over the items proportionally to their amounts.
+    Itemised(NewReceipt),
+    /// Divide the total by the weights of a split profile of the group.
+    Profile(i32),
+}
+
+pub struct SplitWeight {
+    pub user_id: i32,
+    pub weight: u32,
+}
+
+pub struct NewReceipt {
+    pub items: Vec<NewReceiptItem>,
+    pub extras: Vec<NewReceiptExtra>,
+}
+
+pub struct NewReceiptItem {
+    pub name: String,
+    pub amount: i32,
+    pub category_id: Option<i32>,
+    pub weights: Vec<SplitWeight>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExtraKind {
+    Tax,
+    Tip,
+    Discount,
+}
+
+impl ExtraKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtraKind::Tax => "tax",
+            ExtraKind::Tip => "tip",
+            ExtraKind::Discount => "discount",
+        }
+    }
+}
+
+pub struct NewReceiptExtra {
+    pub kind: ExtraKind,
+    /// Always positive, discounts are subtracted from the items.
+    pub amount: i32,
+}
+
+pub struct NewExpense {
+    pub name: String,
+    pub created_at: Option<chrono::DateTime<Utc>>,
+    pub paid_by: i32,
+    pub total: i32,
+    pub currency: String,
+    pub category_id: Option<i32>,
+    pub is_payment: bool,
+    pub split: Split,
+    /// Defaults to [`DEFAULT_GROUP_ID`] when created and to the current group
+    /// when updated.
+    pub group_id: Option<i32>,
+    pub details: ExpenseDetails,
+}
+
+/// A refund of an expense, paid back to whoever paid the expense.
+pub struct NewRefund {
+    /// Defaults to the name of the refunded expense.
+    pub name: Option<String>,
+    pub created_at: Option<chrono::DateTime<Utc>>,
+    pub amount: RefundAmount,
+}
+
+/// How much of an expense is refunded and to whom.
+pub enum RefundAmount {
+    /// Everything that hasn't been refunded yet.
+    Full,
+    /// Divided between the participants in proportion to what they consumed
+    /// and haven't got back yet.
+    Partial(i32),
+    /// What each participant gets back.
+    PerParticipant(Vec<RefundShare>),
+}
+
+pub struct RefundShare {
+    pub user_id: i32,
+    pub amount: i32,
+}
+
+/// A change made within a caller's transaction, whose events are published by
+/// [`ExpenseService::publish_change`] once that transaction has committed.
+pub struct ExpenseChange {
+    pub expense: ExpenseWithShares,
+    actor_id: i32,
+    action: Action,
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Action::Created => "created",
+            Action::Updated => "updated",
+            Action::Deleted => "deleted",
+        }
+    }
+}
+
+impl ExpenseService {
+    pub fn new(db: Pool<Postgres>, events: EventService) -> Self {
+        Self { db, events }
+    }
+
+    /// Tags in the filter are matched the way they are stored, see
+    /// [`normalize_tag`].
+    pub async fn get_expenses(
+        &self,
+        actor: &User,
+        filter: ExpenseFilter,
+    ) -> Result<Vec<ExpenseWithShares>, ExpenseError> {
+        let filter = ExpenseFilter {
+            tags: filter.tags.iter().map(|tag| normalize_tag(tag)).collect(),
+            ..filter
+        };
+        db::expense::get_expenses(&self.db, actor.id, filter)
+            .await
+            .map_err(ExpenseError::Sqlx)
+    }
+
+    pub async fn get_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let mut conn = self.db.acquire().await.map_err(ExpenseError::Sqlx)?;
+        let expense = db::expense::get_expense(expense_id, &mut conn)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .ok_or(ExpenseError::NotFound(expense_id))?;
+
+        let group_id = expense.0.expense.group_id;
+        let is_member = db::group::is_member(&self.db, group_id, actor.id)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        if !is_member {
+            return Err(ExpenseError::NotMember(actor.id, group_id));
+        }
+
+        Ok(expense)
+    }
+
+    pub async fn get_expense_log(
+        &self,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<Vec<ExpenseLog>, ExpenseError> {
+        self.get_expense(actor, expense_id).await?;
+
+        db::expense_log::get_expense_log(&self.db, expense_id)
+            .await
+            .map_err(ExpenseError::Sqlx)
+    }
+
+    pub async fn create_expense(
+        &self,
+        actor: &User,
+        expense: NewExpense,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let change = self.create_expense_in(&mut tx, actor, expense).await?;
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        self.publish_change(&change).await;
+
+        Ok(change.expense)
+    }
+
+    pub async fn create_expense_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        actor: &User,
+        expense: NewExpense,
+    ) -> Result<ExpenseChange, ExpenseError> {
+        let expense = self.resolve_profile(expense).await?;
+        let to_insert = prepare_expense(expense)?;
+        if !is_participant(actor.id, to_insert.paid_by, &to_insert.shares) {
+            return Err(ExpenseError::Forbidden(actor.id));
+        }
+        self.validate_group(actor, &to_insert).await?;
+        self.ensure_open(to_insert.group_id, to_insert.created_at)
+            .await?;
+
+        let created = db::expense::insert_expense(to_insert, tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        let created = self.require_approvals(tx, actor, created).await?;
+        log_action(tx, actor, created.0.expense.id, Action::Created).await?;
+        enqueue_webhooks(tx, &ledger_event(actor.id, &created, Action::Created)).await?;
+
+        Ok(ExpenseChange {
+            expense: created,
+            actor_id: actor.id,
+            action: Action::Created,
+        })
+    }
+
+    /// Creates all expenses in a single transaction, either all of them are
+    /// stored or none.
+    pub async fn create_expenses(
+        &self,
+        actor: &User,
+        expenses: Vec<NewExpense>,
+    ) -> Result<Vec<ExpenseWithShares>, ExpenseError> {
+        let mut to_insert = Vec::new();
+        for expense in expenses {
+            let expense = self.prepare_expense(actor, expense).await?;
+            if !is_participant(actor.id, expense.paid_by, &expense.shares) {
+                return Err(ExpenseError::Forbidden(actor.id));
+            }
+            to_insert.push(expense);
+        }
+
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let mut created = Vec::new();
+        for expense in to_insert {
+            let expense = db::expense::insert_expense(expense, &mut tx)
+                .await
+                .map_err(ExpenseError::Sqlx)?;
+            let expense = self.require_approvals(&mut tx, actor, expense).await?;
+            log_action(&mut tx, actor, expense.0.expense.id, Action::Created).await?;
+            enqueue_webhooks(&mut tx, &ledger_event(actor.id, &expense, Action::Created)).await?;
+            created.push(expense);
+        }
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        for expense in &created {
+            self.publish(actor.id, expense, Action::Created);
+            self.check_budgets(expense).await;
+        }
+
+        Ok(created)
+    }
+
+    /// Validates an expense without storing it.
+    pub async fn prepare_expense(
+        &self,
+        actor: &User,
+        expense: NewExpense,
+    ) -> Result<InsertExpense, ExpenseError> {
+        let expense = self.resolve_profile(expense).await?;
+        let to_insert = prepare_expense(expense)?;
+        self.validate_group(actor, &to_insert).await?;
+        self.ensure_open(to_insert.group_id, to_insert.created_at)
+            .await?;
+
+        Ok(to_insert)
+    }
+
+    /// Replaces a reference to a split profile, or a missing split of an
+    /// expense that isn't a payment, with the weights of the profile. A
+    /// missing split stays empty when the group has no applicable profile.
+    async fn resolve_profile(&self, mut expense: NewExpense) -> Result<NewExpense, ExpenseError> {
+        let group_id = expense.group_id.unwrap_or(DEFAULT_GROUP_ID);
+        let profile = match &expense.split {
+            Split::Profile(profile_id) => Some(
+                db::split_profile::get_profile(&self.db, *profile_id)
+                    .await
+                    .map_err(ExpenseError::Sqlx)?
+                    .filter(|(profile, _)| profile.group_id == group_id)
+                    .ok_or(ExpenseError::Invalid(format!(
+                        "split profile {} is not available in group {}",
+                        profile_id, group_id
+                    )))?,
+            ),
+            Split::Shares(shares) if shares.is_empty() && !expense.is_payment => {
+                db::split_profile::get_applicable_profile(&self.db, group_id, expense.category_id)
+                    .await
+                    .map_err(ExpenseError::Sqlx)?
+            }
+            _ => None,
+        };
+
+        if let Some((_, weights)) = profile {
+            expense.split = Split::Weighted(
+                weights
+                    .iter()
+                    .filter(|weight| weight.weight > 0)
+                    .map(|weight| SplitWeight {
+                        user_id: weight.user_id,
+                        weight: weight.weight as u32,
+                    })
+                    .collect(),
+            );
+        }
+
+        Ok(expense)
+    }
+
+    /// Creates all expenses in a single transaction on behalf of `actor`,
+    /// who doesn't have to participate in them. Imported history is not
+    /// published and doesn't trigger budget alerts.
+    pub async fn import_expenses(
+        &self,
+        actor: &User,
+        expenses: Vec<NewExpense>,
+    ) -> Result<Vec<ExpenseWithShares>, ExpenseError> {
+        let mut to_insert = Vec::new();
+        for expense in expenses {
+            to_insert.push(self.prepare_expense(actor, expense).await?);
+        }
+
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let mut created = Vec::new();
+        for expense in to_insert {
+            let expense = db::expense::insert_expense(expense, &mut tx)
+                .await
+                .map_err(ExpenseError::Sqlx)?;
+            log_action(&mut tx, actor, expense.0.expense.id, Action::Created).await?;
+            created.push(expense);
+        }
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        Ok(created)
+    }
+
+    pub async fn update_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        expense: NewExpense,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let change = self
+            .update_expense_in(&mut tx, actor, expense_id, expense)
+            .await?;
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        self.publish_change(&change).await;
+
+        Ok(change.expense)
+    }
+
+    pub async fn update_expense_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        actor: &User,
+        expense_id: i32,
+        mut expense: NewExpense,
+    ) -> Result<ExpenseChange, ExpenseError> {
+        let existing = db::expense::get_expense(expense_id, tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .ok_or(ExpenseError::NotFound(expense_id))?;
+        // Only moved to another group when asked to, not to the default one.
+        expense.group_id = expense.group_id.or(Some(existing.0.expense.group_id));
+        let expense = self.resolve_profile(expense).await?;
+        let to_insert = prepare_expense(expense)?;
+        self.ensure_participant(actor, &existing)?;
+        if existing.0.expense.refund_of.is_some() {
+            return Err(ExpenseError::Invalid(
+                "a refund can't be changed, delete it and refund again".to_string(),
+            ));
+        }
+        self.ensure_open(
+            existing.0.expense.group_id,
+            Some(existing.0.expense.created_at),
+        )
+        .await?;
+        self.validate_group(actor, &to_insert).await?;
+        self.ensure_open(to_insert.group_id, to_insert.created_at)
+            .await?;
+
+        let updated = db::expense::update_expense(expense_id, to_insert, tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        // Refunds are split by what was consumed in the group and currency of
+        // the original, which has to stay as it was. Rolled back with the
+        // transaction.
+        if !existing.0.refund_ids.is_empty() && moves_balances(&existing, &updated) {
+            return Err(ExpenseError::Invalid(
+                "the amounts, group and participants of a refunded expense can't be changed"
+                    .to_string(),
+            ));
+        }
+        // Approvals are only asked for again when balances would change.
+        let updated = if moves_balances(&existing, &updated) {
+            self.require_approvals(tx, actor, updated).await?
+        } else {
+            updated
+        };
+        log_action(tx, actor, expense_id, Action::Updated).await?;
+        enqueue_webhooks(tx, &ledger_event(actor.id, &updated, Action::Updated)).await?;
+
+        Ok(ExpenseChange {
+            expense: updated,
+            actor_id: actor.id,
+            action: Action::Updated,
+        })
+    }
+
+    pub async fn delete_expense(&self, actor: &User, expense_id: i32) -> Result<(), ExpenseError> {
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let change = self.delete_expense_in(&mut tx, actor, expense_id).await?;
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        self.publish_change(&change).await;
+
+        Ok(())
+    }
+
+    pub async fn delete_expense_in(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<ExpenseChange, ExpenseError> {
+        let existing = db::expense::get_expense(expense_id, tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .ok_or(ExpenseError::NotFound(expense_id))?;
+        self.ensure_participant(actor, &existing)?;
+        if !existing.0.refund_ids.is_empty() {
+            return Err(ExpenseError::Invalid(
+                "an expense with refunds can't be deleted, delete its refunds first".to_string(),
+            ));
+        }
+        self.ensure_open(
+            existing.0.expense.group_id,
+            Some(existing.0.expense.created_at),
+        )
+        .await?;
+
+        db::expense::delete_expense(expense_id, &mut **tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        log_action(tx, actor, expense_id, Action::Deleted).await?;
+        enqueue_webhooks(tx, &ledger_event(actor.id, &existing, Action::Deleted)).await?;
+
+        Ok(ExpenseChange {
+            expense: existing,
+            actor_id: actor.id,
+            action: Action::Deleted,
+        })
+    }
+
+    /// Refunds an expense in full or in part by creating a linked expense with
+    /// a negative total, which reduces what the participants consumed. No
+    /// participant can get back more than they consumed.
+    pub async fn refund_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        refund: NewRefund,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let original = db::expense::get_expense(expense_id, &mut tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .ok_or(ExpenseError::NotFound(expense_id))?;
+        self.ensure_participant(actor, &original)?;
+        if original.0.expense.is_payment {
+            return Err(ExpenseError::Invalid(
+                "a payment can't be refunded".to_string(),
+            ));
+        }
+        if original.0.expense.refund_of.is_some() {
+            return Err(ExpenseError::Invalid(
+                "a refund can't be refunded".to_string(),
+            ));
+        }
+        // A refund is never held for approval, so neither may what it refunds.
+        if original.0.expense.status != CONFIRMED {
+            return Err(ExpenseError::Invalid(
+                "only a confirmed expense can be refunded".to_string(),
+            ));
+        }
+
+        let refunded = db::expense::get_refunded(&mut *tx, expense_id)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        let remaining: Vec<(i32, i64)> = consumed(&original)
+            .into_iter()
+            .map(|(user_id, consumed)| {
+                let refunded = refunded.get(&user_id).copied().unwrap_or(0);
+                (user_id, consumed - refunded)
+            })
+            .filter(|(_, remaining)| *remaining > 0)
+            .collect();
+        let amounts = refund_amounts(refund.amount, &remaining)?;
+        let total: i64 = amounts.iter().map(|(_, amount)| amount).sum();
+        if total == 0 {
+            return Err(ExpenseError::Invalid(
+                "there is nothing left to refund".to_string(),
+            ));
+        }
+
+        let (expense, _) = &original;
+        let name = match refund.name {
+            Some(name) => name.trim().to_string(),
+            None => expense.expense.name.clone(),
+        };
+        if name.is_empty() {
+            return Err(ExpenseError::Invalid("name must not be empty".to_string()));
+        }
+        // Consuming a negative amount is what gives a participant back their
+        // part of the refund.
+        let consumed = amounts
+            .into_iter()
+            .map(|(user_id, amount)| (user_id, -amount))
+            .collect();
+        let total = -total as i32;
+        let to_insert = InsertExpense {
+            name,
+            created_at: refund.created_at,
+            paid_by: expense.paid_by,
+            total,
+            currency: expense.expense.currency.clone(),
+            category_id: expense.category.as_ref().map(|category| category.id),
+            shares: to_shares(total, expense.paid_by, consumed),
+            is_payment: false,
+            group_id: expense.expense.group_id,
+            receipt: None,
+            details: ExpenseDetails {
+                tags: expense.expense.details.tags.clone(),
+                merchant: expense.expense.details.merchant.clone(),
+                ..Default::default()
+            },
+            refund_of: Some(expense_id),
+        };
+        self.validate_group(actor, &to_insert).await?;
+        self.ensure_open(to_insert.group_id, to_insert.created_at)
+            .await?;
+
+        let created = db::expense::insert_expense(to_insert, &mut tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        log_action(&mut tx, actor, created.0.expense.id, Action::Created).await?;
+        enqueue_webhooks(&mut tx, &ledger_event(actor.id, &created, Action::Created)).await?;
+        db::expense_log::insert_expense_log(
+            &mut *tx,
+            InsertExpenseLog {
+                expense_id,
+                user_id: actor.id,
+                action: "refunded".to_string(),
+            },
+        )
+        .await
+        .map_err(ExpenseError::Sqlx)?;
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        let change = ExpenseChange {
+            expense: created,
+            actor_id: actor.id,
+            action: Action::Created,
+        };
+        self.publish_change(&change).await;
+
+        Ok(change.expense)
+    }
+
+    /// Approves an expense the actor was asked to approve, which confirms it
+    /// once everyone asked has approved.
+    pub async fn approve_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        self.decide(actor, expense_id, APPROVED, None).await
+    }
+
+    /// Disputes an expense the actor was asked to approve. It stays out of
+    /// balances until the actor approves it after all, or it is changed.
+    pub async fn dispute_expense(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        reason: String,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let reason = reason.trim().to_string();
+        if reason.is_empty() {
+            return Err(ExpenseError::Invalid(
+                "a dispute needs a reason".to_string(),
+            ));
+        }
+
+        self.decide(actor, expense_id, DISPUTED, Some(reason)).await
+    }
+
+    async fn decide(
+        &self,
+        actor: &User,
+        expense_id: i32,
+        decision: &str,
+        reason: Option<String>,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let mut tx = self.db.begin().await.map_err(ExpenseError::Sqlx)?;
+        let existing = db::expense::get_expense(expense_id, &mut tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .ok_or(ExpenseError::NotFound(expense_id))?;
+        self.ensure_open(
+            existing.0.expense.group_id,
+            Some(existing.0.expense.created_at),
+        )
+        .await?;
+
+        let is_approver =
+            db::expense_approval::decide(&mut tx, expense_id, actor.id, decision, reason.clone())
+                .await
+                .map_err(ExpenseError::Sqlx)?;
+        if !is_approver {
+            return Err(ExpenseError::Invalid(format!(
+                "expense {} doesn't await approval by user {}",
+                expense_id, actor.id
+            )));
+        }
+        db::expense_log::insert_expense_log(
+            &mut *tx,
+            InsertExpenseLog {
+                expense_id,
+                user_id: actor.id,
+                action: decision.to_string(),
+            },
+        )
+        .await
+        .map_err(ExpenseError::Sqlx)?;
+
+        let decided = db::expense::get_expense(expense_id, &mut tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .expect("Failed to fetch after decision");
+
+        let group_id = decided.0.expense.group_id;
+        let ledger_event = if let Some(reason) = reason {
+            Some(LedgerEvent::ExpenseDisputed {
+                expense_id,
+                group_id,
+                actor_id: actor.id,
+                participant_ids: participant_ids(&decided),
+                reason,
+            })
+        } else if decided.0.expense.status == CONFIRMED && existing.0.expense.status != CONFIRMED {
+            Some(LedgerEvent::ExpenseConfirmed {
+                expense_id,
+                group_id,
+                actor_id: actor.id,
+                participant_ids: participant_ids(&decided),
+            })
+        } else {
+            None
+        };
+        if let Some(ledger_event) = &ledger_event {
+            enqueue_webhooks(&mut tx, ledger_event).await?;
+        }
+        tx.commit().await.map_err(ExpenseError::Sqlx)?;
+
+        if let Some(ledger_event) = ledger_event {
+            self.events.publish(ledger_event);
+        }
+
+        Ok(decided)
+    }
+
+    /// Asks the participants of an expense above its group's approval
+    /// threshold to approve it, except for `actor` and guests, who can't.
+    /// Confirms the expense when no approval is needed.
+    async fn require_approvals(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        actor: &User,
+        expense: ExpenseWithShares,
+    ) -> Result<ExpenseWithShares, ExpenseError> {
+        let group = db::group::get_group(&mut **tx, expense.0.expense.group_id)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        let needs_approval = !expense.0.expense.is_payment
+            && group
+                .and_then(|group| group.approval_threshold)
+                .is_some_and(|threshold| expense.0.expense.total > threshold);
+        if !needs_approval && expense.0.approvals.is_empty() {
+            return Ok(expense);
+        }
+
+        let mut approver_ids = Vec::new();
+        if needs_approval {
+            for user_id in participant_ids(&expense) {
+                if user_id == actor.id {
+                    continue;
+                }
+                let is_guest = db::user::get_user_by_id(&self.db, user_id)
+                    .await
+                    .map_err(ExpenseError::Sqlx)?
+                    .is_none_or(|user| user.is_guest);
+                if !is_guest {
+                    approver_ids.push(user_id);
+                }
+            }
+        }
+
+        let expense_id = expense.0.expense.id;
+        db::expense_approval::require_approvals(tx, expense_id, &approver_ids)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+
+        Ok(db::expense::get_expense(expense_id, tx)
+            .await
+            .map_err(ExpenseError::Sqlx)?
+            .expect("Failed to fetch after requiring approvals"))
+    }
+
+    /// Publishes the events of a committed change and checks the budgets it
+    /// affects.
+    pub async fn publish_change(&self, change: &ExpenseChange) {
+        self.publish(change.actor_id, &change.expense, change.action);
+        if !matches!(change.action, Action::Deleted) {
+            self.check_budgets(&change.expense).await;
+        }
+    }
+
+    /// Makes sure `actor` and every participant belong to the group of the
+    /// expense and that its categories, including those of its items, are
+    /// available within that group.
+    async fn validate_group(
+        &self,
+        actor: &User,
+        expense: &InsertExpense,
+    ) -> Result<(), ExpenseError> {
+        let is_member = db::group::is_member(&self.db, expense.group_id, actor.id)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        if !is_member {
+            return Err(ExpenseError::NotMember(actor.id, expense.group_id));
+        }
+
+        let mut participant_ids: Vec<i32> = std::iter::once(expense.paid_by)
+            .chain(expense.shares.iter().map(|share| share.user_id))
+            .chain(
+                expense
+                    .receipt
+                    .iter()
+                    .flat_map(|receipt| receipt.items.iter())
+                    .flat_map(|item| item.shares.iter().map(|share| share.user_id)),
+            )
+            .collect();
+        participant_ids.sort_unstable();
+        participant_ids.dedup();
+        let non_members = db::group::get_non_members(&self.db, expense.group_id, &participant_ids)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        if let Some(user_id) = non_members.first() {
+            return Err(ExpenseError::Invalid(format!(
+                "user {} is not a member of group {}",
+                user_id, expense.group_id
+            )));
+        }
+
+        let item_category_ids = expense
+            .receipt
+            .iter()
+            .flat_map(|receipt| receipt.items.iter())
+            .filter_map(|item| item.category_id);
+        let mut category_ids: Vec<i32> = expense.category_id.into_iter().collect();
+        for category_id in item_category_ids {
+            if !category_ids.contains(&category_id) {
+                category_ids.push(category_id);
+            }
+        }
+
+        for category_id in category_ids {
+            let category = db::expense_category::get_expense_category(&self.db, category_id)
+                .await
+                .map_err(ExpenseError::Sqlx)?;
+            let is_visible = category.is_some_and(|category| {
+                category
+                    .group_id
+                    .is_none_or(|group_id| group_id == expense.group_id)
+            });
+            if !is_visible {
+                return Err(ExpenseError::Invalid(format!(
+                    "category {} is not available in group {}",
+                    category_id, expense.group_id
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure an expense created at `created_at`, or now without it, is
+    /// not part of a closed period of the group.
+    async fn ensure_open(
+        &self,
+        group_id: i32,
+        created_at: Option<chrono::DateTime<Utc>>,
+    ) -> Result<(), ExpenseError> {
+        let latest = db::period_close::get_latest_close(&self.db, group_id, None)
+            .await
+            .map_err(ExpenseError::Sqlx)?;
+        match latest {
+            Some(latest) if created_at.unwrap_or(Utc::now()) < latest.closed_until => {
+                Err(ExpenseError::Closed(group_id, latest.closed_until))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Budget alerts are a side effect of the expense, so failing to check
+    /// them shouldn't fail the expense itself.
+    async fn check_budgets(&self, expense: &ExpenseWithShares) {
+        let budget_service = BudgetService::new(self.db.clone(), self.events.clone());
+        if let Err(budget_error) = budget_service.check_alerts(expense).await {
+            event!(Level::ERROR, %budget_error, "Failed to check budget alerts");
+        }
+    }
+
+    fn ensure_participant(
+        &self,
+        actor: &User,
+        (expense, shares): &ExpenseWithShares,
+    ) -> Result<(), ExpenseError> {
+        let is_participant =
+            expense.paid_by == actor.id || shares.iter().any(|share| share.user_id == actor.id);
+
+        if is_participant {
+            Ok(())
+        } else {
+            Err(ExpenseError::Forbidden(actor.id))
+        }
+    }
+
+    fn publish(&self, actor_id: i32, changed: &ExpenseWithShares, action: Action) {
+        self.events.publish(ledger_event(actor_id, changed, action));
+    }
+}
+
+/// The event published for a change, and delivered to webhooks.
+fn ledger_event(actor_id: i32, changed: &ExpenseWithShares, action: Action) -> LedgerEvent {
+    let (expense, shares) = changed;
+    let expense_id = expense.expense.id;
+    let group_id = expense.expense.group_id;
+    let participant_ids = participant_ids(changed);
+
+    match action {
+        Action::Created if expense.expense.is_payment => {
+            let receiver_id = shares
+                .iter()
+                .find(|share| share.share < 0)
+                .map(|share| share.user_id)
+                .unwrap_or(expense.paid_by);
+
+            LedgerEvent::SettlementRecorded {
+                expense_id,
+                group_id,
+                actor_id,
+                payer_id: expense.paid_by,
+                receiver_id,
+                amount: expense.expense.total,
+                currency: expense.expense.currency.clone(),
+            }
+        }
+        Action::Created => LedgerEvent::ExpenseCreated {
+            expense_id,
+            group_id,
+            actor_id,
+            participant_ids,
+        },
+        Action::Updated => LedgerEvent::ExpenseUpdated {
+            expense_id,
+            group_id,
+            actor_id,
+            participant_ids,
+        },
+        Action::Deleted => LedgerEvent::ExpenseDeleted {
+            expense_id,
+            group_id,
+            actor_id,
+            participant_ids,
+        },
+    }
+}
+
+async fn enqueue_webhooks(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    ledger_event: &LedgerEvent,
+) -> Result<(), ExpenseError> {
+    webhook_service::enqueue(tx, ledger_event)
+        .await
+        .map_err(ExpenseError::Sqlx)
+}
+
+async fn log_action(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    actor: &User,
+    expense_id: i32,
+    action: Action,
+) -> Result<(), ExpenseError> {
+    db::expense_log::insert_expense_log(
+        &mut **tx,
+        InsertExpenseLog {
+            expense_id,
+            user_id: actor.id,
+            action: action.as_str().to_string(),
+        },
+    )
+    .await
+    .map_err(ExpenseError::Sqlx)?;
+
+    Ok(())
+}
+
+/// Everyone with a share in the expense, and whoever paid it.
+fn participant_ids((expense, shares): &ExpenseWithShares) -> Vec<i32> {
+    let mut participant_ids: Vec<i32> = shares.iter().map(|share| share.user_id).collect();
+    if !participant_ids.contains(&expense.paid_by) {
+        participant_ids.push(expense.paid_by);
+    }
+
+    participant_ids
+}
+
+/// What every participant consumed of the expense, i.e. what they paid minus
+/// their share.
+fn consumed((expense, shares): &ExpenseWithShares) -> Vec<(i32, i64)> {
+    let total = expense.expense.total as i64;
+    let mut consumed: Vec<(i32, i64)> = shares
+        .iter()
+        .map(|share| {
+            let paid = if share.user_id == expense.paid_by {
+                total
+            } else {
+                0
+            };
+            (share.user_id, paid - share.share as i64)
+        })
+        .collect();
+    if !shares.iter().any(|share| share.user_id == expense.paid_by) {
+        consumed.push((expense.paid_by, total));
+    }
+
+    consumed
+}
+
+/// What each participant gets back, given what they can get back at most.
+fn refund_amounts(
+    amount: RefundAmount,
+    remaining: &[(i32, i64)],
+) -> Result<Vec<(i32, i64)>, ExpenseError> {
+    let remaining_total: i64 = remaining.iter().map(|(_, remaining)| remaining).sum();
+    match amount {
+        RefundAmount::Full => Ok(remaining.to_vec()),
+        RefundAmount::Partial(amount) => {
+            if amount <= 0 {
+                return Err(ExpenseError::Invalid(
+                    "a refund must be positive".to_string(),
+                ));
+            }
+            if amount as i64 > remaining_total {
+                return Err(ExpenseError::Invalid(format!(
+                    "at most {} can be refunded",
+                    remaining_total
+                )));
+            }
+
+            let weights: Vec<i64> = remaining.iter().map(|(_, remaining)| *remaining).collect();
+            Ok(remaining
+                .iter()
+                .zip(allocate(amount as i64, &weights))
+                .map(|((user_id, _), amount)| (*user_id, amount))
+                .collect())
+        }
+        RefundAmount::PerParticipant(shares) => {
+            let mut amounts: Vec<(i32, i64)> = Vec::new();
+            for share in shares {
+                if share.amount < 0 {
+                    return Err(ExpenseError::Invalid(format!(
+                        "the refund of user {} must not be negative",
+                        share.user_id
+                    )));
+                }
+                if amounts.iter().any(|(user_id, _)| *user_id == share.user_id) {
+                    return Err(ExpenseError::Invalid(format!(
+                        "user {} is refunded more than once",
+                        share.user_id
+                    )));
+                }
+                let remaining = remaining
+                    .iter()
+                    .find(|(user_id, _)| *user_id == share.user_id)
+                    .map_or(0, |(_, remaining)| *remaining);
+                if share.amount as i64 > remaining {
+                    return Err(ExpenseError::Invalid(format!(
+                        "user {} can get back at most {}",
+                        share.user_id, remaining
+                    )));
+                }
+                amounts.push((share.user_id, share.amount as i64));
+            }
+
+            Ok(amounts
+                .into_iter()
+                .filter(|(_, amount)| *amount > 0)
+                .collect())
+        }
+    }
+}
+
+/// Whether the change of an expense changes anyone's balance.
+fn moves_balances(before: &ExpenseWithShares, after: &ExpenseWithShares) -> bool {
+    let shares = |(_, shares): &ExpenseWithShares| {
+        let mut shares: Vec<(i32, i32)> = shares
+            .iter()
+            .map(|share| (share.user_id, share.share))
+            .collect();
+        shares.sort();
+        shares
+    };
+
+    before.0.expense.total != after.0.expense.total
+        || before.0.expense.currency != after.0.expense.currency
+        || before.0.expense.group_id != after.0.expense.group_id
+        || before.0.paid_by != after.0.paid_by
+        || shares(before) != shares(after)
+}
+
+fn is_participant(user_id: i32, paid_by: i32, shares: &[InsertAccountShare]) -> bool {
+    paid_by == user_id || shares.iter().any(|share| share.user_id == user_id)
+}
+
+/// Validates the expense and resolves its split into signed shares.
+fn prepare_expense(expense: NewExpense) -> Result<InsertExpense, ExpenseError> {
+    let name = expense.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ExpenseError::Invalid("name must not be empty".to_string()));
+    }
+    if expense.total <= 0 {
+        return Err(ExpenseError::Invalid("total must be positive".to_string()));
+    }
+    if expense.currency.len() != 3 || !expense.currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ExpenseError::Invalid(format!(
+            "'{}' is not a valid currency code",
+            expense.currency
+        )));
+    }
+
+    let (shares, receipt) = match expense.split {
+        Split::Shares(shares) => (shares, None),
+        Split::Weighted(weights) => (
+            split_by_weight(expense.total, expense.paid_by, &weights)?,
+            None,
+        ),
+        Split::Itemised(_) if expense.is_payment => {
+            return Err(ExpenseError::Invalid(
+                "a payment can't be itemised".to_string(),
+            ));
+        }
+        Split::Itemised(receipt) => {
+            let receipt = split_receipt(expense.total, receipt)?;
+            let shares = receipt_shares(expense.total, expense.paid_by, &receipt);
+            (shares, Some(receipt))
+        }
+        Split::Profile(profile_id) => {
+            return Err(ExpenseError::Invalid(format!(
+                "split profile {} has not been resolved",
+                profile_id
+            )));
+        }
+    };
+    validate_shares(&shares)?;
+
+    if expense.is_payment {
+        let [a, b] = shares.as_slice() else {
+            return Err(ExpenseError::Invalid(
+                "a payment must have exactly two shares".to_string(),
+            ));
+        };
+        let payer_share = if a.user_id == expense.paid_by { a } else { b };
+        if payer_share.user_id != expense.paid_by || payer_share.share != expense.total {
+            return Err(ExpenseError::Invalid(
+                "a payment must move the total from the payer to the receiver".to_string(),
+            ));
+        }
+    }
+
+    Ok(InsertExpense {
+        name,
+        created_at: expense.created_at,
+        paid_by: expense.paid_by,
+        total: expense.total,
+        currency: expense.currency,
+        category_id: expense.category_id,
+        shares,
+        is_payment: expense.is_payment,
+        group_id: expense.group_id.unwrap_or(DEFAULT_GROUP_ID),
+        receipt,
+        details: validate_details(expense.details)?,
+        refund_of: None,
+    })
+}
+
+/// Tags are stored trimmed, lowercase and without a leading `#`.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim().to_lowercase()
+}
+
+fn validate_details(details: ExpenseDetails) -> Result<ExpenseDetails, ExpenseError> {
+    let note = validate_text("note", details.note, MAX_NOTE_LENGTH)?;
+    let merchant = validate_text("merchant", details.merchant, MAX_NAME_LENGTH)?;
+    let place = validate_text("place", details.place, MAX_NAME_LENGTH)?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in details.tags.iter().map(|tag| normalize_tag(tag)) {
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(ExpenseError::Invalid(format!(
+                "tag '{}' is longer than {} characters",
+                tag, MAX_TAG_LENGTH
+            )));
+        }
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(ExpenseError::Invalid(format!(
+            "an expense can have at most {} tags",
+            MAX_TAGS
+        )));
+    }
+
+    match (details.latitude, details.longitude) {
+        (Some(latitude), Some(longitude)) => {
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(ExpenseError::Invalid(format!(
+                    "{}, {} is not a valid location",
+                    latitude, longitude
+                )));
+            }
+        }
+        (None, None) => {}
+        _ => {
+            return Err(ExpenseError::Invalid(
+                "a location needs both latitude and longitude".to_string(),
+            ));
+        }
+    }
+
+    Ok(ExpenseDetails {
+        note,
+        tags,
+        merchant,
+        place,
+        latitude: details.latitude,
+        longitude: details.longitude,
+    })
+}
+
+/// Trims the text, treating blank text as missing.
+fn validate_text(
+    field: &str,
+    text: Option<String>,
+    max_length: usize,
+) -> Result<Option<String>, ExpenseError> {
+    let text = text
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+    if text
+        .as_ref()
+        .is_some_and(|text| text.chars().count() > max_length)
+    {
+        return Err(ExpenseError::Invalid(format!(
+            "{} must be at most {} characters",
+            field, max_length
+        )));
+    }
+
+    Ok(text)
+}
+
+fn validate_shares(shares: &[InsertAccountShare]) -> Result<(), ExpenseError> {
+    if shares.is_empty() {
+        return Err(ExpenseError::Invalid(
+            "shares must not be empty".to_string(),
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = shares.iter().find(|share| !seen.insert(share.user_id)) {
+        return Err(ExpenseError::Invalid(format!(
+            "user {} has more than one share",
+            duplicate.user_id
+        )));
+    }
+
+    let sum: i64 = shares.iter().map(|share| share.share as i64).sum();
+    if sum != 0 {
+        return Err(ExpenseError::Invalid(format!(
+            "shares must sum to zero, got {}",
+            sum
+        )));
+    }
+
+    Ok(())
+}
+
+/// Splits `total` proportionally to the weights, so that the shares always
+/// sum to zero.
+pub fn split_by_weight(
+    total: i32,
+    paid_by: i32,
+    weights: &[SplitWeight],
+) -> Result<Vec<InsertAccountShare>, ExpenseError> {
+    let weight_sum: i64 = weights.iter().map(|weight| weight.weight as i64).sum();
+    if weight_sum == 0 {
+        return Err(ExpenseError::Invalid(
+            "split weights must not all be zero".to_string(),
+        ));
+    }
+
+    let consumed = allocate(
+        total as i64,
+        &weights
+            .iter()
+            .map(|weight| weight.weight as i64)
+            .collect::<Vec<_>>(),
+    );
+
+    Ok(to_shares(
+        total,
+        paid_by,
+        weights
+            .iter()
+            .map(|weight| weight.user_id)
+            .zip(consumed)
+            .collect(),
+    ))
+}
+
+/// Validates the receipt and works out what every participant consumed of
+/// each item.
+fn split_receipt(total: i32, receipt: NewReceipt) -> Result<InsertReceipt, ExpenseError> {
+    if receipt.items.is_empty() {
+        return Err(ExpenseError::Invalid(
+            "an itemised expense must have items".to_string(),
+        ));
+    }
+    for item in &receipt.items {
+        let name = item.name.trim();
+        if name.is_empty() {
+            return Err(ExpenseError::Invalid(
+                "item names must not be empty".to_string(),
+            ));
+        }
+        if item.amount <= 0 {
+            return Err(ExpenseError::Invalid(format!(
+                "the amount of item '{}' must be positive",
+                name
+            )));
+        }
+        if item.weights.iter().all(|weight| weight.weight == 0) {
+            return Err(ExpenseError::Invalid(format!(
+                "item '{}' must have a participant",
+                name
+            )));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = item.weights.iter().find(|w| !seen.insert(w.user_id)) {
+            return Err(ExpenseError::Invalid(format!(
+                "user {} is listed more than once on item '{}'",
+                duplicate.user_id, name
+            )));
+        }
+    }
+    if receipt.extras.iter().any(|extra| extra.amount <= 0) {
+        return Err(ExpenseError::Invalid(
+            "extras must be positive, discounts are subtracted".to_string(),
+        ));
+    }
+
+    let subtotal: i64 = receipt.items.iter().map(|item| item.amount as i64).sum();
+    let extras: i64 = receipt
+        .extras
+        .iter()
+        .map(|extra| match extra.kind {
+            ExtraKind::Discount => -(extra.amount as i64),
+            ExtraKind::Tax | ExtraKind::Tip => extra.amount as i64,
+        })
+        .sum();
+    if subtotal + extras != total as i64 {
+        return Err(ExpenseError::Invalid(format!(
+            "items and extras sum to {}, not the total {}",
+            subtotal + extras,
+            total
+        )));
+    }
+
+    let item_extras = allocate(
+        extras,
+        &receipt
+            .items
+            .iter()
+            .map(|item| item.amount as i64)
+            .collect::<Vec<_>>(),
+    );
+
+    let items = receipt
+        .items
+        .into_iter()
+        .zip(item_extras)
+        .map(|(item, extra)| {
+            let consumed = allocate(
+                item.amount as i64 + extra,
+                &item
+                    .weights
+                    .iter()
+                    .map(|weight| weight.weight as i64)
+                    .collect::<Vec<_>>(),
+            );
+
+            InsertExpenseItem {
+                name: item.name.trim().to_string(),
+                amount: item.amount,
+                category_id: item.category_id,
+                shares: item
+                    .weights
+                    .iter()
+                    .zip(consumed)
+                    .map(|(weight, consumed)| InsertExpenseItemShare {
+                        user_id: weight.user_id,
+                        weight: weight.weight as i32,
+                        consumed: consumed as i32,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Ok(InsertReceipt {
+        items,
+        extras: receipt
+            .extras
+            .iter()
+            .map(|extra| InsertExpenseExtra {
+                kind: extra.kind.as_str().to_string(),
+                amount: extra.amount,
+            })
+            .collect(),
+    })
+}
+
+/// Sums up what every participant consumed over all items of the receipt.
+fn receipt_shares(total: i32, paid_by: i32, receipt: &InsertReceipt) -> Vec<InsertAccountShare> {
+    let mut consumed: Vec<(i32, i64)> = Vec::new();
+    for share in receipt.items.iter().flat_map(|item| item.shares.iter()) {
+        match consumed
+            .iter_mut()
+            .find(|(user_id, _)| *user_id == share.user_id)
+        {
+            Some((_, sum)) => *sum += share.consumed as i64,
+            None => consumed.push((share.user_id, share.consumed as i64)),
+        }
+    }
+
+    to_shares(total, paid_by, consumed)
+}
+
+/// Turns what each user consumed into signed shares, crediting the payer with
+/// the total.
+fn to_shares(total: i32, paid_by: i32, consumed: Vec<(i32, i64)>) -> Vec<InsertAccountShare> {
+    let total = total as i64;
+    let mut shares: Vec<InsertAccountShare> = consumed
+        .iter()
+        .map(|(user_id, consumed)| {
+            let paid = if *user_id == paid_by { total } else { 0 };
+            InsertAccountShare {
+                user_id: *user_id,
+                share: (paid - consumed) as i32,
+            }
+        })
+        .collect();
+
+    if !consumed.iter().any(|(user_id, _)| *user_id == paid_by) {
+        shares.push(InsertAccountShare {
+            user_id: paid_by,
+            share: total as i32,
+        });
+    }
+
+    shares
+}
+
+/// Divides `amount` proportionally to the non-negative `weights`, handing out
+/// the rounding remainder one unit at a time to the largest fractional parts
+/// so that the parts always sum to `amount`. The weights must not all be zero.
+fn allocate(amount: i64, weights: &[i64]) -> Vec<i64> {
+    if amount < 0 {
+        return allocate(-amount, weights)
+            .into_iter()
+            .map(|part| -part)
+            .collect();
+    }
+
+    let weight_sum: i64 = weights.iter().sum();
+    let mut parts: Vec<i64> = weights
+        .iter()
+        .map(|weight| amount * weight / weight_sum)
+        .collect();
+
+    let mut remainders: Vec<(usize, i64)> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| (i, amount * weight % weight_sum))
+        .collect();
+    remainders.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let left_over = amount - parts.iter().sum::<i64>();
+    for (i, _) in remainders.iter().take(left_over as usize) {
+        parts[*i] += 1;
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weights(weights: &[(i32, u32)]) -> Vec<SplitWeight> {
+        weights
+            .iter()
+            .map(|&(user_id, weight)| SplitWeight { user_id, weight })
+            .collect()
+    }
+
+    fn item(name: &str, amount: i32, item_weights: &[(i32, u32)]) -> NewReceiptItem {
+        NewReceiptItem {
+            name: name.to_string(),
+            amount,
+            category_id: None,
+            weights: weights(item_weights),
+        }
+    }
+
+    fn pairs(shares: &[InsertAccountShare]) -> Vec<(i32, i32)> {
+        shares
+            .iter()
+            .map(|share| (share.user_id, share.share))
+            .collect()
+    }
+
+    fn sum(shares: &[InsertAccountShare]) -> i64 {
+        shares.iter().map(|share| share.share as i64).sum()
+    }
+
+    #[test]
+    fn allocate_splits_exactly_divisible_amounts() {
+        assert_eq!(allocate(100, &[1, 2, 3, 4]), vec![10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn allocate_hands_the_remainder_to_the_largest_fractions() {
+        assert_eq!(allocate(100, &[1, 1, 1]), vec![34, 33, 33]);
+        // 2/3 and 1/3 of 10, the larger fraction gets the extra unit.
+        assert_eq!(allocate(10, &[2, 1]), vec![7, 3]);
+        // Ties go to the earliest weight.
+        assert_eq!(allocate(2, &[1, 1, 1]), vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn allocate_skips_zero_weights() {
+        assert_eq!(allocate(7, &[0, 1, 1]), vec![0, 4, 3]);
+    }
+
+    #[test]
+    fn allocate_mirrors_negative_amounts() {
+        assert_eq!(allocate(-100, &[1, 1, 1]), vec![-34, -33, -33]);
+    }
+
+    #[test]
+    fn allocate_always_sums_to_the_amount() {
+        for amount in [-1001, -1, 0, 1, 99, 12345] {
+            for parts in [
+                vec![1],
+                vec![3, 7],
+                vec![1, 1, 1, 1, 1, 1, 1],
+                vec![5, 0, 2],
+            ] {
+                assert_eq!(allocate(amount, &parts).iter().sum::<i64>(), amount);
+            }
+        }
+    }
+
+    #[test]
+    fn split_by_weight_credits_the_payer() {
+        let shares = split_by_weight(100, 1, &weights(&[(1, 1), (2, 1), (3, 1)])).unwrap();
+        assert_eq!(pairs(&shares), vec![(1, 66), (2, -33), (3, -33)]);
+        assert_eq!(sum(&shares), 0);
+    }
+
+    #[test]
+    fn split_by_weight_adds_a_payer_who_is_not_a_participant() {
+        let shares = split_by_weight(100, 9, &weights(&[(1, 1), (2, 1)])).unwrap();
+        assert_eq!(pairs(&shares), vec![(1, -50), (2, -50), (9, 100)]);
+        assert_eq!(sum(&shares), 0);
+    }
+
+    #[test]
+    fn split_by_weight_handles_negative_totals() {
+        let shares = split_by_weight(-101, 1, &weights(&[(1, 1), (2, 1)])).unwrap();
+        assert_eq!(pairs(&shares), vec![(1, -50), (2, 50)]);
+        assert_eq!(sum(&shares), 0);
+    }
+
+    #[test]
+    fn split_by_weight_rejects_all_zero_weights() {
+        assert!(matches!(
+            split_by_weight(100, 1, &weights(&[(1, 0), (2, 0)])),
+            Err(ExpenseError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn to_shares_gives_a_payer_who_consumed_everything_nothing() {
+        let shares = to_shares(100, 1, vec![(1, 100)]);
+        assert_eq!(pairs(&shares), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn to_shares_sum_to_zero_when_the_consumption_sums_to_the_total() {
+        let shares = to_shares(100, 2, vec![(1, 25), (2, 25), (3, 50)]);
+        assert_eq!(pairs(&shares), vec![(1, -25), (2, 75), (3, -50)]);
+        assert_eq!(sum(&shares), 0);
+    }
+
+    #[test]
+    fn split_receipt_spreads_extras_over_the_items() {
+        let receipt = split_receipt(
+            105,
+            NewReceipt {
+                items: vec![
+                    item("Pizza", 60, &[(1, 1), (2, 1)]),
+                    item("Wine", 40, &[(2, 1)]),
+                ],
+                extras: vec![
+                    NewReceiptExtra {
+                        kind: ExtraKind::Tip,
+                        amount: 10,
+                    },
+                    NewReceiptExtra {
+                        kind: ExtraKind::Discount,
+                        amount: 5,
+                    },
+                ],
+            },
+        )
+        .unwrap();
+
+        // The 5 in net extras are split 3/2 by item amount.
+        let consumed: Vec<Vec<(i32, i32)>> = receipt
+            .items
+            .iter()
+            .map(|item| {
+                item.shares
+                    .iter()
+                    .map(|share| (share.user_id, share.consumed))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(consumed, vec![vec![(1, 32), (2, 31)], vec![(2, 42)]]);
+
+        let shares = receipt_shares(105, 1, &receipt);
+        assert_eq!(pairs(&shares), vec![(1, 73), (2, -73)]);
+        assert_eq!(sum(&shares), 0);
+    }
+
+    #[test]
+    fn split_receipt_rejects_items_that_do_not_add_up() {
+        let receipt = NewReceipt {
+            items: vec![item("Pizza", 60, &[(1, 1)])],
+            extras: vec![],
+        };
+        assert!(matches!(
+            split_receipt(100, receipt),
+            Err(ExpenseError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn split_receipt_rejects_invalid_items() {
+        for invalid in [
+            item("Pizza", 0, &[(1, 1)]),
+            item("Pizza", -10, &[(1, 1)]),
+            item(" ", 10, &[(1, 1)]),
+            item("Pizza", 10, &[(1, 0)]),
+            item("Pizza", 10, &[(1, 1), (1, 2)]),
+        ] {
+            let receipt = NewReceipt {
+                items: vec![invalid],
+                extras: vec![],
+            };
+            assert!(matches!(
+                split_receipt(10, receipt),
+                Err(ExpenseError::Invalid(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use futures::{SinkExt, Stream, StreamExt};
+use sqlx::{Pool, Postgres};
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    export::{ExportFilter, ExportRow},
+    user::User,
+};
+
+use super::amount::format_amount;
+
+#[derive(Debug, Clone)]
+pub struct ExportService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid export: {0}")]
+    Invalid(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportColumn {
+    ExpenseId,
+    Date,
+    Name,
+    Category,
+    ParentCategory,
+    PaidBy,
+    Total,
+    Currency,
+    /// One of `expense`, `payment` or `refund`.
+    Type,
+    /// The participant of a share.
+    User,
+    /// The participant's share, negative when the participant owes.
+    Share,
+    /// What the participant paid for the expense.
+    Paid,
+    /// The participant's part of the costs, always zero for payments.
+    Consumed,
+}
+
+impl ExportColumn {
+    pub const EXPENSE_DEFAULTS: &'static [ExportColumn] = &[
+        ExportColumn::Date,
+        ExportColumn::Name,
+        ExportColumn::Category,
+        ExportColumn::PaidBy,
+        ExportColumn::Total,
+        ExportColumn::Currency,
+        ExportColumn::Type,
+    ];
+
+    pub const SHARE_DEFAULTS: &'static [ExportColumn] = &[
+        ExportColumn::Date,
+        ExportColumn::Name,
+        ExportColumn::Category,
+        ExportColumn::PaidBy,
+        ExportColumn::Total,
+        ExportColumn::Currency,
+        ExportColumn::Type,
+        ExportColumn::User,
+        ExportColumn::Paid,
+        ExportColumn::Consumed,
+    ];
+
+    pub fn parse(column: &str) -> Option<Self> {
+        match column {
+            "expense_id" => Some(ExportColumn::ExpenseId),
+            "date" => Some(ExportColumn::Date),
+            "name" => Some(ExportColumn::Name),
+            "category" => Some(ExportColumn::Category),
+            "parent_category" => Some(ExportColumn::ParentCategory),
+            "paid_by" => Some(ExportColumn::PaidBy),
+            "total" => Some(ExportColumn::Total),
+            "currency" => Some(ExportColumn::Currency),
+            "type" => Some(ExportColumn::Type),
+            "user" => Some(ExportColumn::User),
+            "share" => Some(ExportColumn::Share),
+            "paid" => Some(ExportColumn::Paid),
+            "consumed" => Some(ExportColumn::Consumed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportColumn::ExpenseId => "expense_id",
+            ExportColumn::Date => "date",
+            ExportColumn::Name => "name",
+            ExportColumn::Category => "category",
+            ExportColumn::ParentCategory => "parent_category",
+            ExportColumn::PaidBy => "paid_by",
+            ExportColumn::Total => "total",
+            ExportColumn::Currency => "currency",
+            ExportColumn::Type => "type",
+            ExportColumn::User => "user",
+            ExportColumn::Share => "share",
+            ExportColumn::Paid => "paid",
+            ExportColumn::Consumed => "consumed",
+        }
+    }
+
+    fn is_share_column(&self) -> bool {
+        matches!(
+            self,
+            ExportColumn::User | ExportColumn::Share | ExportColumn::Paid | ExportColumn::Consumed
+        )
+    }
+}
+
+/// Decides how numbers are written and which delimiter keeps them apart.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportLocale {
+    /// Decimal point and comma separated values.
+    En,
+    /// Decimal comma and semicolon separated values, as Swedish spreadsheet
+    /// programs expect.
+    Sv,
+}
+
+impl ExportLocale {
+    fn decimal_separator(&self) -> char {
+        match self {
+            ExportLocale::En => '.',
+            ExportLocale::Sv => ',',
+        }
+    }
+
+    fn delimiter(&self) -> u8 {
+        match self {
+            ExportLocale::En => b',',
+            ExportLocale::Sv => b';',
+        }
+    }
+}
+
+pub struct ExportOptions {
+    pub filter: ExportFilter,
+    pub columns: Vec<ExportColumn>,
+    pub locale: ExportLocale,
+    /// Writes a byte order mark and CRLF line endings so that Excel opens the
+    /// file with the right encoding.
+    pub excel: bool,
+}
+
+impl ExportService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Streams the expenses of a group as CSV, one line per item. Rows are
+    /// read from the database while the response is being written, so large
+    /// groups never have to fit in memory.
+    pub async fn export_csv(
+        &self,
+        actor: &User,
+        options: ExportOptions,
+    ) -> Result<impl Stream<Item = Result<String, sqlx::Error>>, ExportError> {
+        if options.filter.from >= options.filter.to {
+            return Err(ExportError::Invalid("from must be before to".to_string()));
+        }
+        if options.columns.is_empty() {
+            return Err(ExportError::Invalid(
+                "at least one column is required".to_string(),
+            ));
+        }
+        if let Some(column) = options
+            .columns
+            .iter()
+            .find(|column| column.is_share_column() && !options.filter.per_share)
+        {
+            return Err(ExportError::Invalid(format!(
+                "column {} requires one row per share",
+                column.as_str()
+            )));
+        }
+        let is_member = db::group::is_member(&self.db, options.filter.group_id, actor.id)
+            .await
+            .map_err(ExportError::Sqlx)?;
+        if !is_member {
+            return Err(ExportError::NotMember(actor.id, options.filter.group_id));
+        }
+
+        let (mut sender, receiver) = futures::channel::mpsc::channel(64);
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let mut header = if options.excel {
+                "\u{feff}".to_string()
+            } else {
+                String::new()
+            };
+            header.push_str(&write_record(
+                options
+                    .columns
+                    .iter()
+                    .map(|column| column.as_str().to_string()),
+                &options,
+            ));
+            if sender.send(Ok(header)).await.is_err() {
+                return;
+            }
+
+            let mut rows = db::export::get_export_rows(&db, &options.filter);
+            while let Some(row) = rows.next().await {
+                let line = row.map(|row| {
+                    write_record(
+                        options
+                            .columns
+                            .iter()
+                            .map(|column| format_column(&row, *column, options.locale)),
+                        &options,
+                    )
+                });
+                if let Err(err) = &line {
+                    event!(Level::ERROR, %err, "Export failed");
+                }
+
+                let is_err = line.is_err();
+                if sender.send(line).await.is_err() || is_err {
+                    return;
+                }
+            }
+        });
+
+        Ok(receiver)
+    }
+}
+
+fn write_record(record: impl Iterator<Item = String>, options: &ExportOptions) -> String {
+    let terminator = if options.excel {
+        csv::Terminator::CRLF
+    } else {
+        csv::Terminator::Any(b'\n')
+    };
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.locale.delimiter())
+        .terminator(terminator)
+        .from_writer(Vec::new());
+    writer
+        .write_record(record)
+        .expect("Writing to memory can't fail");
+    let bytes = writer.into_inner().expect("Writing to memory can't fail");
+
+    String::from_utf8(bytes).expect("Every field is valid UTF-8")
+}
+
+fn format_column(row: &ExportRow, column: ExportColumn, locale: ExportLocale) -> String {
+    let share = row.share.unwrap_or(0) as i64;
+    let paid = match row.user_id {
+        Some(user_id) if user_id == row.paid_by => row.total as i64,
+        _ => 0,
+    };
+
+    match column {
+        ExportColumn::ExpenseId => row.expense_id.to_string(),
+        ExportColumn::Date => row.created_at.format("%Y-%m-%d").to_string(),
+        ExportColumn::Name => text(&row.name),
+        ExportColumn::Category => text(row.category_name.as_deref().unwrap_or_default()),
+        ExportColumn::ParentCategory => {
+            text(row.parent_category_name.as_deref().unwrap_or_default())
+        }
+        ExportColumn::PaidBy => text(&row.paid_by_name),
+        ExportColumn::Total => amount(row.total as i64, locale),
+        ExportColumn::Currency => row.currency.clone(),
+        ExportColumn::Type if row.is_payment => "payment".to_string(),
+        ExportColumn::Type if row.refund_of.is_some() => "refund".to_string(),
+        ExportColumn::Type => "expense".to_string(),
+        ExportColumn::User => text(row.user_name.as_deref().unwrap_or_default()),
+        ExportColumn::Share => amount(share, locale),
+        ExportColumn::Paid => amount(paid, locale),
+        // A share is what the participant paid minus what they consumed.
+        ExportColumn::Consumed if row.is_payment => amount(0, locale),
+        ExportColumn::Consumed => amount(paid - share, locale),
+    }
+}
+
+fn amount(amount: i64, locale: ExportLocale) -> String {
+    format_amount(amount, locale.decimal_separator())
+}
+
+/// Keeps spreadsheet programs from evaluating user supplied text as a formula.
+fn text(value: &str) -> String {
+    if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    }
+}
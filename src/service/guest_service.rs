@@ -0,0 +1,169 @@
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    guest::{Guest, InsertGuest, UpdateGuest},
+    user::User,
+};
+
+use super::swish;
+
+#[derive(Debug, Clone)]
+pub struct GuestService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GuestError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Guest {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid guest: {0}")]
+    Invalid(String),
+}
+
+pub struct NewGuest {
+    pub group_id: i32,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+}
+
+impl GuestService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_guests(&self, actor: &User, group_id: i32) -> Result<Vec<Guest>, GuestError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::guest::get_guests(&self.db, group_id)
+            .await
+            .map_err(GuestError::Sqlx)
+    }
+
+    /// Adds a guest to the group, who can then take part in its expenses
+    /// until invited to take over with an account of their own.
+    pub async fn create_guest(&self, actor: &User, guest: NewGuest) -> Result<Guest, GuestError> {
+        self.ensure_member(actor.id, guest.group_id).await?;
+        let guest = validate_guest(guest)?;
+
+        let mut conn = self.db.acquire().await.map_err(GuestError::Sqlx)?;
+        db::guest::insert_guest(
+            &mut conn,
+            InsertGuest {
+                group_id: guest.group_id,
+                name: guest.name,
+                email: guest.email,
+                phone_number: guest.phone_number,
+                created_by: actor.id,
+            },
+        )
+        .await
+        .map_err(GuestError::Sqlx)
+    }
+
+    /// The group of a guest can't be changed.
+    pub async fn update_guest(
+        &self,
+        actor: &User,
+        id: i32,
+        guest: NewGuest,
+    ) -> Result<Guest, GuestError> {
+        self.get_guest(actor, id).await?;
+        let guest = validate_guest(guest)?;
+
+        let mut conn = self.db.acquire().await.map_err(GuestError::Sqlx)?;
+        db::guest::update_guest(
+            &mut conn,
+            id,
+            UpdateGuest {
+                name: guest.name,
+                email: guest.email,
+                phone_number: guest.phone_number,
+            },
+        )
+        .await
+        .map_err(GuestError::Sqlx)
+    }
+
+    /// Only guests that haven't taken part in any expense can be deleted,
+    /// others have to be merged into an account.
+    pub async fn delete_guest(&self, actor: &User, id: i32) -> Result<(), GuestError> {
+        self.get_guest(actor, id).await?;
+
+        let has_expenses = db::guest::has_expenses(&self.db, id)
+            .await
+            .map_err(GuestError::Sqlx)?;
+        if has_expenses {
+            return Err(GuestError::Invalid(format!(
+                "guest {} takes part in expenses",
+                id
+            )));
+        }
+
+        let mut conn = self.db.acquire().await.map_err(GuestError::Sqlx)?;
+        db::guest::delete_guest(&mut conn, id)
+            .await
+            .map_err(GuestError::Sqlx)
+    }
+
+    async fn get_guest(&self, actor: &User, id: i32) -> Result<Guest, GuestError> {
+        let guest = db::guest::get_guest(&self.db, id)
+            .await
+            .map_err(GuestError::Sqlx)?
+            .ok_or(GuestError::NotFound(id))?;
+        self.ensure_member(actor.id, guest.group_id).await?;
+
+        Ok(guest)
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), GuestError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(GuestError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(GuestError::NotMember(user_id, group_id))
+        }
+    }
+}
+
+fn validate_guest(guest: NewGuest) -> Result<NewGuest, GuestError> {
+    let name = guest.name.trim().to_string();
+    if name.is_empty() {
+        return Err(GuestError::Invalid("name must not be empty".to_string()));
+    }
+
+    let email = guest
+        .email
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty());
+    if email.as_ref().is_some_and(|email| !email.contains('@')) {
+        return Err(GuestError::Invalid(
+            "email must be an email address".to_string(),
+        ));
+    }
+
+    let phone_number = match guest.phone_number {
+        Some(phone_number) => Some(swish::normalize_phone_number(&phone_number).ok_or(
+            GuestError::Invalid(format!("'{}' is not a Swedish mobile number", phone_number)),
+        )?),
+        None => None,
+    };
+
+    Ok(NewGuest {
+        group_id: guest.group_id,
+        name,
+        email,
+        phone_number,
+    })
+}
@@ -0,0 +1,948 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    expense::{Expense, ExpenseDetails, InsertAccountShare},
+    expense_category::ExpenseCategory,
+    user::User,
+};
+
+use super::{
+    category_service::{tokenize, CategoryError, CategoryService, SuggestionQuery},
+    event_service::EventService,
+    expense_service::{ExpenseError, ExpenseService, NewExpense, Split, SplitWeight},
+};
+
+/// Columns every Splitwise export starts with, followed by one column per
+/// person.
+const SPLITWISE_COLUMNS: [&str; 5] = ["date", "description", "category", "cost", "currency"];
+const SPLITWISE_PAYMENT_CATEGORY: &str = "payment";
+const SPLITWISE_TOTAL_ROW: &str = "total balance";
+
+/// How many days a bank transaction and an expense may differ while still
+/// being considered the same purchase.
+const MATCH_WINDOW_DAYS: i64 = 3;
+/// Some banks put a summary above the header row of their CSV exports.
+const MAX_PREAMBLE_LINES: usize = 10;
+const DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%Y/%m/%d", "%d.%m.%Y", "%Y%m%d"];
+
+#[derive(Debug, Clone)]
+pub struct ImportService {
+    db: Pool<Postgres>,
+    events: EventService,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error(transparent)]
+    Csv(csv::Error),
+
+    #[error(transparent)]
+    Expense(ExpenseError),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid import: {0}")]
+    Invalid(String),
+}
+
+pub struct SplitwiseImport {
+    pub group_id: i32,
+    pub csv: String,
+    /// Splitwise names mapped to users. Names that aren't mapped fall back to
+    /// the group member with the same name.
+    pub people: HashMap<String, i32>,
+    pub dry_run: bool,
+}
+
+pub struct PersonMapping {
+    pub name: String,
+    pub user_id: Option<i32>,
+}
+
+pub enum ImportRowStatus {
+    /// Will be, or has been, imported.
+    New,
+    /// Matches an existing expense, which is kept instead.
+    Duplicate(i32),
+    /// Doesn't need importing, e.g. because it doesn't affect any balance.
+    Skipped(String),
+    /// Can't be imported and blocks the import until resolved.
+    Conflict(String),
+}
+
+pub struct ImportRow {
+    /// Line in the file, the header being line 1.
+    pub line: u64,
+    pub status: ImportRowStatus,
+    pub name: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub total: i32,
+    pub currency: String,
+    pub category_id: Option<i32>,
+    pub is_payment: bool,
+    pub paid_by: Option<i32>,
+    pub shares: Vec<InsertAccountShare>,
+    pub warnings: Vec<String>,
+    /// Set once the row has been imported.
+    pub expense_id: Option<i32>,
+}
+
+/// Which columns of a bank CSV export hold what, matched against the header
+/// row ignoring case.
+pub struct BankCsvMapping {
+    pub delimiter: u8,
+    pub date_column: String,
+    pub description_column: String,
+    /// Signed, negative for money leaving the account.
+    pub amount_column: String,
+    /// Falls back to the currency of the import when missing.
+    pub currency_column: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Bank {
+    Nordea,
+    Seb,
+    Swedbank,
+    Handelsbanken,
+    IcaBanken,
+}
+
+impl Bank {
+    pub fn parse(bank: &str) -> Option<Self> {
+        match bank {
+            "nordea" => Some(Bank::Nordea),
+            "seb" => Some(Bank::Seb),
+            "swedbank" => Some(Bank::Swedbank),
+            "handelsbanken" => Some(Bank::Handelsbanken),
+            "icabanken" => Some(Bank::IcaBanken),
+            _ => None,
+        }
+    }
+
+    pub fn mapping(&self) -> BankCsvMapping {
+        let (delimiter, date_column, description_column, amount_column, currency_column) =
+            match self {
+                Bank::Nordea => (b';', "Bokföringsdag", "Rubrik", "Belopp", Some("Valuta")),
+                Bank::Seb => (b';', "Bokföringsdatum", "Text", "Belopp", None),
+                Bank::Swedbank => (
+                    b',',
+                    "Transaktionsdag",
+                    "Beskrivning",
+                    "Belopp",
+                    Some("Valuta"),
+                ),
+                Bank::Handelsbanken => (b';', "Transaktionsdatum", "Text", "Belopp", None),
+                Bank::IcaBanken => (b';', "Datum", "Text", "Belopp", None),
+            };
+
+        BankCsvMapping {
+            delimiter,
+            date_column: date_column.to_string(),
+            description_column: description_column.to_string(),
+            amount_column: amount_column.to_string(),
+            currency_column: currency_column.map(|column| column.to_string()),
+        }
+    }
+}
+
+pub enum BankStatementFormat {
+    Csv(BankCsvMapping),
+    /// ISO 20022 bank to customer statement.
+    Camt053,
+}
+
+pub struct BankImport {
+    pub group_id: i32,
+    pub format: BankStatementFormat,
+    pub content: String,
+    pub currency: String,
+    /// Money coming in is usually a settlement or salary rather than an
+    /// expense, so it is ignored unless asked for.
+    pub include_incoming: bool,
+}
+
+pub struct BankTransaction {
+    /// Line in a CSV file or position of the entry in a statement, starting
+    /// at 1.
+    pub line: u64,
+    pub date: NaiveDate,
+    pub description: String,
+    pub amount: i32,
+    pub currency: String,
+}
+
+pub struct ExpenseMatch {
+    pub expense: Expense,
+    /// Word overlap between the names, between 0 and 1.
+    pub similarity: f64,
+}
+
+pub enum ProposalStatus {
+    New,
+    /// Probably already recorded, see the matches.
+    Matched,
+    Ignored(String),
+}
+
+/// An expense paid by the importing user and split evenly within the group,
+/// ready to be confirmed as is or after editing.
+pub struct ProposedExpense {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub paid_by: i32,
+    pub total: i32,
+    pub currency: String,
+    pub category_id: Option<i32>,
+    pub weights: Vec<SplitWeight>,
+    pub group_id: i32,
+}
+
+pub struct BankTransactionProposal {
+    pub transaction: BankTransaction,
+    pub status: ProposalStatus,
+    pub expense: Option<ProposedExpense>,
+    /// Best match first.
+    pub matches: Vec<ExpenseMatch>,
+}
+
+pub struct ImportReport {
+    pub people: Vec<PersonMapping>,
+    pub rows: Vec<ImportRow>,
+    pub committed: bool,
+}
+
+impl ImportRow {
+    fn conflict(&mut self, reason: impl Into<String>) {
+        if !matches!(self.status, ImportRowStatus::Conflict(_)) {
+            self.status = ImportRowStatus::Conflict(reason.into());
+        }
+    }
+
+    fn to_new_expense(&self, group_id: i32) -> NewExpense {
+        NewExpense {
+            name: self.name.clone(),
+            created_at: self.created_at,
+            paid_by: self.paid_by.unwrap_or_default(),
+            total: self.total,
+            currency: self.currency.clone(),
+            category_id: self.category_id,
+            is_payment: self.is_payment,
+            split: Split::Shares(
+                self.shares
+                    .iter()
+                    .map(|share| InsertAccountShare {
+                        user_id: share.user_id,
+                        share: share.share,
+                    })
+                    .collect(),
+            ),
+            group_id: Some(group_id),
+            details: ExpenseDetails::default(),
+        }
+    }
+}
+
+impl ImportService {
+    pub fn new(db: Pool<Postgres>, events: EventService) -> Self {
+        Self { db, events }
+    }
+
+    /// Converts a Splitwise CSV export into expenses. Every person column holds
+    /// what that person paid minus what they owe, which is exactly the signed
+    /// share stored in `account_share`. Nothing is stored for a dry run or
+    /// when any row has a conflict.
+    pub async fn import_splitwise(
+        &self,
+        actor: &User,
+        import: SplitwiseImport,
+    ) -> Result<ImportReport, ImportError> {
+        let is_member = db::group::is_member(&self.db, import.group_id, actor.id)
+            .await
+            .map_err(ImportError::Sqlx)?;
+        if !is_member {
+            return Err(ImportError::NotMember(actor.id, import.group_id));
+        }
+
+        let members = db::group::get_members(&self.db, import.group_id)
+            .await
+            .map_err(ImportError::Sqlx)?;
+        let categories =
+            db::expense_category::get_expense_categories(&self.db, Some(import.group_id), true)
+                .await
+                .map_err(ImportError::Sqlx)?;
+
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(import.csv.as_bytes());
+        let headers = reader.headers().map_err(ImportError::Csv)?.clone();
+        let is_splitwise = headers.len() > SPLITWISE_COLUMNS.len()
+            && SPLITWISE_COLUMNS
+                .iter()
+                .zip(headers.iter())
+                .all(|(expected, header)| header.eq_ignore_ascii_case(expected));
+        if !is_splitwise {
+            return Err(ImportError::Invalid(
+                "the file is not a Splitwise export".to_string(),
+            ));
+        }
+
+        let people: Vec<PersonMapping> = headers
+            .iter()
+            .skip(SPLITWISE_COLUMNS.len())
+            .map(|name| PersonMapping {
+                name: name.to_string(),
+                user_id: import.people.get(name).copied().or_else(|| {
+                    members
+                        .iter()
+                        .find(|member| member.name.trim().eq_ignore_ascii_case(name))
+                        .map(|member| member.id)
+                }),
+            })
+            .collect();
+        if let Some(person) = people.iter().find(|person| {
+            person
+                .user_id
+                .is_some_and(|user_id| members.iter().all(|member| member.id != user_id))
+        }) {
+            return Err(ImportError::Invalid(format!(
+                "{} is mapped to a user outside group {}",
+                person.name, import.group_id
+            )));
+        }
+
+        let mut rows = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(ImportError::Csv)?;
+            let line = record
+                .position()
+                .map(|position| position.line())
+                .unwrap_or(0);
+            let description = record.get(1).unwrap_or_default();
+            if record.iter().all(str::is_empty)
+                || description.eq_ignore_ascii_case(SPLITWISE_TOTAL_ROW)
+            {
+                continue;
+            }
+
+            let mut row = convert_splitwise_row(line, &record, &people, &categories);
+            if matches!(row.status, ImportRowStatus::New) {
+                self.check_row(actor, import.group_id, &mut row).await?;
+            }
+            rows.push(row);
+        }
+
+        let has_conflicts = rows
+            .iter()
+            .any(|row| matches!(row.status, ImportRowStatus::Conflict(_)));
+        if import.dry_run || has_conflicts {
+            return Ok(ImportReport {
+                people,
+                rows,
+                committed: false,
+            });
+        }
+
+        let to_import: Vec<&mut ImportRow> = rows
+            .iter_mut()
+            .filter(|row| matches!(row.status, ImportRowStatus::New))
+            .collect();
+        let expenses = to_import
+            .iter()
+            .map(|row| row.to_new_expense(import.group_id))
+            .collect();
+        let created = ExpenseService::new(self.db.clone(), self.events.clone())
+            .import_expenses(actor, expenses)
+            .await
+            .map_err(ImportError::Expense)?;
+        for (row, (expense, _)) in to_import.into_iter().zip(created) {
+            row.expense_id = Some(expense.expense.id);
+        }
+
+        Ok(ImportReport {
+            people,
+            rows,
+            committed: true,
+        })
+    }
+
+    /// Proposes an expense for every outgoing transaction of a bank statement
+    /// and looks for existing expenses with the same amount around the same
+    /// date. Nothing is stored; confirmed proposals are created like any
+    /// other expenses.
+    pub async fn preview_bank_statement(
+        &self,
+        actor: &User,
+        import: BankImport,
+    ) -> Result<Vec<BankTransactionProposal>, ImportError> {
+        let is_member = db::group::is_member(&self.db, import.group_id, actor.id)
+            .await
+            .map_err(ImportError::Sqlx)?;
+        if !is_member {
+            return Err(ImportError::NotMember(actor.id, import.group_id));
+        }
+
+        let transactions = match &import.format {
+            BankStatementFormat::Csv(mapping) => {
+                parse_bank_csv(&import.content, mapping, &import.currency)?
+            }
+            BankStatementFormat::Camt053 => parse_camt053(&import.content)?,
+        };
+        let member_ids = db::group::get_member_ids(&self.db, import.group_id)
+            .await
+            .map_err(ImportError::Sqlx)?;
+        let category_service = CategoryService::new(self.db.clone());
+
+        let mut proposals = Vec::new();
+        for transaction in transactions {
+            if transaction.amount == 0 || transaction.amount > 0 && !import.include_incoming {
+                proposals.push(BankTransactionProposal {
+                    transaction,
+                    status: ProposalStatus::Ignored("not an outgoing payment".to_string()),
+                    expense: None,
+                    matches: Vec::new(),
+                });
+                continue;
+            }
+
+            let total = transaction.amount.abs();
+            let matches = self
+                .find_matches(import.group_id, &transaction, total)
+                .await?;
+            let suggestion = category_service
+                .suggest_categories(
+                    actor,
+                    SuggestionQuery {
+                        name: transaction.description.clone(),
+                        group_id: import.group_id,
+                        paid_by: Some(actor.id),
+                        total: Some(total),
+                        limit: 1,
+                    },
+                )
+                .await;
+            let category_id = match suggestion {
+                Ok(suggestions) => suggestions.first().map(|s| s.category.id),
+                Err(CategoryError::Sqlx(err)) => return Err(ImportError::Sqlx(err)),
+                Err(_) => None,
+            };
+
+            proposals.push(BankTransactionProposal {
+                status: if matches.is_empty() {
+                    ProposalStatus::New
+                } else {
+                    ProposalStatus::Matched
+                },
+                expense: Some(ProposedExpense {
+                    name: transaction.description.clone(),
+                    created_at: noon(transaction.date),
+                    paid_by: actor.id,
+                    total,
+                    currency: transaction.currency.clone(),
+                    category_id,
+                    weights: member_ids
+                        .iter()
+                        .map(|user_id| SplitWeight {
+                            user_id: *user_id,
+                            weight: 1,
+                        })
+                        .collect(),
+                    group_id: import.group_id,
+                }),
+                matches,
+                transaction,
+            });
+        }
+
+        Ok(proposals)
+    }
+
+    async fn find_matches(
+        &self,
+        group_id: i32,
+        transaction: &BankTransaction,
+        total: i32,
+    ) -> Result<Vec<ExpenseMatch>, ImportError> {
+        let day_start = transaction.date.and_time(NaiveTime::MIN).and_utc();
+        let candidates = db::expense::get_expenses_with_total(
+            &self.db,
+            group_id,
+            total,
+            &transaction.currency,
+            day_start - Duration::days(MATCH_WINDOW_DAYS),
+            day_start + Duration::days(MATCH_WINDOW_DAYS + 1),
+        )
+        .await
+        .map_err(ImportError::Sqlx)?;
+
+        let tokens = tokenize(&transaction.description);
+        let mut matches: Vec<ExpenseMatch> = candidates
+            .into_iter()
+            .filter(|expense| !expense.is_payment)
+            .map(|expense| {
+                let expense_tokens = tokenize(&expense.name);
+                let union = tokens.union(&expense_tokens).count();
+                let similarity = if union == 0 {
+                    0.0
+                } else {
+                    tokens.intersection(&expense_tokens).count() as f64 / union as f64
+                };
+
+                ExpenseMatch {
+                    expense,
+                    similarity,
+                }
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.similarity.total_cmp(&a.similarity).then_with(|| {
+                let distance = |expense: &Expense| (expense.created_at - day_start).abs();
+                distance(&a.expense).cmp(&distance(&b.expense))
+            })
+        });
+
+        Ok(matches)
+    }
+
+    /// Marks the row as a duplicate of an existing expense with the same
+    /// name, total and day, or as a conflict if it wouldn't be a valid
+    /// expense.
+    async fn check_row(
+        &self,
+        actor: &User,
+        group_id: i32,
+        row: &mut ImportRow,
+    ) -> Result<(), ImportError> {
+        let created_at = row.created_at.expect("New rows have a date");
+        let day_start = created_at.date_naive().and_time(NaiveTime::MIN).and_utc();
+        let existing = db::expense::get_expenses_with_total(
+            &self.db,
+            group_id,
+            row.total,
+            &row.currency,
+            day_start,
+            day_start + Duration::days(1),
+        )
+        .await
+        .map_err(ImportError::Sqlx)?;
+        if let Some(duplicate) = existing
+            .iter()
+            .find(|expense| expense.name.trim().eq_ignore_ascii_case(row.name.trim()))
+        {
+            row.status = ImportRowStatus::Duplicate(duplicate.id);
+            return Ok(());
+        }
+
+        let expense_service = ExpenseService::new(self.db.clone(), self.events.clone());
+        match expense_service
+            .prepare_expense(actor, row.to_new_expense(group_id))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(ExpenseError::Invalid(reason)) => {
+                row.conflict(reason);
+                Ok(())
+            }
+            Err(err @ ExpenseError::Closed(_, _)) => {
+                row.conflict(err.to_string());
+                Ok(())
+            }
+            Err(err) => Err(ImportError::Expense(err)),
+        }
+    }
+}
+
+fn convert_splitwise_row(
+    line: u64,
+    record: &csv::StringRecord,
+    people: &[PersonMapping],
+    categories: &[ExpenseCategory],
+) -> ImportRow {
+    let field = |i: usize| record.get(i).unwrap_or_default();
+    let category = field(2);
+    let mut row = ImportRow {
+        line,
+        status: ImportRowStatus::New,
+        name: field(1).to_string(),
+        created_at: None,
+        total: 0,
+        currency: field(4).to_uppercase(),
+        category_id: None,
+        is_payment: category.eq_ignore_ascii_case(SPLITWISE_PAYMENT_CATEGORY),
+        paid_by: None,
+        shares: Vec::new(),
+        warnings: Vec::new(),
+        expense_id: None,
+    };
+
+    match NaiveDate::parse_from_str(field(0), "%Y-%m-%d") {
+        Ok(date) => row.created_at = Some(noon(date)),
+        Err(_) => row.conflict(format!("'{}' is not a date", field(0))),
+    }
+    match parse_amount(field(3)) {
+        Some(total) => row.total = total,
+        None => row.conflict(format!("'{}' is not an amount", field(3))),
+    }
+
+    if !row.is_payment && !category.is_empty() {
+        row.category_id = categories
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(category))
+            .map(|c| c.id);
+        if row.category_id.is_none() {
+            row.warnings
+                .push(format!("category '{}' doesn't exist", category));
+        }
+    }
+
+    for (i, person) in people.iter().enumerate() {
+        let value = field(SPLITWISE_COLUMNS.len() + i);
+        let share = match parse_amount(if value.is_empty() { "0" } else { value }) {
+            Some(share) => share,
+            None => {
+                row.conflict(format!("'{}' is not an amount", value));
+                continue;
+            }
+        };
+        if share == 0 {
+            continue;
+        }
+
+        match person.user_id {
+            Some(user_id) => row.shares.push(InsertAccountShare { user_id, share }),
+            None => row.conflict(format!("{} is not mapped to a user", person.name)),
+        }
+    }
+
+    let mut payers = row.shares.iter().filter(|share| share.share > 0);
+    row.paid_by = payers
+        .clone()
+        .max_by_key(|share| share.share)
+        .map(|share| share.user_id);
+    // An expense has a single payer, recording only one of them would move
+    // what the others paid onto the wrong person.
+    if payers.nth(1).is_some() {
+        row.conflict("more than one person paid, split it into one expense per payer");
+    }
+    if row.shares.is_empty() && matches!(row.status, ImportRowStatus::New) {
+        row.status = ImportRowStatus::Skipped("doesn't affect any balance".to_string());
+    }
+
+    row
+}
+
+/// Imported files only have dates, noon keeps them on the same day in every
+/// European time zone.
+fn noon(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::from_hms_opt(12, 0, 0).expect("Noon is a valid time"))
+        .and_utc()
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+fn parse_bank_csv(
+    content: &str,
+    mapping: &BankCsvMapping,
+    currency: &str,
+) -> Result<Vec<BankTransaction>, ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+    let mut records = reader.records();
+
+    let mut columns = None;
+    for record in records.by_ref().take(MAX_PREAMBLE_LINES) {
+        let record = record.map_err(ImportError::Csv)?;
+        let position = |column: &str| {
+            record
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+        };
+        if let (Some(date), Some(description), Some(amount)) = (
+            position(&mapping.date_column),
+            position(&mapping.description_column),
+            position(&mapping.amount_column),
+        ) {
+            let currency = mapping.currency_column.as_deref().and_then(position);
+            columns = Some((date, description, amount, currency));
+            break;
+        }
+    }
+    let Some((date_column, description_column, amount_column, currency_column)) = columns else {
+        return Err(ImportError::Invalid(format!(
+            "no header row with the columns {}, {} and {}",
+            mapping.date_column, mapping.description_column, mapping.amount_column
+        )));
+    };
+
+    let mut transactions = Vec::new();
+    for record in records {
+        let record = record.map_err(ImportError::Csv)?;
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+
+        let line = record
+            .position()
+            .map(|position| position.line())
+            .unwrap_or(0);
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        let date = parse_date(field(date_column)).ok_or_else(|| {
+            ImportError::Invalid(format!(
+                "line {}: '{}' is not a date",
+                line,
+                field(date_column)
+            ))
+        })?;
+        // ICA Banken and others write amounts like "-123,45 kr".
+        let amount_field = field(amount_column).trim_end_matches(|c: char| c.is_alphabetic());
+        let amount = parse_amount(amount_field).ok_or_else(|| {
+            ImportError::Invalid(format!(
+                "line {}: '{}' is not an amount",
+                line,
+                field(amount_column)
+            ))
+        })?;
+
+        transactions.push(BankTransaction {
+            line,
+            date,
+            description: field(description_column).to_string(),
+            amount,
+            currency: currency_column
+                .map(field)
+                .filter(|currency| !currency.is_empty())
+                .unwrap_or(currency)
+                .to_uppercase(),
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// Reads the entries of every statement in a camt.053 document. Debit entries
+/// become negative amounts, like in the CSV exports.
+fn parse_camt053(content: &str) -> Result<Vec<BankTransaction>, ImportError> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|err| ImportError::Invalid(format!("not a camt.053 document: {}", err)))?;
+    let entries = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "Ntry");
+    let mut transactions = Vec::new();
+    for (i, entry) in entries.enumerate() {
+        let line = i as u64 + 1;
+        let invalid = |what: &str| ImportError::Invalid(format!("entry {}: {}", line, what));
+
+        let amount_node = child(entry, &["Amt"]).ok_or_else(|| invalid("missing amount"))?;
+        let amount = amount_node
+            .text()
+            .and_then(|amount| parse_amount(amount.trim()))
+            .ok_or_else(|| invalid("invalid amount"))?;
+        let currency = amount_node
+            .attribute("Ccy")
+            .ok_or_else(|| invalid("missing currency"))?;
+        let is_debit = text(entry, &["CdtDbtInd"]).as_deref() == Some("DBIT");
+
+        let date = [
+            &["BookgDt", "Dt"][..],
+            &["BookgDt", "DtTm"],
+            &["ValDt", "Dt"],
+            &["ValDt", "DtTm"],
+        ]
+        .iter()
+        .find_map(|path| text(entry, path))
+        .and_then(|date| parse_date(date.get(..10).unwrap_or(&date)))
+        .ok_or_else(|| invalid("missing booking date"))?;
+
+        let details = child(entry, &["NtryDtls", "TxDtls"]);
+        let party = if is_debit { "Cdtr" } else { "Dbtr" };
+        let description = details
+            .and_then(|details| {
+                text(details, &["RltdPties", party, "Nm"])
+                    .or_else(|| text(details, &["RltdPties", party, "Pty", "Nm"]))
+                    .or_else(|| text(details, &["RmtInf", "Ustrd"]))
+            })
+            .or_else(|| text(entry, &["AddtlNtryInf"]))
+            .unwrap_or_default();
+
+        transactions.push(BankTransaction {
+            line,
+            date,
+            description,
+            amount: if is_debit { -amount } else { amount },
+            currency: currency.to_uppercase(),
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// Follows the path of element names down from `node`.
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    path: &[&str],
+) -> Option<roxmltree::Node<'a, 'input>> {
+    path.iter().try_fold(node, |node, name| {
+        node.children()
+            .find(|child| child.tag_name().name() == *name)
+    })
+}
+
+fn text(node: roxmltree::Node<'_, '_>, path: &[&str]) -> Option<String> {
+    child(node, path)
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Parses an amount in major units into minor units. Accepts both decimal
+/// points and decimal commas, and ignores spaces used as thousands
+/// separators.
+pub fn parse_amount(value: &str) -> Option<i32> {
+    let value: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\u{a0}')
+        .collect();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(&value)),
+    };
+
+    let (whole, fraction) = match value.rfind(['.', ',']) {
+        Some(i) if value.len() - i - 1 <= 2 => (&value[..i], &value[i + 1..]),
+        _ => (value, ""),
+    };
+    let whole: String = whole.chars().filter(|c| *c != '.' && *c != ',').collect();
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let whole: i64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let fraction: i64 = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<i64>().ok()? * 10,
+        _ => fraction.parse().ok()?,
+    };
+    let amount = whole.checked_mul(100)?.checked_add(fraction)?;
+
+    i32::try_from(if negative { -amount } else { amount }).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_amount_reads_major_units() {
+        assert_eq!(parse_amount("123"), Some(12300));
+        assert_eq!(parse_amount("12.3"), Some(1230));
+        assert_eq!(parse_amount("12,34"), Some(1234));
+        assert_eq!(parse_amount(",5"), Some(50));
+        assert_eq!(parse_amount("+5"), Some(500));
+    }
+
+    #[test]
+    fn parse_amount_ignores_thousands_separators() {
+        assert_eq!(parse_amount("1 234,50"), Some(123450));
+        assert_eq!(parse_amount("1\u{a0}234.50"), Some(123450));
+        assert_eq!(parse_amount("1.234,5"), Some(123450));
+        // Three digits after the last separator can't be a fraction.
+        assert_eq!(parse_amount("1,234"), Some(123400));
+    }
+
+    #[test]
+    fn parse_amount_reads_negative_amounts() {
+        assert_eq!(parse_amount("-12.30"), Some(-1230));
+        assert_eq!(parse_amount("- 1 000"), Some(-100000));
+    }
+
+    #[test]
+    fn parse_amount_rejects_what_is_not_an_amount() {
+        for value in ["", "-", "abc", "12a", "--5", "99999999999"] {
+            assert_eq!(parse_amount(value), None, "{}", value);
+        }
+    }
+
+    const STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Ntry>
+        <Amt Ccy="sek">249.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <BookgDt><Dt>2025-03-14</Dt></BookgDt>
+        <NtryDtls><TxDtls>
+          <RltdPties><Cdtr><Nm>ICA Maxi</Nm></Cdtr></RltdPties>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="SEK">1000</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <ValDt><DtTm>2025-03-15T08:30:00</DtTm></ValDt>
+        <AddtlNtryInf>Swish from Kim</AddtlNtryInf>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    #[test]
+    fn parse_camt053_reads_every_entry() {
+        let transactions = parse_camt053(STATEMENT).unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        let debit = &transactions[0];
+        assert_eq!(debit.line, 1);
+        assert_eq!(debit.date, NaiveDate::from_ymd_opt(2025, 3, 14).unwrap());
+        assert_eq!(debit.description, "ICA Maxi");
+        assert_eq!(debit.amount, -24950);
+        assert_eq!(debit.currency, "SEK");
+
+        let credit = &transactions[1];
+        assert_eq!(credit.line, 2);
+        assert_eq!(credit.date, NaiveDate::from_ymd_opt(2025, 3, 15).unwrap());
+        assert_eq!(credit.description, "Swish from Kim");
+        assert_eq!(credit.amount, 100000);
+    }
+
+    #[test]
+    fn parse_camt053_rejects_entries_without_an_amount() {
+        let statement = STATEMENT.replace(r#"<Amt Ccy="SEK">1000</Amt>"#, "");
+        assert!(matches!(
+            parse_camt053(&statement),
+            Err(ImportError::Invalid(reason)) if reason.starts_with("entry 2:")
+        ));
+    }
+
+    #[test]
+    fn parse_camt053_rejects_what_is_not_xml() {
+        assert!(matches!(
+            parse_camt053("Datum;Belopp"),
+            Err(ImportError::Invalid(_))
+        ));
+    }
+}
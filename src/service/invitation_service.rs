@@ -0,0 +1,185 @@
+use chrono::{Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    invitation::{InsertInvitation, Invitation},
+    user::User,
+};
+
+/// How long an invitation can be accepted unless told otherwise.
+pub const DEFAULT_VALID_DAYS: i64 = 14;
+const MAX_VALID_DAYS: i64 = 90;
+
+#[derive(Debug, Clone)]
+pub struct InvitationService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvitationError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Invitation not found")]
+    NotFound,
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid invitation: {0}")]
+    Invalid(String),
+}
+
+pub struct NewInvitation {
+    pub group_id: i32,
+    /// The guest whoever accepts takes over.
+    pub guest_id: Option<i32>,
+    pub email: Option<String>,
+    /// Defaults to [`DEFAULT_VALID_DAYS`].
+    pub valid_days: Option<i64>,
+}
+
+impl InvitationService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_invitations(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<Invitation>, InvitationError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::invitation::get_pending_invitations(&self.db, group_id)
+            .await
+            .map_err(InvitationError::Sqlx)
+    }
+
+    /// Invites someone to the group through a token that is handed to them
+    /// out of band, e.g. as a link.
+    pub async fn create_invitation(
+        &self,
+        actor: &User,
+        invitation: NewInvitation,
+    ) -> Result<Invitation, InvitationError> {
+        self.ensure_member(actor.id, invitation.group_id).await?;
+
+        let valid_days = invitation.valid_days.unwrap_or(DEFAULT_VALID_DAYS);
+        if !(1..=MAX_VALID_DAYS).contains(&valid_days) {
+            return Err(InvitationError::Invalid(format!(
+                "valid_days must be between 1 and {}",
+                MAX_VALID_DAYS
+            )));
+        }
+
+        let mut email = invitation.email;
+        if let Some(guest_id) = invitation.guest_id {
+            let guest = db::guest::get_guest(&self.db, guest_id)
+                .await
+                .map_err(InvitationError::Sqlx)?
+                .filter(|guest| guest.group_id == invitation.group_id)
+                .ok_or(InvitationError::Invalid(format!(
+                    "user {} is not a guest of group {}",
+                    guest_id, invitation.group_id
+                )))?;
+            email = email.or(guest.email);
+        }
+
+        db::invitation::insert_invitation(
+            &self.db,
+            InsertInvitation {
+                group_id: invitation.group_id,
+                guest_id: invitation.guest_id,
+                token: generate_token(),
+                email,
+                created_by: actor.id,
+                expires_at: Utc::now() + Duration::days(valid_days),
+            },
+        )
+        .await
+        .map_err(InvitationError::Sqlx)
+    }
+
+    pub async fn revoke_invitation(&self, actor: &User, id: i32) -> Result<(), InvitationError> {
+        let invitation = db::invitation::get_invitation(&self.db, id)
+            .await
+            .map_err(InvitationError::Sqlx)?
+            .ok_or(InvitationError::NotFound)?;
+        self.ensure_member(actor.id, invitation.group_id).await?;
+
+        db::invitation::delete_invitation(&self.db, id)
+            .await
+            .map_err(InvitationError::Sqlx)
+    }
+
+    /// Adds `actor` to the group of the invitation. If the invitation is for
+    /// a guest, everything of the guest is merged into `actor`, who takes
+    /// over their expenses and balances.
+    pub async fn accept_invitation(
+        &self,
+        actor: &User,
+        token: &str,
+    ) -> Result<Invitation, InvitationError> {
+        if actor.is_guest {
+            return Err(InvitationError::Invalid(
+                "guests can't accept invitations".to_string(),
+            ));
+        }
+
+        let mut tx = self.db.begin().await.map_err(InvitationError::Sqlx)?;
+        let invitation = db::invitation::lock_invitation_by_token(&mut tx, token)
+            .await
+            .map_err(InvitationError::Sqlx)?
+            .ok_or(InvitationError::NotFound)?;
+        if invitation.accepted_at.is_some() {
+            return Err(InvitationError::Invalid(
+                "the invitation has already been accepted".to_string(),
+            ));
+        }
+        if invitation.expires_at <= Utc::now() {
+            return Err(InvitationError::Invalid(
+                "the invitation has expired".to_string(),
+            ));
+        }
+
+        db::group::add_member(&mut *tx, invitation.group_id, actor.id)
+            .await
+            .map_err(InvitationError::Sqlx)?;
+        // Cleared when the guest is deleted, so it always refers to a guest.
+        if let Some(guest_id) = invitation.guest_id {
+            db::guest::merge_guest(&mut tx, guest_id, actor.id)
+                .await
+                .map_err(InvitationError::Sqlx)?;
+        }
+        let accepted = db::invitation::mark_accepted(&mut *tx, invitation.id, actor.id)
+            .await
+            .map_err(InvitationError::Sqlx)?;
+        tx.commit().await.map_err(InvitationError::Sqlx)?;
+
+        Ok(accepted)
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), InvitationError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(InvitationError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(InvitationError::NotMember(user_id, group_id))
+        }
+    }
+}
+
+fn generate_token() -> String {
+    let mut token = [0u8; 24];
+    SystemRandom::new()
+        .fill(&mut token)
+        .expect("System randomness is available");
+
+    token.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -1 +1,24 @@
-pub mod auth_service;
\ No newline at end of file
+pub mod auth_service;
+pub mod budget_service;
+pub mod category_service;
+pub mod change_service;
+pub mod event_service;
+pub mod expense_service;
+pub mod export_service;
+pub mod import_service;
+pub mod notification_service;
+pub mod push_service;
+pub mod report_service;
+pub mod settlement_service;
+pub mod swish;
+pub mod sync_service;
+pub mod web_push;
+pub mod webhook_service;
+pub mod guest_service;
+pub mod invitation_service;
+pub mod split_profile_service;
+pub mod comment_service;
+pub mod period_close_service;
+pub mod statement;
+pub mod admin_service;
+pub mod amount;
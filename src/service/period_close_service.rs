@@ -0,0 +1,231 @@
+use std::collections::BTreeSet;
+
+use chrono::Utc;
+use sqlx::{Pool, Postgres};
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    group::Group,
+    period_close::{InsertPeriodClose, PeriodClose, PeriodCloseBalance, StatementLine},
+    user::User,
+};
+
+#[derive(Debug, Clone)]
+pub struct PeriodCloseService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PeriodCloseError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Period close {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid period close: {0}")]
+    Invalid(String),
+}
+
+/// What happened to one user's balance in a group during a closed period.
+pub struct Statement {
+    pub close: PeriodClose,
+    pub group: Group,
+    pub user: User,
+    /// Where the previous close ended, `None` for the first close of the
+    /// group, whose period covers everything before it.
+    pub from: Option<chrono::DateTime<Utc>>,
+    /// One per currency the user had a balance or an expense in.
+    pub currencies: Vec<CurrencyStatement>,
+}
+
+pub struct CurrencyStatement {
+    pub currency: String,
+    pub opening_balance: i64,
+    pub expenses: Vec<StatementLine>,
+    pub settlements: Vec<StatementLine>,
+    /// Always the opening balance plus the user's shares of the expenses and
+    /// settlements.
+    pub closing_balance: i64,
+}
+
+impl PeriodCloseService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_closes(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<PeriodClose>, PeriodCloseError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::period_close::get_closes(&self.db, group_id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)
+    }
+
+    /// Closes the group's expenses created before `closed_until` against
+    /// changes and snapshots the balances at that point. Periods can only be
+    /// closed in order and only once everything in them is confirmed.
+    pub async fn close_period(
+        &self,
+        actor: &User,
+        group_id: i32,
+        closed_until: chrono::DateTime<Utc>,
+    ) -> Result<PeriodClose, PeriodCloseError> {
+        self.ensure_member(actor.id, group_id).await?;
+        if closed_until > Utc::now() {
+            return Err(PeriodCloseError::Invalid(
+                "a period can't be closed before it has ended".to_string(),
+            ));
+        }
+
+        let latest = db::period_close::get_latest_close(&self.db, group_id, None)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?;
+        if let Some(latest) = latest.filter(|latest| latest.closed_until >= closed_until) {
+            return Err(PeriodCloseError::Invalid(format!(
+                "group {} is already closed until {}",
+                group_id,
+                latest.closed_until.to_rfc3339()
+            )));
+        }
+
+        let unconfirmed = db::period_close::count_unconfirmed(&self.db, group_id, closed_until)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?;
+        if unconfirmed > 0 {
+            return Err(PeriodCloseError::Invalid(format!(
+                "{} expenses of the period still await approval",
+                unconfirmed
+            )));
+        }
+
+        let mut conn = self.db.acquire().await.map_err(PeriodCloseError::Sqlx)?;
+        let close = db::period_close::insert_close(
+            &mut conn,
+            InsertPeriodClose {
+                group_id,
+                closed_until,
+                closed_by: actor.id,
+            },
+        )
+        .await
+        .map_err(PeriodCloseError::Sqlx)?;
+        event!(
+            Level::INFO,
+            close_id = close.id,
+            group_id,
+            user_id = actor.id,
+            "Closed period"
+        );
+
+        Ok(close)
+    }
+
+    /// The statement of `user_id` for the period that ended with the close.
+    pub async fn get_statement(
+        &self,
+        actor: &User,
+        close_id: i32,
+        user_id: i32,
+    ) -> Result<Statement, PeriodCloseError> {
+        let close = db::period_close::get_close(&self.db, close_id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?
+            .ok_or(PeriodCloseError::NotFound(close_id))?;
+        self.ensure_member(actor.id, close.group_id).await?;
+        let group = db::group::get_group(&self.db, close.group_id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?
+            .ok_or(PeriodCloseError::NotFound(close_id))?;
+        let user = db::user::get_user_by_id(&self.db, user_id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?
+            .ok_or(PeriodCloseError::Invalid(format!(
+                "user {} doesn't exist",
+                user_id
+            )))?;
+
+        let previous =
+            db::period_close::get_latest_close(&self.db, close.group_id, Some(close.closed_until))
+                .await
+                .map_err(PeriodCloseError::Sqlx)?;
+        let opening = match &previous {
+            Some(previous) => db::period_close::get_balances(&self.db, previous.id)
+                .await
+                .map_err(PeriodCloseError::Sqlx)?,
+            None => Vec::new(),
+        };
+        let closing = db::period_close::get_balances(&self.db, close.id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?;
+        let from = previous.map(|previous| previous.closed_until);
+        let lines = db::period_close::get_statement_lines(
+            &self.db,
+            close.group_id,
+            user_id,
+            from,
+            close.closed_until,
+        )
+        .await
+        .map_err(PeriodCloseError::Sqlx)?;
+
+        let balance = |balances: &[PeriodCloseBalance], currency: &str| {
+            balances
+                .iter()
+                .find(|balance| balance.user_id == user_id && balance.currency == currency)
+                .map_or(0, |balance| balance.balance)
+        };
+        let currencies: BTreeSet<&str> = opening
+            .iter()
+            .chain(closing.iter())
+            .filter(|balance| balance.user_id == user_id)
+            .map(|balance| balance.currency.as_str())
+            .chain(lines.iter().map(|line| line.currency.as_str()))
+            .collect();
+        let currencies = currencies
+            .into_iter()
+            .map(|currency| {
+                let (settlements, expenses) = lines
+                    .iter()
+                    .filter(|line| line.currency == currency)
+                    .cloned()
+                    .partition(|line| line.is_payment);
+
+                CurrencyStatement {
+                    currency: currency.to_string(),
+                    opening_balance: balance(&opening, currency),
+                    expenses,
+                    settlements,
+                    closing_balance: balance(&closing, currency),
+                }
+            })
+            .collect();
+
+        Ok(Statement {
+            close,
+            group,
+            user,
+            from,
+            currencies,
+        })
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), PeriodCloseError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(PeriodCloseError::Sqlx)?;
+        if is_member {
+            Ok(())
+        } else {
+            Err(PeriodCloseError::NotMember(user_id, group_id))
+        }
+    }
+}
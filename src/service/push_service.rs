@@ -0,0 +1,321 @@
+use reqwest::{redirect::Policy, StatusCode};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    push_subscription::{InsertPushSubscription, PushSubscription},
+    user::User,
+};
+
+use super::{
+    amount::format_with_currency,
+    event_service::{EventService, LedgerEvent},
+    web_push::{self, VapidKey, WebPushError},
+    webhook_service::resolve_public,
+};
+
+/// Seconds a push service keeps a message for an offline device.
+const PUSH_TTL: u32 = 24 * 60 * 60;
+
+#[derive(Clone)]
+pub struct PushService {
+    db: Pool<Postgres>,
+    vapid_key: VapidKey,
+    /// Contact for push services, see RFC 8292.
+    subject: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PushError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error(transparent)]
+    WebPush(WebPushError),
+
+    #[error(transparent)]
+    Http(reqwest::Error),
+
+    #[error("Push subscription {0} not found")]
+    NotFound(i32),
+
+    #[error("Invalid push subscription: {0}")]
+    Invalid(String),
+}
+
+pub struct NewPushSubscription {
+    pub endpoint: String,
+    pub p256dh: String,
+    pub auth: String,
+    pub device_name: Option<String>,
+}
+
+/// What the service worker receives.
+#[derive(Serialize)]
+struct PushMessage {
+    title: String,
+    body: String,
+    /// Messages with the same tag replace each other on the device.
+    tag: String,
+    expense_id: i32,
+}
+
+impl PushService {
+    pub fn new(db: Pool<Postgres>, vapid_key: VapidKey) -> Self {
+        Self {
+            db,
+            vapid_key,
+            subject: std::env::var("VAPID_SUBJECT")
+                .unwrap_or("mailto:noreply@jostrid.se".to_string()),
+        }
+    }
+
+    /// Uses `VAPID_PRIVATE_KEY` when set, otherwise the key stored in the
+    /// database, generating one on first start. Changing the key invalidates
+    /// every existing subscription.
+    pub async fn load_vapid_key(db: &Pool<Postgres>) -> Result<VapidKey, PushError> {
+        if let Ok(private_key) = std::env::var("VAPID_PRIVATE_KEY") {
+            return VapidKey::from_private_key(&private_key).map_err(PushError::WebPush);
+        }
+
+        let private_key = match db::push_subscription::get_vapid_private_key(db)
+            .await
+            .map_err(PushError::Sqlx)?
+        {
+            Some(private_key) => private_key,
+            None => {
+                let generated = VapidKey::generate().map_err(PushError::WebPush)?;
+                event!(Level::INFO, public_key = %generated.public_key, "Generated VAPID key");
+                // Another instance may have raced us, the stored key wins.
+                db::push_subscription::insert_vapid_private_key(db, &generated.private_key)
+                    .await
+                    .map_err(PushError::Sqlx)?
+            }
+        };
+
+        VapidKey::from_private_key(&private_key).map_err(PushError::WebPush)
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.vapid_key.public_key
+    }
+
+    pub async fn get_subscriptions(
+        &self,
+        actor: &User,
+    ) -> Result<Vec<PushSubscription>, PushError> {
+        db::push_subscription::get_push_subscriptions(&self.db, actor.id)
+            .await
+            .map_err(PushError::Sqlx)
+    }
+
+    pub async fn subscribe(
+        &self,
+        actor: &User,
+        subscription: NewPushSubscription,
+    ) -> Result<PushSubscription, PushError> {
+        let is_https = reqwest::Url::parse(&subscription.endpoint)
+            .is_ok_and(|endpoint| endpoint.scheme() == "https");
+        if !is_https {
+            return Err(PushError::Invalid(
+                "endpoint must be an https URL".to_string(),
+            ));
+        }
+        // The endpoint is supplied by the browser, i.e. by the user, so it
+        // gets the same check as a webhook URL.
+        resolve_public(&subscription.endpoint)
+            .await
+            .map_err(PushError::Invalid)?;
+        // Fails for keys the encryption can't use.
+        web_push::encrypt(b"", &subscription.p256dh, &subscription.auth)
+            .map_err(PushError::WebPush)?;
+
+        db::push_subscription::upsert_push_subscription(
+            &self.db,
+            InsertPushSubscription {
+                user_id: actor.id,
+                endpoint: subscription.endpoint,
+                p256dh: subscription.p256dh,
+                auth: subscription.auth,
+                device_name: subscription.device_name,
+            },
+        )
+        .await
+        .map_err(PushError::Sqlx)
+    }
+
+    pub async fn unsubscribe(&self, actor: &User, id: i32) -> Result<(), PushError> {
+        let deleted = db::push_subscription::delete_push_subscription(&self.db, id, actor.id)
+            .await
+            .map_err(PushError::Sqlx)?;
+
+        if deleted {
+            Ok(())
+        } else {
+            Err(PushError::NotFound(id))
+        }
+    }
+
+    /// Pushes ledger events to the devices of the affected users as they are
+    /// published.
+    pub fn spawn(self, events: &EventService) {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(ledger_event) => {
+                        if let Err(push_error) = self.notify(&ledger_event).await {
+                            event!(Level::ERROR, %push_error, "Failed to push notification");
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, skipped, "Push fell behind, skipping events");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    async fn notify(&self, ledger_event: &LedgerEvent) -> Result<(), PushError> {
+        let (expense_id, actor_id, recipients, verb) = match ledger_event {
+            LedgerEvent::ExpenseCreated {
+                expense_id,
+                actor_id,
+                participant_ids,
+                ..
+            } => (*expense_id, *actor_id, participant_ids.clone(), "lade till"),
+            LedgerEvent::ExpenseUpdated {
+                expense_id,
+                actor_id,
+                participant_ids,
+                ..
+            } => (*expense_id, *actor_id, participant_ids.clone(), "ändrade"),
+            LedgerEvent::SettlementRecorded {
+                expense_id,
+                actor_id,
+                receiver_id,
+                ..
+            } => (*expense_id, *actor_id, vec![*receiver_id], "registrerade"),
+            LedgerEvent::ExpenseConfirmed {
+                expense_id,
+                actor_id,
+                participant_ids,
+                ..
+            } => (*expense_id, *actor_id, participant_ids.clone(), "godkände"),
+            LedgerEvent::ExpenseDisputed {
+                expense_id,
+                actor_id,
+                participant_ids,
+                ..
+            } => (*expense_id, *actor_id, participant_ids.clone(), "bestred"),
+            LedgerEvent::ExpenseCommented {
+                expense_id,
+                actor_id,
+                participant_ids,
+                ..
+            } => (
+                *expense_id,
+                *actor_id,
+                participant_ids.clone(),
+                "kommenterade",
+            ),
+            _ => return Ok(()),
+        };
+
+        let mut conn = self.db.acquire().await.map_err(PushError::Sqlx)?;
+        let Some((expense, _)) = db::expense::get_expense(expense_id, &mut conn)
+            .await
+            .map_err(PushError::Sqlx)?
+        else {
+            return Ok(());
+        };
+        let actor_name = db::user::get_user_by_id(&self.db, actor_id)
+            .await
+            .map_err(PushError::Sqlx)?
+            .map(|actor| actor.name)
+            .unwrap_or_default();
+
+        let message = PushMessage {
+            title: expense.expense.name.clone(),
+            body: format!(
+                "{} {} {} ({})",
+                actor_name,
+                verb,
+                expense.expense.name,
+                format_with_currency(expense.expense.total as i64, &expense.expense.currency)
+            ),
+            tag: format!("expense-{}", expense_id),
+            expense_id,
+        };
+        let payload = serde_json::to_vec(&message).expect("Push messages serialize");
+
+        for user_id in recipients.into_iter().filter(|id| *id != actor_id) {
+            let subscriptions = db::push_subscription::get_push_subscriptions(&self.db, user_id)
+                .await
+                .map_err(PushError::Sqlx)?;
+            for subscription in subscriptions {
+                if let Err(push_error) = self.send(&subscription, &payload).await {
+                    event!(Level::WARN, %push_error, subscription.id, "Failed to push");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Subscriptions the push service no longer knows about are deleted.
+    async fn send(&self, subscription: &PushSubscription, payload: &[u8]) -> Result<(), PushError> {
+        let body = web_push::encrypt(payload, &subscription.p256dh, &subscription.auth)
+            .map_err(PushError::WebPush)?;
+        let authorization = self
+            .vapid_key
+            .authorization(&subscription.endpoint, &self.subject)
+            .map_err(PushError::WebPush)?;
+
+        // Resolved again for every push, as where a host points can change
+        // after subscribing.
+        let (host, addrs) = resolve_public(&subscription.endpoint)
+            .await
+            .map_err(PushError::Invalid)?;
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .resolve_to_addrs(&host, &addrs)
+            .build()
+            .map_err(PushError::Http)?;
+
+        let response = client
+            .post(&subscription.endpoint)
+            .header("Authorization", authorization)
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .header("TTL", PUSH_TTL.to_string())
+            .body(body)
+            .send()
+            .await
+            .map_err(PushError::Http)?;
+
+        match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::GONE => {
+                event!(
+                    Level::INFO,
+                    subscription.id,
+                    "Pruning expired push subscription"
+                );
+                db::push_subscription::delete_push_subscription_by_endpoint(
+                    &self.db,
+                    &subscription.endpoint,
+                )
+                .await
+                .map_err(PushError::Sqlx)
+            }
+            _ => response
+                .error_for_status()
+                .map(|_| ())
+                .map_err(PushError::Http),
+        }
+    }
+}
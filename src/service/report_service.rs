@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    report::{CategoryTotal, PeriodTotal, ReportFilter, UserTotal},
+    user::User,
+};
+
+#[derive(Debug, Clone)]
+pub struct ReportService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid report: {0}")]
+    Invalid(String),
+}
+
+#[derive(Clone, Copy)]
+pub enum ReportInterval {
+    Week,
+    Month,
+}
+
+impl ReportInterval {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReportInterval::Week => "week",
+            ReportInterval::Month => "month",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ParticipantTotal {
+    pub user_id: i32,
+    /// The user's part of the costs.
+    pub consumed: i64,
+    /// Cash paid for expenses, regardless of who consumed it.
+    pub paid: i64,
+    pub settlements_paid: i64,
+    pub settlements_received: i64,
+}
+
+pub struct SpendingReport {
+    pub filter: ReportFilter,
+    pub total_consumed: i64,
+    pub by_category: Vec<CategoryTotal>,
+    pub by_payer: Vec<UserTotal>,
+    pub by_participant: Vec<ParticipantTotal>,
+    pub by_period: Vec<PeriodTotal>,
+}
+
+impl ReportService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Aggregates the spending within a group. When the filter is narrowed to
+    /// a user, consumption only counts that user's part while payments and
+    /// settlements still cover the whole group.
+    pub async fn get_spending_report(
+        &self,
+        actor: &User,
+        filter: ReportFilter,
+        interval: ReportInterval,
+    ) -> Result<SpendingReport, ReportError> {
+        if filter.from >= filter.to {
+            return Err(ReportError::Invalid("from must be before to".to_string()));
+        }
+        let is_member = db::group::is_member(&self.db, filter.group_id, actor.id)
+            .await
+            .map_err(ReportError::Sqlx)?;
+        if !is_member {
+            return Err(ReportError::NotMember(actor.id, filter.group_id));
+        }
+
+        let by_category = db::report::get_consumption_by_category(&self.db, &filter)
+            .await
+            .map_err(ReportError::Sqlx)?;
+        let by_period = db::report::get_consumption_by_period(&self.db, &filter, interval.as_str())
+            .await
+            .map_err(ReportError::Sqlx)?;
+        let consumed = db::report::get_consumption_by_user(&self.db, &filter)
+            .await
+            .map_err(ReportError::Sqlx)?;
+        let by_payer = db::report::get_paid_by_user(&self.db, &filter, false)
+            .await
+            .map_err(ReportError::Sqlx)?;
+        let settlements_paid = db::report::get_paid_by_user(&self.db, &filter, true)
+            .await
+            .map_err(ReportError::Sqlx)?;
+        let settlements_received = db::report::get_settlements_received_by_user(&self.db, &filter)
+            .await
+            .map_err(ReportError::Sqlx)?;
+
+        let mut participants: BTreeMap<i32, ParticipantTotal> = BTreeMap::new();
+        for total in &consumed {
+            participant(&mut participants, total.user_id).consumed += total.amount;
+        }
+        for total in &by_payer {
+            participant(&mut participants, total.user_id).paid += total.amount;
+        }
+        for total in &settlements_paid {
+            participant(&mut participants, total.user_id).settlements_paid += total.amount;
+        }
+        for total in &settlements_received {
+            participant(&mut participants, total.user_id).settlements_received += total.amount;
+        }
+
+        Ok(SpendingReport {
+            total_consumed: consumed.iter().map(|total| total.amount).sum(),
+            filter,
+            by_category,
+            by_payer,
+            by_participant: participants.into_values().collect(),
+            by_period,
+        })
+    }
+}
+
+fn participant(
+    participants: &mut BTreeMap<i32, ParticipantTotal>,
+    user_id: i32,
+) -> &mut ParticipantTotal {
+    participants
+        .entry(user_id)
+        .or_insert_with(|| ParticipantTotal {
+            user_id,
+            ..Default::default()
+        })
+}
@@ -0,0 +1,172 @@
+use sqlx::{Pool, Postgres};
+
+use crate::db::{self, user::User};
+
+use super::swish;
+
+const SWISH_CURRENCY: &str = "SEK";
+const DEFAULT_SWISH_MESSAGE: &str = "Betalning via jostrid.se";
+
+#[derive(Debug, Clone)]
+pub struct SettlementService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettlementError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("User {0} has no valid Swish number")]
+    NoSwishNumber(i32),
+
+    #[error("Invalid settlement: {0}")]
+    Invalid(String),
+}
+
+pub struct SuggestedSettlement {
+    pub payer_id: i32,
+    pub receiver_id: i32,
+    pub amount: i64,
+    pub currency: String,
+}
+
+pub struct SwishPayment {
+    pub settlement: SuggestedSettlement,
+    /// The receiver's normalised mobile number.
+    pub payee: String,
+    pub message: String,
+    pub deep_link: String,
+}
+
+impl SettlementService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    /// Payments that settle every balance of the group in the given currency.
+    /// The largest debts are paired with the largest credits first, which
+    /// keeps the number of payments low.
+    pub async fn suggest_settlements(
+        &self,
+        actor: &User,
+        group_id: i32,
+        currency: &str,
+    ) -> Result<Vec<SuggestedSettlement>, SettlementError> {
+        self.ensure_member(actor.id, group_id).await?;
+        let balances = db::balance::get_group_balance(&self.db, group_id, currency)
+            .await
+            .map_err(SettlementError::Sqlx)?;
+
+        let mut debtors: Vec<(i32, i64)> = balances
+            .iter()
+            .filter(|balance| balance.balance < 0)
+            .map(|balance| (balance.user_id, -balance.balance))
+            .collect();
+        let mut creditors: Vec<(i32, i64)> = balances
+            .iter()
+            .filter(|balance| balance.balance > 0)
+            .map(|balance| (balance.user_id, balance.balance))
+            .collect();
+        debtors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        creditors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut settlements = Vec::new();
+        let (mut d, mut c) = (0, 0);
+        while d < debtors.len() && c < creditors.len() {
+            let amount = debtors[d].1.min(creditors[c].1);
+            settlements.push(SuggestedSettlement {
+                payer_id: debtors[d].0,
+                receiver_id: creditors[c].0,
+                amount,
+                currency: currency.to_string(),
+            });
+
+            debtors[d].1 -= amount;
+            creditors[c].1 -= amount;
+            if debtors[d].1 == 0 {
+                d += 1;
+            }
+            if creditors[c].1 == 0 {
+                c += 1;
+            }
+        }
+
+        Ok(settlements)
+    }
+
+    /// A prefilled Swish payment from `actor` to `receiver_id`. The amount
+    /// defaults to the suggested settlement between the two.
+    pub async fn swish_payment(
+        &self,
+        actor: &User,
+        group_id: i32,
+        receiver_id: i32,
+        amount: Option<i64>,
+        message: Option<String>,
+    ) -> Result<SwishPayment, SettlementError> {
+        self.ensure_member(actor.id, group_id).await?;
+        self.ensure_member(receiver_id, group_id).await?;
+
+        let amount = match amount {
+            Some(amount) if amount <= 0 => {
+                return Err(SettlementError::Invalid(
+                    "amount must be positive".to_string(),
+                ))
+            }
+            Some(amount) => amount,
+            None => self
+                .suggest_settlements(actor, group_id, SWISH_CURRENCY)
+                .await?
+                .into_iter()
+                .find(|settlement| {
+                    settlement.payer_id == actor.id && settlement.receiver_id == receiver_id
+                })
+                .map(|settlement| settlement.amount)
+                .ok_or_else(|| {
+                    SettlementError::Invalid(format!(
+                        "no settlement from user {} to user {} is suggested",
+                        actor.id, receiver_id
+                    ))
+                })?,
+        };
+
+        let receiver = db::user::get_user_by_id(&self.db, receiver_id)
+            .await
+            .map_err(SettlementError::Sqlx)?
+            .ok_or(SettlementError::NotMember(receiver_id, group_id))?;
+        let payee = receiver
+            .phone_number
+            .as_deref()
+            .and_then(swish::normalize_phone_number)
+            .ok_or(SettlementError::NoSwishNumber(receiver_id))?;
+        let message = swish::truncate_message(message.as_deref().unwrap_or(DEFAULT_SWISH_MESSAGE));
+
+        Ok(SwishPayment {
+            deep_link: swish::deep_link(&payee, amount, &message),
+            settlement: SuggestedSettlement {
+                payer_id: actor.id,
+                receiver_id,
+                amount,
+                currency: SWISH_CURRENCY.to_string(),
+            },
+            payee,
+            message,
+        })
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), SettlementError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(SettlementError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(SettlementError::NotMember(user_id, group_id))
+        }
+    }
+}
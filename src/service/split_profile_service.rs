@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use sqlx::{Pool, Postgres};
+
+use crate::db::{
+    self,
+    split_profile::{InsertSplitProfile, InsertSplitProfileWeight, SplitProfileWithWeights},
+    user::User,
+};
+
+#[derive(Debug, Clone)]
+pub struct SplitProfileService {
+    db: Pool<Postgres>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SplitProfileError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Split profile {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid split profile: {0}")]
+    Invalid(String),
+}
+
+pub struct NewSplitProfile {
+    pub group_id: i32,
+    pub name: String,
+    pub category_id: Option<i32>,
+    pub is_default: bool,
+    pub weights: Vec<NewSplitProfileWeight>,
+}
+
+pub struct NewSplitProfileWeight {
+    pub user_id: i32,
+    pub weight: i32,
+}
+
+impl SplitProfileService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self { db }
+    }
+
+    pub async fn get_profiles(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<SplitProfileWithWeights>, SplitProfileError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::split_profile::get_profiles(&self.db, group_id)
+            .await
+            .map_err(SplitProfileError::Sqlx)
+    }
+
+    pub async fn create_profile(
+        &self,
+        actor: &User,
+        profile: NewSplitProfile,
+    ) -> Result<SplitProfileWithWeights, SplitProfileError> {
+        self.ensure_member(actor.id, profile.group_id).await?;
+        let profile = self.validate_profile(None, profile).await?;
+
+        let mut conn = self.db.acquire().await.map_err(SplitProfileError::Sqlx)?;
+        let id = db::split_profile::insert_profile(&mut conn, profile)
+            .await
+            .map_err(SplitProfileError::Sqlx)?;
+
+        self.get_profile(actor, id).await
+    }
+
+    /// The group of a profile can't be changed.
+    pub async fn update_profile(
+        &self,
+        actor: &User,
+        id: i32,
+        profile: NewSplitProfile,
+    ) -> Result<SplitProfileWithWeights, SplitProfileError> {
+        let (existing, _) = self.get_profile(actor, id).await?;
+        let profile = self
+            .validate_profile(
+                Some(id),
+                NewSplitProfile {
+                    group_id: existing.group_id,
+                    ..profile
+                },
+            )
+            .await?;
+
+        let mut conn = self.db.acquire().await.map_err(SplitProfileError::Sqlx)?;
+        db::split_profile::update_profile(&mut conn, id, profile)
+            .await
+            .map_err(SplitProfileError::Sqlx)?;
+
+        self.get_profile(actor, id).await
+    }
+
+    pub async fn delete_profile(&self, actor: &User, id: i32) -> Result<(), SplitProfileError> {
+        self.get_profile(actor, id).await?;
+
+        db::split_profile::delete_profile(&self.db, id)
+            .await
+            .map_err(SplitProfileError::Sqlx)
+    }
+
+    async fn get_profile(
+        &self,
+        actor: &User,
+        id: i32,
+    ) -> Result<SplitProfileWithWeights, SplitProfileError> {
+        let profile = db::split_profile::get_profile(&self.db, id)
+            .await
+            .map_err(SplitProfileError::Sqlx)?
+            .ok_or(SplitProfileError::NotFound(id))?;
+        self.ensure_member(actor.id, profile.0.group_id).await?;
+
+        Ok(profile)
+    }
+
+    /// Weights must belong to members of the group and a category can have
+    /// only one profile per group.
+    async fn validate_profile(
+        &self,
+        id: Option<i32>,
+        profile: NewSplitProfile,
+    ) -> Result<InsertSplitProfile, SplitProfileError> {
+        let name = profile.name.trim().to_string();
+        if name.is_empty() {
+            return Err(SplitProfileError::Invalid(
+                "name must not be empty".to_string(),
+            ));
+        }
+        if profile.is_default && profile.category_id.is_some() {
+            return Err(SplitProfileError::Invalid(
+                "a category profile can't be the default".to_string(),
+            ));
+        }
+
+        if profile.weights.iter().any(|weight| weight.weight < 0) {
+            return Err(SplitProfileError::Invalid(
+                "weights must not be negative".to_string(),
+            ));
+        }
+        if profile.weights.iter().all(|weight| weight.weight == 0) {
+            return Err(SplitProfileError::Invalid(
+                "weights must not all be zero".to_string(),
+            ));
+        }
+        let mut seen = HashSet::new();
+        if let Some(duplicate) = profile
+            .weights
+            .iter()
+            .find(|weight| !seen.insert(weight.user_id))
+        {
+            return Err(SplitProfileError::Invalid(format!(
+                "user {} has more than one weight",
+                duplicate.user_id
+            )));
+        }
+
+        let member_ids = db::group::get_member_ids(&self.db, profile.group_id)
+            .await
+            .map_err(SplitProfileError::Sqlx)?;
+        if let Some(weight) = profile
+            .weights
+            .iter()
+            .find(|weight| !member_ids.contains(&weight.user_id))
+        {
+            return Err(SplitProfileError::Invalid(format!(
+                "user {} is not a member of group {}",
+                weight.user_id, profile.group_id
+            )));
+        }
+
+        if let Some(category_id) = profile.category_id {
+            let category = db::expense_category::get_expense_category(&self.db, category_id)
+                .await
+                .map_err(SplitProfileError::Sqlx)?;
+            let is_visible = category.is_some_and(|category| {
+                category
+                    .group_id
+                    .is_none_or(|group_id| group_id == profile.group_id)
+            });
+            if !is_visible {
+                return Err(SplitProfileError::Invalid(format!(
+                    "category {} is not available in group {}",
+                    category_id, profile.group_id
+                )));
+            }
+
+            let profiles = db::split_profile::get_profiles(&self.db, profile.group_id)
+                .await
+                .map_err(SplitProfileError::Sqlx)?;
+            let is_taken = profiles
+                .iter()
+                .any(|(other, _)| other.category_id == Some(category_id) && Some(other.id) != id);
+            if is_taken {
+                return Err(SplitProfileError::Invalid(format!(
+                    "category {} already has a split profile",
+                    category_id
+                )));
+            }
+        }
+
+        Ok(InsertSplitProfile {
+            group_id: profile.group_id,
+            name,
+            category_id: profile.category_id,
+            is_default: profile.is_default,
+            weights: profile
+                .weights
+                .into_iter()
+                .map(|weight| InsertSplitProfileWeight {
+                    user_id: weight.user_id,
+                    weight: weight.weight,
+                })
+                .collect(),
+        })
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), SplitProfileError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(SplitProfileError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(SplitProfileError::NotMember(user_id, group_id))
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::db::period_close::StatementLine;
+
+use super::{
+    amount::format_with_currency,
+    period_close_service::{CurrencyStatement, Statement},
+};
+
+/// A4 in points.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const FONT_SIZE: f32 = 10.0;
+const LEADING: f32 = 14.0;
+/// Where the date, name, payer, total and share of a line start.
+const COLUMNS: [f32; 5] = [50.0, 115.0, 300.0, 400.0, 480.0];
+/// What fits between the columns of names and payers.
+const MAX_NAME_CHARS: usize = 34;
+const MAX_PAYER_CHARS: usize = 18;
+
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+fn title(statement: &Statement) -> String {
+    format!("Kontoutdrag för {}", statement.user.name)
+}
+
+fn period(statement: &Statement) -> String {
+    let to = statement.close.closed_until.format("%Y-%m-%d");
+    match statement.from {
+        Some(from) => format!("{} till {}", from.format("%Y-%m-%d"), to),
+        None => format!("till {}", to),
+    }
+}
+
+fn line_cells(line: &StatementLine) -> [String; 5] {
+    [
+        line.created_at.format("%Y-%m-%d").to_string(),
+        line.name.clone(),
+        line.paid_by_name.clone(),
+        format_with_currency(line.total as i64, &line.currency),
+        format_with_currency(line.share, &line.currency),
+    ]
+}
+
+fn sum(lines: &[StatementLine]) -> i64 {
+    lines.iter().map(|line| line.share).sum()
+}
+
+/// A standalone document, ready to be printed from a browser.
+pub fn render_html(statement: &Statement) -> String {
+    let mut html =
+        String::from("<!DOCTYPE html>\n<html lang=\"sv\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(&title(statement))));
+    html.push_str(
+        "<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;width:100%}\
+th,td{text-align:left;padding:2px 8px}td.amount,th.amount{text-align:right}</style>\n",
+    );
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", escape(&title(statement))));
+    html.push_str(&format!(
+        "<p>Grupp: {}<br>Period: {}</p>\n",
+        escape(&statement.group.name),
+        escape(&period(statement))
+    ));
+
+    if statement.currencies.is_empty() {
+        html.push_str("<p>Inga utgifter under perioden.</p>\n");
+    }
+    for currency in &statement.currencies {
+        html.push_str(&format!("<h2>{}</h2>\n", escape(&currency.currency)));
+        html.push_str(&format!(
+            "<p>Ingående saldo: {}</p>\n",
+            escape(&format_with_currency(
+                currency.opening_balance,
+                &currency.currency
+            ))
+        ));
+        html_lines(&mut html, "Utgifter", &currency.expenses, currency);
+        html_lines(&mut html, "Betalningar", &currency.settlements, currency);
+        html.push_str(&format!(
+            "<p><strong>Utgående saldo: {}</strong></p>\n",
+            escape(&format_with_currency(
+                currency.closing_balance,
+                &currency.currency
+            ))
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+fn html_lines(
+    html: &mut String,
+    heading: &str,
+    lines: &[StatementLine],
+    currency: &CurrencyStatement,
+) {
+    html.push_str(&format!("<h3>{}</h3>\n", heading));
+    if lines.is_empty() {
+        html.push_str("<p>Inga.</p>\n");
+        return;
+    }
+
+    html.push_str(
+        "<table>\n<tr><th>Datum</th><th>Namn</th><th>Betalt av</th>\
+<th class=\"amount\">Totalt</th><th class=\"amount\">Din andel</th></tr>\n",
+    );
+    for line in lines {
+        let [date, name, paid_by, total, share] = line_cells(line);
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>\n",
+            escape(&date),
+            escape(&name),
+            escape(&paid_by),
+            escape(&total),
+            escape(&share)
+        ));
+    }
+    html.push_str(&format!(
+        "<tr><th colspan=\"4\">Summa</th><th class=\"amount\">{}</th></tr>\n</table>\n",
+        escape(&format_with_currency(sum(lines), &currency.currency))
+    ));
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Writes text top to bottom, starting a new page whenever one is full.
+struct PdfPages {
+    pages: Vec<Content>,
+    y: f32,
+}
+
+impl PdfPages {
+    fn new() -> Self {
+        let mut pages = Self {
+            pages: Vec::new(),
+            y: 0.0,
+        };
+        pages.new_page();
+        pages
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Content::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Writes one line of cells, each starting at its own x coordinate.
+    fn line(&mut self, font: Name, size: f32, cells: &[(f32, &str)]) {
+        if self.y < MARGIN {
+            self.new_page();
+        }
+        let y = self.y;
+        let content = self.pages.last_mut().expect("There is always a page");
+        for (x, text) in cells {
+            content
+                .begin_text()
+                .set_font(font, size)
+                .next_line(*x, y)
+                .show(Str(&latin1(text)))
+                .end_text();
+        }
+        self.y -= LEADING * size / FONT_SIZE;
+    }
+
+    fn text(&mut self, text: &str) {
+        self.line(REGULAR, FONT_SIZE, &[(MARGIN, text)]);
+    }
+
+    fn skip(&mut self) {
+        self.y -= LEADING / 2.0;
+    }
+}
+
+/// A document of plain text using the standard Helvetica fonts every PDF
+/// reader has, so no font has to be embedded.
+pub fn render_pdf(statement: &Statement) -> Vec<u8> {
+    let mut pages = PdfPages::new();
+    pages.line(BOLD, 16.0, &[(MARGIN, &title(statement))]);
+    pages.text(&format!("Grupp: {}", statement.group.name));
+    pages.text(&format!("Period: {}", period(statement)));
+
+    if statement.currencies.is_empty() {
+        pages.skip();
+        pages.text("Inga utgifter under perioden.");
+    }
+    for currency in &statement.currencies {
+        pages.skip();
+        pages.line(BOLD, 12.0, &[(MARGIN, &currency.currency)]);
+        pages.text(&format!(
+            "Ingående saldo: {}",
+            format_with_currency(currency.opening_balance, &currency.currency)
+        ));
+        pdf_lines(&mut pages, "Utgifter", &currency.expenses, currency);
+        pdf_lines(&mut pages, "Betalningar", &currency.settlements, currency);
+        pages.skip();
+        pages.line(
+            BOLD,
+            FONT_SIZE,
+            &[(
+                MARGIN,
+                &format!(
+                    "Utgående saldo: {}",
+                    format_with_currency(currency.closing_balance, &currency.currency)
+                ),
+            )],
+        );
+    }
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let regular_id = Ref::new(3);
+    let bold_id = Ref::new(4);
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    let mut page_ids = Vec::new();
+    for (i, content) in pages.pages.into_iter().enumerate() {
+        let page_id = Ref::new(5 + 2 * i as i32);
+        let content_id = Ref::new(6 + 2 * i as i32);
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources()
+            .fonts()
+            .pair(REGULAR, regular_id)
+            .pair(BOLD, bold_id);
+        page.finish();
+        pdf.stream(content_id, &content.finish());
+        page_ids.push(page_id);
+    }
+    pdf.pages(page_tree_id)
+        .count(page_ids.len() as i32)
+        .kids(page_ids);
+
+    pdf.finish()
+}
+
+fn pdf_lines(
+    pages: &mut PdfPages,
+    heading: &str,
+    lines: &[StatementLine],
+    currency: &CurrencyStatement,
+) {
+    pages.skip();
+    pages.line(BOLD, FONT_SIZE, &[(MARGIN, heading)]);
+    if lines.is_empty() {
+        pages.text("Inga.");
+        return;
+    }
+
+    let header = ["Datum", "Namn", "Betalt av", "Totalt", "Din andel"];
+    pages.line(
+        BOLD,
+        FONT_SIZE,
+        &std::array::from_fn::<_, 5, _>(|i| (COLUMNS[i], header[i])),
+    );
+    for line in lines {
+        let mut cells = line_cells(line);
+        cells[1] = truncate(&cells[1], MAX_NAME_CHARS);
+        cells[2] = truncate(&cells[2], MAX_PAYER_CHARS);
+        pages.line(
+            REGULAR,
+            FONT_SIZE,
+            &std::array::from_fn::<_, 5, _>(|i| (COLUMNS[i], cells[i].as_str())),
+        );
+    }
+    let total = format_with_currency(sum(lines), &currency.currency);
+    pages.line(
+        BOLD,
+        FONT_SIZE,
+        &[(COLUMNS[0], "Summa"), (COLUMNS[4], total.as_str())],
+    );
+}
+
+/// Keeps long names from running into the next column.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+/// The standard fonts only cover Latin-1, anything beyond it is replaced.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect()
+}
@@ -0,0 +1,149 @@
+use qrcode::{render::svg, Color, QrCode};
+
+use super::amount::format_amount;
+
+const SWISH_BASE_URL: &str = "https://app.swish.nu/1/p/sw/";
+/// Swish cuts longer messages.
+pub const MAX_MESSAGE_LENGTH: usize = 50;
+/// Empty modules around the code, as required by the QR specification.
+const QUIET_ZONE: usize = 4;
+/// Swish amounts use a decimal point.
+pub const DECIMAL_SEPARATOR: char = '.';
+
+/// Normalises a Swedish mobile number to the national format Swish expects,
+/// e.g. `+46 70-123 45 67` becomes `0701234567`. Returns `None` for anything
+/// that isn't a Swedish mobile number.
+pub fn normalize_phone_number(phone_number: &str) -> Option<String> {
+    let digits: String = phone_number
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '\u{a0}'))
+        .collect();
+    let national = if let Some(rest) = digits.strip_prefix("+46") {
+        format!("0{}", rest.trim_start_matches('0'))
+    } else if let Some(rest) = digits.strip_prefix("0046") {
+        format!("0{}", rest.trim_start_matches('0'))
+    } else if digits.starts_with("46") && digits.len() == 11 {
+        format!("0{}", &digits[2..])
+    } else {
+        digits
+    };
+
+    let is_mobile = national.len() == 10
+        && national.chars().all(|c| c.is_ascii_digit())
+        && ["070", "072", "073", "076", "079"]
+            .iter()
+            .any(|prefix| national.starts_with(prefix));
+
+    is_mobile.then_some(national)
+}
+
+pub fn truncate_message(message: &str) -> String {
+    message.trim().chars().take(MAX_MESSAGE_LENGTH).collect()
+}
+
+/// Link that opens Swish with the payment prefilled, letting the payer edit
+/// the amount and message.
+pub fn deep_link(payee: &str, amount: i64, message: &str) -> String {
+    let amount = format_amount(amount, DECIMAL_SEPARATOR);
+    reqwest::Url::parse_with_params(
+        SWISH_BASE_URL,
+        [
+            ("sw", payee),
+            ("amt", amount.as_str()),
+            ("msg", message),
+            ("edit", "amt,msg"),
+        ],
+    )
+    .expect("The Swish base URL is valid")
+    .to_string()
+}
+
+/// QR codes encode the deep link, which the Swish app recognises when
+/// scanning.
+fn qr_code(deep_link: &str) -> QrCode {
+    QrCode::new(deep_link.as_bytes()).expect("A deep link always fits in a QR code")
+}
+
+pub fn qr_svg(deep_link: &str) -> String {
+    qr_code(deep_link)
+        .render::<svg::Color>()
+        .min_dimensions(256, 256)
+        .quiet_zone(true)
+        .build()
+}
+
+/// Renders the QR code as a greyscale PNG with every module `scale` pixels
+/// wide.
+pub fn qr_png(deep_link: &str, scale: usize) -> Result<Vec<u8>, png::EncodingError> {
+    let code = qr_code(deep_link);
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * scale;
+
+    let mut pixels = vec![u8::MAX; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x = (i % modules + QUIET_ZONE) * scale;
+        let y = (i / modules + QUIET_ZONE) * scale;
+        for row in y..y + scale {
+            pixels[row * size + x..row * size + x + scale].fill(0);
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_phone_number;
+
+    #[test]
+    fn normalizes_swedish_mobile_numbers() {
+        for phone_number in [
+            "0701234567",
+            "070-123 45 67",
+            "(070) 123\u{a0}45\u{a0}67",
+            "+46 70-123 45 67",
+            "+46 070 123 45 67",
+            "0046701234567",
+            "46701234567",
+        ] {
+            assert_eq!(
+                normalize_phone_number(phone_number).as_deref(),
+                Some("0701234567"),
+                "{}",
+                phone_number
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_swedish_mobile_number() {
+        for phone_number in [
+            "",
+            "08-123 456 78",
+            "+47 70 123 45 67",
+            "070123456",
+            "07012345678",
+            "070123456a",
+            "070.123.45.67",
+        ] {
+            assert_eq!(
+                normalize_phone_number(phone_number),
+                None,
+                "{}",
+                phone_number
+            );
+        }
+    }
+}
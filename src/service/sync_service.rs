@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::db::{
+    self,
+    expense::{ExpenseFilter, ExpenseWithShares},
+    expense_category::ExpenseCategory,
+    sync::{SyncChange, SyncMutation, CATEGORY_ENTITY, EXPENSE_ENTITY},
+    user::User,
+};
+
+use super::{
+    event_service::EventService,
+    expense_service::{ExpenseChange, ExpenseError, ExpenseService, NewExpense},
+};
+
+#[derive(Debug, Clone)]
+pub struct SyncService {
+    db: Pool<Postgres>,
+    events: EventService,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SyncError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+}
+
+/// An expense together with the version a client has to send back when
+/// changing it.
+pub struct SyncedExpense {
+    pub expense: ExpenseWithShares,
+    pub version: Option<i64>,
+}
+
+/// Everything a client needs to bring its local copy up to date.
+pub struct SyncChanges {
+    /// To be sent with the next sync.
+    pub cursor: i64,
+    /// `true` when the client had no cursor and should replace its local copy
+    /// instead of merging into it.
+    pub full: bool,
+    pub expenses: Vec<SyncedExpense>,
+    pub deleted_expense_ids: Vec<i32>,
+    pub categories: Vec<ExpenseCategory>,
+    pub deleted_category_ids: Vec<i32>,
+}
+
+pub enum MutationTarget {
+    /// An expense known to the server.
+    Expense(i32),
+    /// An expense created offline by an earlier mutation with this client id.
+    Created(Uuid),
+}
+
+pub enum MutationKind {
+    Create(NewExpense),
+    Update {
+        target: MutationTarget,
+        base_version: Option<i64>,
+        expense: NewExpense,
+    },
+    Delete {
+        target: MutationTarget,
+        base_version: Option<i64>,
+    },
+}
+
+pub struct Mutation {
+    pub client_id: Uuid,
+    pub kind: MutationKind,
+}
+
+pub enum MutationStatus {
+    Applied,
+    /// The mutation was applied by an earlier request.
+    Duplicate,
+    /// The expense changed on the server since the client last synced it.
+    /// The server copy wins and is returned so the client can rebase.
+    Conflict,
+    Rejected(String),
+}
+
+pub struct MutationResult {
+    pub client_id: Uuid,
+    pub status: MutationStatus,
+    pub expense_id: Option<i32>,
+    /// Current server copy, `None` if the expense doesn't exist (anymore).
+    pub expense: Option<SyncedExpense>,
+}
+
+impl SyncService {
+    pub fn new(db: Pool<Postgres>, events: EventService) -> Self {
+        Self { db, events }
+    }
+
+    /// Changes visible to `actor` since `cursor`, or everything if there is
+    /// no cursor or it is from before purged tombstones. A change may be
+    /// returned more than once, but never missed.
+    pub async fn get_changes(
+        &self,
+        actor: &User,
+        cursor: Option<i64>,
+    ) -> Result<SyncChanges, SyncError> {
+        // Taken before reading, so anything committed while reading is
+        // returned again next time rather than skipped.
+        let next_cursor = db::sync::get_cursor(&self.db)
+            .await
+            .map_err(SyncError::Sqlx)?;
+        let horizon = db::sync::get_horizon(&self.db)
+            .await
+            .map_err(SyncError::Sqlx)?;
+        let cursor = cursor.filter(|cursor| horizon.is_none_or(|horizon| *cursor > horizon));
+
+        let Some(cursor) = cursor else {
+            let expenses = db::expense::get_expenses(&self.db, actor.id, ExpenseFilter::default())
+                .await
+                .map_err(SyncError::Sqlx)?;
+            let categories = db::sync::get_visible_categories(&self.db, actor.id)
+                .await
+                .map_err(SyncError::Sqlx)?;
+
+            return Ok(SyncChanges {
+                cursor: next_cursor,
+                full: true,
+                expenses: self.with_versions(expenses).await?,
+                deleted_expense_ids: Vec::new(),
+                categories,
+                deleted_category_ids: Vec::new(),
+            });
+        };
+
+        let changes = db::sync::get_changes_since(&self.db, actor.id, cursor)
+            .await
+            .map_err(SyncError::Sqlx)?;
+        let expense_ids = changed_ids(&changes, EXPENSE_ENTITY);
+        let category_ids = changed_ids(&changes, CATEGORY_ENTITY);
+
+        let expenses = db::expense::get_expenses_by_id(&self.db, actor.id, &expense_ids)
+            .await
+            .map_err(SyncError::Sqlx)?;
+        let categories = db::sync::get_categories_by_id(&self.db, &category_ids)
+            .await
+            .map_err(SyncError::Sqlx)?;
+
+        // Changed rows that can no longer be read were deleted, or moved out of
+        // the user's groups which looks the same to the client.
+        let found: HashSet<i32> = expenses.iter().map(|(e, _)| e.expense.id).collect();
+        let deleted_expense_ids = expense_ids
+            .into_iter()
+            .filter(|id| !found.contains(id))
+            .collect();
+        let found: HashSet<i32> = categories.iter().map(|category| category.id).collect();
+        let deleted_category_ids = category_ids
+            .into_iter()
+            .filter(|id| !found.contains(id))
+            .collect();
+
+        Ok(SyncChanges {
+            cursor: next_cursor,
+            full: false,
+            expenses: self.with_versions(expenses).await?,
+            deleted_expense_ids,
+            categories,
+            deleted_category_ids,
+        })
+    }
+
+    /// Applies the mutations in order, each in its own transaction, so one
+    /// rejected mutation doesn't hold back the rest of the batch.
+    ///
+    /// Conflicts are resolved the same way regardless of timing: an update or
+    /// delete only applies if the expense is still at the version the client
+    /// based it on, otherwise the server copy wins. Deleting an expense that
+    /// is already gone succeeds.
+    pub async fn apply_mutations(
+        &self,
+        actor: &User,
+        mutations: Vec<Mutation>,
+    ) -> Result<Vec<MutationResult>, SyncError> {
+        let mut results = Vec::new();
+        for mutation in mutations {
+            results.push(self.apply_mutation(actor, mutation).await?);
+        }
+
+        Ok(results)
+    }
+
+    async fn apply_mutation(
+        &self,
+        actor: &User,
+        mutation: Mutation,
+    ) -> Result<MutationResult, SyncError> {
+        let client_id = mutation.client_id;
+        let existing = db::sync::get_mutation(&self.db, client_id)
+            .await
+            .map_err(SyncError::Sqlx)?;
+        if let Some(existing) = existing {
+            if existing.user_id != actor.id {
+                return Ok(self.rejected(client_id, "client id is already in use"));
+            }
+            return self
+                .result(
+                    actor,
+                    client_id,
+                    MutationStatus::Duplicate,
+                    existing.expense_id,
+                )
+                .await;
+        }
+
+        let expense_service = ExpenseService::new(self.db.clone(), self.events.clone());
+        let mut tx = self.db.begin().await.map_err(SyncError::Sqlx)?;
+
+        let change = match mutation.kind {
+            MutationKind::Create(expense) => {
+                expense_service
+                    .create_expense_in(&mut tx, actor, expense)
+                    .await
+            }
+            MutationKind::Update {
+                target,
+                base_version,
+                expense,
+            } => {
+                let Some(expense_id) = self.resolve(&target).await? else {
+                    return Ok(self.rejected(client_id, "unknown expense"));
+                };
+                let version = db::sync::lock_expense_version(&mut *tx, expense_id)
+                    .await
+                    .map_err(SyncError::Sqlx)?;
+                if version != Some(base_version) {
+                    return self
+                        .result(actor, client_id, MutationStatus::Conflict, Some(expense_id))
+                        .await;
+                }
+
+                expense_service
+                    .update_expense_in(&mut tx, actor, expense_id, expense)
+                    .await
+            }
+            MutationKind::Delete {
+                target,
+                base_version,
+            } => {
+                let Some(expense_id) = self.resolve(&target).await? else {
+                    return Ok(self.rejected(client_id, "unknown expense"));
+                };
+                match db::sync::lock_expense_version(&mut *tx, expense_id)
+                    .await
+                    .map_err(SyncError::Sqlx)?
+                {
+                    None => {
+                        return self
+                            .result(actor, client_id, MutationStatus::Applied, Some(expense_id))
+                            .await
+                    }
+                    Some(version) if version != base_version => {
+                        return self
+                            .result(actor, client_id, MutationStatus::Conflict, Some(expense_id))
+                            .await
+                    }
+                    Some(_) => {}
+                }
+
+                expense_service
+                    .delete_expense_in(&mut tx, actor, expense_id)
+                    .await
+            }
+        };
+
+        let change: ExpenseChange = match change {
+            Ok(change) => change,
+            Err(ExpenseError::Sqlx(err)) => return Err(SyncError::Sqlx(err)),
+            Err(err) => return Ok(self.rejected(client_id, &err.to_string())),
+        };
+        let expense_id = change.expense.0.expense.id;
+
+        let recorded = db::sync::insert_mutation(
+            &mut *tx,
+            SyncMutation {
+                client_id,
+                user_id: actor.id,
+                expense_id: Some(expense_id),
+            },
+        )
+        .await
+        .map_err(SyncError::Sqlx)?;
+        if !recorded {
+            // The same mutation was applied concurrently, drop this copy.
+            tx.rollback().await.map_err(SyncError::Sqlx)?;
+            return self
+                .result(
+                    actor,
+                    client_id,
+                    MutationStatus::Duplicate,
+                    Some(expense_id),
+                )
+                .await;
+        }
+        tx.commit().await.map_err(SyncError::Sqlx)?;
+
+        expense_service.publish_change(&change).await;
+
+        self.result(actor, client_id, MutationStatus::Applied, Some(expense_id))
+            .await
+    }
+
+    async fn resolve(&self, target: &MutationTarget) -> Result<Option<i32>, SyncError> {
+        match target {
+            MutationTarget::Expense(expense_id) => Ok(Some(*expense_id)),
+            MutationTarget::Created(client_id) => Ok(db::sync::get_mutation(&self.db, *client_id)
+                .await
+                .map_err(SyncError::Sqlx)?
+                .and_then(|mutation| mutation.expense_id)),
+        }
+    }
+
+    async fn result(
+        &self,
+        actor: &User,
+        client_id: Uuid,
+        status: MutationStatus,
+        expense_id: Option<i32>,
+    ) -> Result<MutationResult, SyncError> {
+        let expense = match expense_id {
+            Some(expense_id) => {
+                let expenses = db::expense::get_expenses_by_id(&self.db, actor.id, &[expense_id])
+                    .await
+                    .map_err(SyncError::Sqlx)?;
+                self.with_versions(expenses).await?.pop()
+            }
+            None => None,
+        };
+
+        Ok(MutationResult {
+            client_id,
+            status,
+            expense_id,
+            expense,
+        })
+    }
+
+    fn rejected(&self, client_id: Uuid, reason: &str) -> MutationResult {
+        MutationResult {
+            client_id,
+            status: MutationStatus::Rejected(reason.to_string()),
+            expense_id: None,
+            expense: None,
+        }
+    }
+
+    async fn with_versions(
+        &self,
+        expenses: Vec<ExpenseWithShares>,
+    ) -> Result<Vec<SyncedExpense>, SyncError> {
+        let expense_ids: Vec<i32> = expenses.iter().map(|(e, _)| e.expense.id).collect();
+        let versions: HashMap<i32, i64> =
+            db::sync::get_versions(&self.db, EXPENSE_ENTITY, &expense_ids)
+                .await
+                .map_err(SyncError::Sqlx)?
+                .into_iter()
+                .map(|change| (change.entity_id, change.version))
+                .collect();
+
+        Ok(expenses
+            .into_iter()
+            .map(|expense| SyncedExpense {
+                version: versions.get(&expense.0.expense.id).copied(),
+                expense,
+            })
+            .collect())
+    }
+}
+
+fn changed_ids(changes: &[SyncChange], entity: &str) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    changes
+        .iter()
+        .filter(|change| change.entity == entity && seen.insert(change.entity_id))
+        .map(|change| change.entity_id)
+        .collect()
+}
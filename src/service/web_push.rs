@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::{
+    aead, agreement, hkdf,
+    rand::{SecureRandom, SystemRandom},
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde_json::json;
+
+/// Size of the single record a push message is encrypted into.
+const RECORD_SIZE: u32 = 4096;
+/// How long a VAPID token is valid, push services accept at most 24 hours.
+const VAPID_TOKEN_LIFETIME: i64 = 12 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebPushError {
+    #[error("Invalid VAPID key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid push subscription: {0}")]
+    InvalidSubscription(String),
+
+    #[error("Encryption failed")]
+    Crypto,
+}
+
+/// The server's application key, identifying it to push services (RFC 8292).
+#[derive(Clone)]
+pub struct VapidKey {
+    key_pair: Arc<EcdsaKeyPair>,
+    /// PKCS#8 document of the key pair, encoded as URL safe base64.
+    pub private_key: String,
+    /// Uncompressed P-256 point encoded as URL safe base64, used by clients as
+    /// `applicationServerKey`.
+    pub public_key: String,
+}
+
+impl std::fmt::Debug for VapidKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VapidKey")
+            .field("public_key", &self.public_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VapidKey {
+    pub fn generate() -> Result<Self, WebPushError> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .map_err(|_| WebPushError::InvalidKey("failed to generate key".to_string()))?;
+
+        Self::from_private_key(&URL_SAFE_NO_PAD.encode(pkcs8.as_ref()))
+    }
+
+    pub fn from_private_key(private_key: &str) -> Result<Self, WebPushError> {
+        let pkcs8 = URL_SAFE_NO_PAD
+            .decode(private_key.trim())
+            .map_err(|err| WebPushError::InvalidKey(err.to_string()))?;
+        let key_pair = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            &pkcs8,
+            &SystemRandom::new(),
+        )
+        .map_err(|err| WebPushError::InvalidKey(err.to_string()))?;
+
+        Ok(VapidKey {
+            public_key: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            private_key: private_key.trim().to_string(),
+            key_pair: Arc::new(key_pair),
+        })
+    }
+
+    /// Value of the `Authorization` header for a request to `endpoint`.
+    pub fn authorization(&self, endpoint: &str, subject: &str) -> Result<String, WebPushError> {
+        let url = reqwest::Url::parse(endpoint)
+            .map_err(|err| WebPushError::InvalidSubscription(err.to_string()))?;
+        let audience = url.origin().ascii_serialization();
+
+        let header = URL_SAFE_NO_PAD.encode(json!({ "typ": "JWT", "alg": "ES256" }).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(
+            json!({
+                "aud": audience,
+                "exp": chrono::Utc::now().timestamp() + VAPID_TOKEN_LIFETIME,
+                "sub": subject,
+            })
+            .to_string(),
+        );
+        let signing_input = format!("{}.{}", header, claims);
+        let signature = self
+            .key_pair
+            .sign(&SystemRandom::new(), signing_input.as_bytes())
+            .map_err(|_| WebPushError::Crypto)?;
+
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.as_ref()),
+            self.public_key
+        ))
+    }
+}
+
+struct Len(usize);
+
+impl hkdf::KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[&[u8]], len: usize) -> Result<Vec<u8>, WebPushError> {
+    let mut out = vec![0; len];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+        .extract(ikm)
+        .expand(info, Len(len))
+        .and_then(|okm| okm.fill(&mut out))
+        .map_err(|_| WebPushError::Crypto)?;
+
+    Ok(out)
+}
+
+/// Encrypts `payload` for a subscription with the `aes128gcm` content coding
+/// (RFC 8291 and RFC 8188). `p256dh` and `auth` are the keys of the
+/// subscription, encoded as URL safe base64.
+pub fn encrypt(payload: &[u8], p256dh: &str, auth: &str) -> Result<Vec<u8>, WebPushError> {
+    let decode = |value: &str| {
+        URL_SAFE_NO_PAD
+            .decode(value.trim_end_matches('='))
+            .map_err(|err| WebPushError::InvalidSubscription(err.to_string()))
+    };
+    let ua_public = decode(p256dh)?;
+    let auth_secret = decode(auth)?;
+
+    let rng = SystemRandom::new();
+    let private_key = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng)
+        .map_err(|_| WebPushError::Crypto)?;
+    let as_public = private_key
+        .compute_public_key()
+        .map_err(|_| WebPushError::Crypto)?;
+    let ecdh_secret = agreement::agree_ephemeral(
+        private_key,
+        &agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, &ua_public),
+        |secret| secret.to_vec(),
+    )
+    .map_err(|_| WebPushError::InvalidSubscription("invalid p256dh key".to_string()))?;
+
+    let ikm = hkdf(
+        &auth_secret,
+        &ecdh_secret,
+        &[b"WebPush: info\0", &ua_public, as_public.as_ref()],
+        32,
+    )?;
+    let mut salt = [0; 16];
+    rng.fill(&mut salt).map_err(|_| WebPushError::Crypto)?;
+    let cek = hkdf(&salt, &ikm, &[b"Content-Encoding: aes128gcm\0"], 16)?;
+    let nonce = hkdf(&salt, &ikm, &[b"Content-Encoding: nonce\0"], 12)?;
+
+    // A single record, ended by the padding delimiter.
+    let mut record = payload.to_vec();
+    record.push(2);
+    let key = aead::LessSafeKey::new(
+        aead::UnboundKey::new(&aead::AES_128_GCM, &cek).map_err(|_| WebPushError::Crypto)?,
+    );
+    key.seal_in_place_append_tag(
+        aead::Nonce::try_assume_unique_for_key(&nonce).map_err(|_| WebPushError::Crypto)?,
+        aead::Aad::empty(),
+        &mut record,
+    )
+    .map_err(|_| WebPushError::Crypto)?;
+
+    let mut body = Vec::with_capacity(16 + 4 + 1 + as_public.as_ref().len() + record.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_ref().len() as u8);
+    body.extend_from_slice(as_public.as_ref());
+    body.extend_from_slice(&record);
+
+    Ok(body)
+}
@@ -0,0 +1,515 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use reqwest::{redirect::Policy, Url};
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::Serialize;
+use sqlx::{PgConnection, Pool, Postgres};
+use tokio::{
+    net::lookup_host,
+    sync::{broadcast::error::RecvError, Notify},
+};
+use tracing::{event, Level};
+
+use crate::db::{
+    self,
+    expense::{AccountShare, Expense},
+    expense_category::ExpenseCategory,
+    user::User,
+    webhook::{
+        DeliveryResult, DueDelivery, InsertWebhook, UpdateWebhook, Webhook, WebhookDelivery,
+    },
+};
+
+use super::event_service::{EventService, LedgerEvent};
+
+/// The ledger events a webhook can subscribe to, named like
+/// [`LedgerEvent::name`].
+pub const WEBHOOK_EVENTS: [&str; 8] = [
+    "expense_created",
+    "expense_updated",
+    "expense_deleted",
+    "settlement_recorded",
+    "expense_confirmed",
+    "expense_disputed",
+    "expense_commented",
+    "budget_exceeded",
+];
+
+/// Attempts before a delivery is given up on. With the backoff below the last
+/// one is made about four hours after the first.
+const MAX_ATTEMPTS: i32 = 10;
+const RETRY_BASE_SECONDS: i64 = 30;
+/// How long a claimed delivery is left alone by other instances.
+const LEASE_SECONDS: i64 = 60;
+const BATCH_SIZE: i64 = 20;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How much of an error to keep in the delivery log.
+const MAX_ERROR_LENGTH: usize = 1000;
+
+#[derive(Clone)]
+pub struct WebhookService {
+    db: Pool<Postgres>,
+    /// Wakes the delivery loop when something has been queued.
+    queued: Arc<Notify>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error(transparent)]
+    Sqlx(sqlx::Error),
+
+    #[error("Webhook {0} not found")]
+    NotFound(i32),
+
+    #[error("User {0} is not a member of group {1}")]
+    NotMember(i32, i32),
+
+    #[error("Invalid webhook: {0}")]
+    Invalid(String),
+}
+
+pub struct NewWebhook {
+    pub group_id: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    pub description: Option<String>,
+    pub active: bool,
+}
+
+/// What is posted to the webhook URL.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    event: &'a str,
+    group_id: i32,
+    occurred_at: chrono::DateTime<Utc>,
+    data: &'a LedgerEvent,
+    /// The expense as it is after the event, `None` once it has been deleted.
+    expense: Option<WebhookExpense>,
+}
+
+#[derive(Serialize)]
+struct WebhookExpense {
+    #[serde(flatten)]
+    expense: Expense,
+    paid_by: i32,
+    category: Option<ExpenseCategory>,
+    shares: Vec<AccountShare>,
+}
+
+impl WebhookService {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        Self {
+            db,
+            queued: Arc::new(Notify::new()),
+        }
+    }
+
+    pub async fn get_webhooks(
+        &self,
+        actor: &User,
+        group_id: i32,
+    ) -> Result<Vec<Webhook>, WebhookError> {
+        self.ensure_member(actor.id, group_id).await?;
+
+        db::webhook::get_webhooks(&self.db, group_id)
+            .await
+            .map_err(WebhookError::Sqlx)
+    }
+
+    /// The secret of the new webhook is generated here and used to sign every
+    /// delivery.
+    pub async fn create_webhook(
+        &self,
+        actor: &User,
+        webhook: NewWebhook,
+    ) -> Result<Webhook, WebhookError> {
+        self.ensure_member(actor.id, webhook.group_id).await?;
+        validate_webhook(&webhook)?;
+        resolve_public(&webhook.url)
+            .await
+            .map_err(WebhookError::Invalid)?;
+
+        db::webhook::insert_webhook(
+            &self.db,
+            InsertWebhook {
+                group_id: webhook.group_id,
+                url: webhook.url,
+                secret: generate_secret(),
+                events: webhook.events,
+                description: webhook.description,
+                active: webhook.active,
+                created_by: actor.id,
+            },
+        )
+        .await
+        .map_err(WebhookError::Sqlx)
+    }
+
+    /// Replaces a webhook. The group and secret of a webhook can't be changed.
+    pub async fn update_webhook(
+        &self,
+        actor: &User,
+        id: i32,
+        webhook: NewWebhook,
+    ) -> Result<Webhook, WebhookError> {
+        self.get_webhook(actor, id).await?;
+        validate_webhook(&webhook)?;
+        resolve_public(&webhook.url)
+            .await
+            .map_err(WebhookError::Invalid)?;
+
+        db::webhook::update_webhook(
+            &self.db,
+            id,
+            UpdateWebhook {
+                url: webhook.url,
+                events: webhook.events,
+                description: webhook.description,
+                active: webhook.active,
+            },
+        )
+        .await
+        .map_err(WebhookError::Sqlx)
+    }
+
+    pub async fn delete_webhook(&self, actor: &User, id: i32) -> Result<(), WebhookError> {
+        self.get_webhook(actor, id).await?;
+
+        db::webhook::delete_webhook(&self.db, id)
+            .await
+            .map_err(WebhookError::Sqlx)
+    }
+
+    pub async fn get_deliveries(
+        &self,
+        actor: &User,
+        id: i32,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, WebhookError> {
+        self.get_webhook(actor, id).await?;
+
+        db::webhook::get_deliveries(&self.db, id, limit)
+            .await
+            .map_err(WebhookError::Sqlx)
+    }
+
+    /// Sends queued deliveries until they succeed or run out of attempts.
+    /// Deliveries are queued by [`enqueue`] together with the change, so they
+    /// survive restarts. Events published by this instance only wake the
+    /// loop up, those of other instances are picked up when polling.
+    pub fn spawn(self, events: &EventService) {
+        let mut receiver = events.subscribe();
+        let waker = self.queued.clone();
+        tokio::spawn(async move {
+            while let Ok(_) | Err(RecvError::Lagged(_)) = receiver.recv().await {
+                waker.notify_one();
+            }
+        });
+
+        tokio::spawn(async move {
+            loop {
+                match self.deliver_due().await {
+                    // There may be more waiting.
+                    Ok(claimed) if claimed as i64 == BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(webhook_error) => {
+                        event!(Level::ERROR, %webhook_error, "Failed to deliver webhooks");
+                    }
+                }
+
+                tokio::select! {
+                    _ = self.queued.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+        });
+    }
+
+    /// Sends a batch of due deliveries, returning how many were claimed.
+    async fn deliver_due(&self) -> Result<usize, WebhookError> {
+        let due = db::webhook::claim_due_deliveries(&self.db, BATCH_SIZE, LEASE_SECONDS)
+            .await
+            .map_err(WebhookError::Sqlx)?;
+        let claimed = due.len();
+
+        let results =
+            futures::future::join_all(due.iter().map(|delivery| self.deliver(delivery))).await;
+        for (delivery, result) in due.iter().zip(results) {
+            db::webhook::record_delivery_result(&self.db, delivery.id, result)
+                .await
+                .map_err(WebhookError::Sqlx)?;
+        }
+
+        Ok(claimed)
+    }
+
+    async fn deliver(&self, delivery: &DueDelivery) -> DeliveryResult {
+        let timestamp = Utc::now().timestamp();
+        let signature = sign(&delivery.secret, timestamp, &delivery.payload);
+
+        // Resolved again for every delivery, as where a host points can
+        // change after the webhook was created.
+        let client = match resolve_public(&delivery.url).await {
+            Ok((host, addrs)) => reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .user_agent("jostrid-webhooks")
+                .redirect(Policy::none())
+                .resolve_to_addrs(&host, &addrs)
+                .build()
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        };
+        let response = match client {
+            Ok(client) => client
+                .post(&delivery.url)
+                .header("Content-Type", "application/json")
+                .header("X-Jostrid-Event", &delivery.event)
+                .header("X-Jostrid-Delivery", delivery.id.to_string())
+                .header(
+                    "X-Jostrid-Signature",
+                    format!("t={},v1={}", timestamp, signature),
+                )
+                .body(delivery.payload.clone())
+                .send()
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        };
+
+        let (response_status, error) = match response {
+            Ok(response) if response.status().is_success() => {
+                return DeliveryResult {
+                    status: "delivered".to_string(),
+                    response_status: Some(response.status().as_u16() as i32),
+                    last_error: None,
+                    next_attempt_at: None,
+                };
+            }
+            // The body is not kept, it is shown to the group's members and
+            // could leak what the receiver didn't mean to.
+            Ok(response) => (
+                Some(response.status().as_u16() as i32),
+                response.status().to_string(),
+            ),
+            Err(err) => (None, err),
+        };
+
+        event!(
+            Level::WARN,
+            delivery.id,
+            delivery.webhook_id,
+            delivery.attempts,
+            error,
+            "Webhook delivery failed"
+        );
+        let last_error = Some(error.chars().take(MAX_ERROR_LENGTH).collect());
+
+        if delivery.attempts >= MAX_ATTEMPTS {
+            DeliveryResult {
+                status: "failed".to_string(),
+                response_status,
+                last_error,
+                next_attempt_at: None,
+            }
+        } else {
+            DeliveryResult {
+                status: "pending".to_string(),
+                response_status,
+                last_error,
+                next_attempt_at: Some(Utc::now() + retry_delay(delivery.attempts)),
+            }
+        }
+    }
+
+    async fn get_webhook(&self, actor: &User, id: i32) -> Result<Webhook, WebhookError> {
+        let webhook = db::webhook::get_webhook(&self.db, id)
+            .await
+            .map_err(WebhookError::Sqlx)?
+            .ok_or(WebhookError::NotFound(id))?;
+        self.ensure_member(actor.id, webhook.group_id).await?;
+
+        Ok(webhook)
+    }
+
+    async fn ensure_member(&self, user_id: i32, group_id: i32) -> Result<(), WebhookError> {
+        let is_member = db::group::is_member(&self.db, group_id, user_id)
+            .await
+            .map_err(WebhookError::Sqlx)?;
+
+        if is_member {
+            Ok(())
+        } else {
+            Err(WebhookError::NotMember(user_id, group_id))
+        }
+    }
+}
+
+/// Queues a delivery of the event to every subscribed webhook of its group.
+/// Called in the transaction that makes the change, so that a delivery is
+/// queued if and only if the change is committed.
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    ledger_event: &LedgerEvent,
+) -> Result<(), sqlx::Error> {
+    let webhooks = db::webhook::get_subscribed_webhooks(
+        &mut *conn,
+        ledger_event.group_id(),
+        ledger_event.name(),
+    )
+    .await?;
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let expense = db::expense::get_expense(ledger_event.expense_id(), &mut *conn)
+        .await?
+        .map(|(expense, shares)| WebhookExpense {
+            expense: expense.expense,
+            paid_by: expense.paid_by,
+            category: expense.category,
+            shares,
+        });
+    let payload = serde_json::to_string(&WebhookPayload {
+        event: ledger_event.name(),
+        group_id: ledger_event.group_id(),
+        occurred_at: Utc::now(),
+        data: ledger_event,
+        expense,
+    })
+    .expect("Webhook payloads serialize");
+
+    for webhook in webhooks {
+        db::webhook::insert_delivery(&mut *conn, webhook.id, ledger_event.name(), &payload).await?;
+    }
+
+    Ok(())
+}
+
+/// Resolves the host of the URL, refusing it unless every address it
+/// resolves to is public, so that webhooks and push endpoints can't reach
+/// the server's own network. Returns the host and the addresses to connect
+/// to.
+pub(crate) async fn resolve_public(url: &str) -> Result<(String, Vec<SocketAddr>), String> {
+    let url = Url::parse(url).map_err(|err| format!("'{}' is not a valid URL: {}", url, err))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("'{}' has no host", url))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    // IPv6 hosts come in brackets.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addrs: Vec<SocketAddr> = lookup_host((host, port))
+        .await
+        .map_err(|err| format!("can't resolve '{}': {}", host, err))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("'{}' doesn't resolve to any address", host));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        return Err(format!(
+            "'{}' resolves to {}, which is not a public address",
+            host,
+            addr.ip()
+        ));
+    }
+
+    Ok((host.to_string(), addrs))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space of carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local, fc00::/7.
+                    || (first & 0xfe00) == 0xfc00
+                    // Link-local, fe80::/10.
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+fn validate_webhook(webhook: &NewWebhook) -> Result<(), WebhookError> {
+    let is_http = reqwest::Url::parse(&webhook.url)
+        .is_ok_and(|url| url.scheme() == "https" || url.scheme() == "http");
+    if !is_http {
+        return Err(WebhookError::Invalid(format!(
+            "'{}' is not an http(s) URL",
+            webhook.url
+        )));
+    }
+
+    if webhook.events.is_empty() {
+        return Err(WebhookError::Invalid(
+            "events must not be empty".to_string(),
+        ));
+    }
+    if let Some(unknown) = webhook
+        .events
+        .iter()
+        .find(|event| !WEBHOOK_EVENTS.contains(&event.as_str()))
+    {
+        return Err(WebhookError::Invalid(format!(
+            "unknown event '{}', expected one of {}",
+            unknown,
+            WEBHOOK_EVENTS.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Doubles with every attempt: 30 seconds after the first, an hour after the
+/// eighth.
+fn retry_delay(attempts: i32) -> chrono::Duration {
+    chrono::Duration::seconds(RETRY_BASE_SECONDS << (attempts - 1).clamp(0, 16))
+}
+
+fn generate_secret() -> String {
+    let mut secret = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .expect("System randomness is available");
+
+    hex(&secret)
+}
+
+/// HMAC-SHA256 over `{timestamp}.{payload}`, hex encoded. Receivers should
+/// compute the same and reject old timestamps to prevent replays.
+fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let signed = format!("{}.{}", timestamp, payload);
+
+    hex(hmac::sign(&key, signed.as_bytes()).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}